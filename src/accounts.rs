@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::config::Config;
+
+const ACCOUNTS_FILE: &str = "account_status.json";
+
+pub struct AccountStatus {
+    path: PathBuf,
+    disabled: RwLock<HashMap<String, bool>>,
+}
+
+impl AccountStatus {
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(ACCOUNTS_FILE);
+        let disabled = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read account status: {:?}", path))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse account status: {:?}", path))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path,
+            disabled: RwLock::new(disabled),
+        })
+    }
+
+    pub fn is_disabled(&self, config: &Config, username: &str) -> bool {
+        self.disabled
+            .read()
+            .ok()
+            .and_then(|disabled| disabled.get(username).copied())
+            .unwrap_or_else(|| config.users.get(username).is_some_and(|user| user.disabled))
+    }
+
+    pub fn set_disabled(&self, username: &str, disabled: bool) -> Result<()> {
+        let mut overrides = self
+            .disabled
+            .write()
+            .map_err(|_| anyhow::anyhow!("Account status lock poisoned"))?;
+        overrides.insert(username.to_string(), disabled);
+        let content = serde_json::to_string_pretty(&*overrides)?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to save account status: {:?}", self.path))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tera::Value;
+
+use crate::urls;
+
+pub const IMMUTABLE_CACHE: &str = "public, max-age=31536000, immutable";
+const FINGERPRINT_LEN: usize = 16;
+
+pub struct AssetManifest {
+    root: PathBuf,
+    hashes: RwLock<HashMap<String, String>>,
+}
+
+impl AssetManifest {
+    pub fn load(root: &Path) -> Result<Self> {
+        let manifest = Self {
+            root: root.to_path_buf(),
+            hashes: RwLock::new(HashMap::new()),
+        };
+        manifest.reload()?;
+        Ok(manifest)
+    }
+
+    pub fn reload(&self) -> Result<()> {
+        let mut hashes = HashMap::new();
+        if self.root.is_dir() {
+            let mut pending = vec![self.root.clone()];
+            while let Some(dir) = pending.pop() {
+                for entry in fs::read_dir(&dir)
+                    .with_context(|| format!("Failed to read static directory: {:?}", dir))?
+                {
+                    let path = entry?.path();
+                    if path.is_dir() {
+                        pending.push(path);
+                    } else if let Ok(relative) = path.strip_prefix(&self.root) {
+                        let content = fs::read(&path)
+                            .with_context(|| format!("Failed to read static asset: {:?}", path))?;
+                        let digest = format!("{:x}", Sha256::digest(&content));
+                        hashes.insert(
+                            crate::pathbuf_to_string(relative),
+                            digest[..FINGERPRINT_LEN].to_string(),
+                        );
+                    }
+                }
+            }
+        }
+        *self
+            .hashes
+            .write()
+            .map_err(|_| anyhow::anyhow!("Asset manifest lock poisoned"))? = hashes;
+        Ok(())
+    }
+
+    pub fn fingerprint(&self, path: &str) -> Option<String> {
+        self.hashes.read().ok()?.get(path).cloned()
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        match self.fingerprint(path) {
+            Some(hash) => format!("/static/{}?v={}", urls::path(path), hash),
+            None => format!("/static/{}", urls::path(path)),
+        }
+    }
+
+    pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let relative = Path::new(path);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return None;
+        }
+        self.fingerprint(path)?;
+        Some(self.root.join(relative))
+    }
+}
+
+pub fn url_function(
+    manifest: Arc<AssetManifest>,
+) -> impl Fn(&HashMap<String, Value>) -> tera::Result<Value> + Send + Sync {
+    move |args| {
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| tera::Error::msg("Function `static_url` expects a `path` argument"))?;
+        Ok(Value::String(manifest.url(path)))
+    }
+}
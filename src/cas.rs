@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::duplicates;
+
+pub const BLOB_DIR: &str = ".lunafinder_blobs";
+
+pub fn blob_root(base: &Path) -> PathBuf {
+    base.join(BLOB_DIR)
+}
+
+pub fn store(base: &Path, file: &Path) -> Result<bool> {
+    let hash = duplicates::hash_file(file)?;
+    let blob = blob_root(base).join(&hash[..2]).join(&hash);
+    if blob.is_file() {
+        duplicates::replace_with_link(&blob, file)?;
+        return Ok(true);
+    }
+
+    if let Some(shard) = blob.parent() {
+        fs::create_dir_all(shard)
+            .with_context(|| format!("Failed to create blob directory: {:?}", shard))?;
+    }
+    fs::hard_link(file, &blob).with_context(|| format!("Failed to store blob for {:?}", file))?;
+    Ok(false)
+}
+
+pub fn collect_garbage(base: &Path) -> Result<usize> {
+    let root = blob_root(base);
+    if !root.is_dir() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for shard in fs::read_dir(&root).with_context(|| format!("Failed to read {:?}", root))? {
+        let shard = shard?.path();
+        if !shard.is_dir() {
+            continue;
+        }
+        for blob in fs::read_dir(&shard).with_context(|| format!("Failed to read {:?}", shard))? {
+            let blob = blob?;
+            if is_unreferenced(&blob.metadata()?) {
+                fs::remove_file(blob.path())
+                    .with_context(|| format!("Failed to remove blob {:?}", blob.path()))?;
+                removed += 1;
+            }
+        }
+        let _ = fs::remove_dir(&shard);
+    }
+    Ok(removed)
+}
+
+#[cfg(unix)]
+fn is_unreferenced(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.is_file() && metadata.nlink() <= 1
+}
+
+#[cfg(not(unix))]
+fn is_unreferenced(_metadata: &fs::Metadata) -> bool {
+    false
+}
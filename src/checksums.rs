@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::storage::{self, Codec};
+
+pub const SIDECAR_EXTENSION: &str = "sha256";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Missing,
+    Mismatch,
+    Orphaned,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChecksumIssue {
+    pub path: String,
+    #[serde(skip)]
+    file: PathBuf,
+    pub status: Status,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub checked: usize,
+    pub issues: Vec<ChecksumIssue>,
+}
+
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == SIDECAR_EXTENSION)
+}
+
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(SIDECAR_EXTENSION);
+    path.with_file_name(name)
+}
+
+pub fn generate(codec: Codec, path: &Path) -> Result<String> {
+    let hash = content_hash(codec, path)?;
+    write_sidecar(path, &hash)?;
+    Ok(hash)
+}
+
+pub fn verify(base: &Path, codec: Codec) -> Result<Report> {
+    let mut report = Report::default();
+    walk(base, Path::new(""), codec, &mut report)?;
+    report.issues.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+pub fn refresh(base: &Path, codec: Codec) -> Result<usize> {
+    let report = verify(base, codec)?;
+    let mut updated = 0;
+    for issue in &report.issues {
+        match (issue.status, &issue.actual) {
+            (Status::Orphaned, _) => fs::remove_file(&issue.file)
+                .with_context(|| format!("Failed to remove {:?}", issue.path))?,
+            (_, Some(hash)) => write_sidecar(&issue.file, hash)?,
+            (_, None) => continue,
+        }
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+fn walk(base: &Path, relative: &Path, codec: Codec, report: &mut Report) -> Result<()> {
+    let current = base.join(relative);
+    let listing = match fs::read_dir(&current) {
+        Ok(listing) => listing,
+        Err(e) if relative.as_os_str().is_empty() => {
+            return Err(e).with_context(|| format!("Failed to read directory: {:?}", current))
+        }
+        Err(e) => {
+            log::debug!("Skipping {:?}: {}", current, e);
+            return Ok(());
+        }
+    };
+    for entry in listing {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let name = entry.file_name();
+        if crate::is_reserved_name(&name.to_string_lossy()) {
+            continue;
+        }
+        let child_relative = relative.join(&name);
+        let path = entry.path();
+        let Some(metadata) = crate::contained_metadata(base, &entry) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            walk(base, &child_relative, codec, report)?;
+            continue;
+        }
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let encoded = crate::osname::encode_path(&child_relative);
+        if is_sidecar(&path) {
+            if !path.with_extension("").is_file() {
+                report.issues.push(ChecksumIssue {
+                    path: encoded,
+                    file: path,
+                    status: Status::Orphaned,
+                    expected: None,
+                    actual: None,
+                });
+            }
+            continue;
+        }
+
+        report.checked += 1;
+        let expected = read_sidecar(&sidecar_path(&path));
+        let actual = match content_hash(codec, &path) {
+            Ok(actual) => actual,
+            Err(e) => {
+                log::debug!("Skipping {:?}: {}", path, e);
+                continue;
+            }
+        };
+        let status = match &expected {
+            None => Status::Missing,
+            Some(expected) if *expected != actual => Status::Mismatch,
+            Some(_) => continue,
+        };
+        report.issues.push(ChecksumIssue {
+            path: encoded,
+            file: path,
+            status,
+            expected,
+            actual: Some(actual),
+        });
+    }
+    Ok(())
+}
+
+fn content_hash(codec: Codec, path: &Path) -> Result<String> {
+    let (mut reader, _) = storage::open_reader(codec, path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher).with_context(|| format!("Failed to hash {:?}", path))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn read_sidecar(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let hash = content.split_whitespace().next()?.to_ascii_lowercase();
+    (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())).then_some(hash)
+}
+
+fn write_sidecar(path: &Path, hash: &str) -> Result<()> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let sidecar = sidecar_path(path);
+    fs::write(&sidecar, format!("{}  {}\n", hash, name))
+        .with_context(|| format!("Failed to write checksum: {:?}", sidecar))
+}
@@ -0,0 +1,27 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(name = "lunafinder", version, about = "A modern file browser")]
+pub struct Cli {
+    #[arg(long, env = "LUNAFINDER_CONFIG", default_value = "config.toml")]
+    pub config: PathBuf,
+
+    #[arg(long, env = "LUNAFINDER_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    #[arg(long)]
+    pub host: Option<String>,
+
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    #[arg(long)]
+    pub read_only: bool,
+
+    #[arg(long)]
+    pub dev: bool,
+}
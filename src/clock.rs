@@ -0,0 +1,122 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::value::{Datetime, Offset};
+
+pub fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn datetime_to_unix(datetime: &Datetime) -> Option<u64> {
+    let date = datetime.date?;
+    let days = days_from_civil(date.year as i64, date.month as i64, date.day as i64);
+
+    let seconds_of_day = datetime
+        .time
+        .map(|time| time.hour as i64 * 3600 + time.minute as i64 * 60 + time.second as i64)
+        .unwrap_or(0);
+
+    let offset_seconds = match datetime.offset {
+        Some(Offset::Custom { minutes }) => minutes as i64 * 60,
+        Some(Offset::Z) | None => 0,
+    };
+
+    let timestamp = days * 86_400 + seconds_of_day - offset_seconds;
+    u64::try_from(timestamp).ok()
+}
+
+pub fn parse_date(value: &str) -> Option<u64> {
+    let mut parts = value.trim().splitn(3, '-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<i64>().ok()?;
+    let day = parts.next()?.parse::<i64>().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    u64::try_from(days_from_civil(year, month, day) * 86_400).ok()
+}
+
+pub fn parse_amz_date(value: &str) -> Option<u64> {
+    if value.len() != 16 || !value.ends_with('Z') || value.as_bytes()[8] != b'T' {
+        return None;
+    }
+    let days = parse_date(&format!(
+        "{}-{}-{}",
+        &value[0..4],
+        &value[4..6],
+        &value[6..8]
+    ))?;
+    let hour = value[9..11].parse::<u64>().ok()?;
+    let minute = value[11..13].parse::<u64>().ok()?;
+    let second = value[13..15].parse::<u64>().ok()?;
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    Some(days + hour * 3600 + minute * 60 + second)
+}
+
+pub fn format_iso8601(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds_of_day = timestamp % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.000Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+pub fn format_file_stamp(timestamp: u64) -> String {
+    let seconds_of_day = timestamp % 86_400;
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+pub fn format_list_date(timestamp: u64) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    format!("{} {:>2}  {}", MONTHS[(month - 1) as usize], day, year)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -1,4 +1,4 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use serde::de::{self, Deserializer, SeqAccess};
 use serde::ser::SerializeSeq;
 use serde::{Deserialize, Serialize};
@@ -6,7 +6,12 @@ use std::collections::{BTreeSet, HashMap};
 use std::fmt;
 use std::fs;
 use std::mem;
-use std::path::{Path, PathBuf};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use toml::value::Datetime;
+
+use crate::pattern;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -18,12 +23,263 @@ pub struct Config {
     pub mounts: HashMap<String, MountConfig>,
     #[serde(default)]
     pub permissions: HashMap<String, PermissionProfile>,
+    #[serde(default)]
+    pub scan: Option<ScanConfig>,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub branding: BrandingConfig,
+    #[serde(default)]
+    pub pages: PagesConfig,
+    #[serde(default)]
+    pub negotiate: Option<NegotiateConfig>,
+    #[serde(default)]
+    pub notifications: Option<NotificationsConfig>,
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+    #[serde(default)]
+    pub ftp: Option<FtpConfig>,
+    #[serde(default)]
+    pub metrics: Option<MetricsConfig>,
+    #[serde(default)]
+    pub security: Option<SecurityConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    #[serde(default)]
+    pub bind: Vec<String>,
+    #[serde(default = "default_templates_dir")]
+    pub templates_dir: PathBuf,
+    #[serde(default = "default_static_dir")]
+    pub static_dir: PathBuf,
+    #[serde(default)]
+    pub data_dir: Option<PathBuf>,
+    #[serde(default = "default_http2")]
+    pub http2: bool,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub dev: bool,
+    #[serde(default)]
+    pub nested_mounts: NestedMountPolicy,
+    #[serde(default)]
+    pub maintenance: bool,
+    #[serde(default)]
+    pub maintenance_message: Option<String>,
+    #[serde(default)]
+    pub url_signing_secret: Option<String>,
+    #[serde(default)]
+    pub session_secret: Option<String>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub blocking: BlockingConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub confirm: ConfirmConfig,
+    #[serde(default)]
+    pub tuning: TuningConfig,
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+    #[serde(default)]
+    pub uploads: UploadsConfig,
+}
+
+pub const DEFAULT_UPLOAD_QUEUE_CHUNKS: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadsConfig {
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
+    #[serde(default = "default_upload_queue_chunks")]
+    pub queue_chunks: usize,
+}
+
+impl Default for UploadsConfig {
+    fn default() -> Self {
+        UploadsConfig {
+            temp_dir: None,
+            queue_chunks: DEFAULT_UPLOAD_QUEUE_CHUNKS,
+        }
+    }
+}
+
+fn default_upload_queue_chunks() -> usize {
+    DEFAULT_UPLOAD_QUEUE_CHUNKS
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimeoutsConfig {
+    #[serde(default)]
+    pub request_secs: Option<u64>,
+    #[serde(default)]
+    pub upload_secs: Option<u64>,
+    #[serde(default)]
+    pub slow_request_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TuningConfig {
+    #[serde(default)]
+    pub workers: Option<usize>,
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    #[serde(default)]
+    pub keep_alive_secs: Option<u64>,
+    #[serde(default)]
+    pub client_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfirmConfig {
+    #[serde(default)]
+    pub entries: Option<u64>,
+    #[serde(default)]
+    pub bytes: Option<u64>,
+}
+
+impl ConfirmConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.entries.is_some() || self.bytes.is_some()
+    }
+
+    pub fn exceeded_by(&self, summary: &crate::confirm::Summary) -> bool {
+        self.entries.is_some_and(|limit| summary.entries > limit)
+            || self.bytes.is_some_and(|limit| summary.bytes > limit)
+    }
+}
+
+pub const DEFAULT_MAX_FORM_SIZE: usize = 1024 * 1024;
+pub const DEFAULT_MAX_PAYLOAD_SIZE: usize = 256 * 1024;
+pub const DEFAULT_MAX_LINK_HOURS: u64 = 24 * 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    #[serde(default = "default_max_form_size")]
+    pub max_form_size: usize,
+    #[serde(default = "default_max_payload_size")]
+    pub max_payload_size: usize,
+    #[serde(default)]
+    pub max_request_size: Option<u64>,
+    #[serde(default)]
+    pub max_field_size: Option<u64>,
+    #[serde(default)]
+    pub max_files: Option<usize>,
+    #[serde(default)]
+    pub max_upload_size: Option<u64>,
+    #[serde(default = "default_max_link_hours")]
+    pub max_link_hours: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_form_size: DEFAULT_MAX_FORM_SIZE,
+            max_payload_size: DEFAULT_MAX_PAYLOAD_SIZE,
+            max_request_size: None,
+            max_field_size: None,
+            max_files: None,
+            max_upload_size: None,
+            max_link_hours: DEFAULT_MAX_LINK_HOURS,
+        }
+    }
+}
+
+fn default_max_form_size() -> usize {
+    DEFAULT_MAX_FORM_SIZE
+}
+
+fn default_max_payload_size() -> usize {
+    DEFAULT_MAX_PAYLOAD_SIZE
+}
+
+fn default_max_link_hours() -> u64 {
+    DEFAULT_MAX_LINK_HOURS
+}
+
+impl ServerConfig {
+    pub fn listen_addresses(&self) -> Result<Vec<SocketAddr>> {
+        let mut addresses = Vec::new();
+        if self.bind.is_empty() {
+            addresses.extend(
+                (self.host.as_str(), self.port)
+                    .to_socket_addrs()
+                    .with_context(|| {
+                        format!("Invalid listen address: {}:{}", self.host, self.port)
+                    })?,
+            );
+        } else {
+            for bind in &self.bind {
+                addresses.extend(
+                    bind.to_socket_addrs()
+                        .with_context(|| format!("Invalid listen address: {}", bind))?,
+                );
+            }
+        }
+        Ok(addresses)
+    }
+}
+
+pub const DEFAULT_DATA_DIR: &str = "data";
+
+fn default_templates_dir() -> PathBuf {
+    PathBuf::from("templates")
+}
+
+fn default_static_dir() -> PathBuf {
+    PathBuf::from("static")
+}
+
+fn default_http2() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
+}
+
+pub const DEFAULT_BLOCKING_MAX_JOBS: usize = 4;
+pub const DEFAULT_BLOCKING_TIMEOUT_SECS: u64 = 300;
+pub const DEFAULT_BACKGROUND_DELETE_ENTRIES: u64 = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockingConfig {
+    #[serde(default = "default_blocking_max_jobs")]
+    pub max_jobs: usize,
+    #[serde(default = "default_blocking_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_background_delete_entries")]
+    pub background_delete_entries: u64,
+}
+
+impl Default for BlockingConfig {
+    fn default() -> Self {
+        BlockingConfig {
+            max_jobs: DEFAULT_BLOCKING_MAX_JOBS,
+            timeout_secs: DEFAULT_BLOCKING_TIMEOUT_SECS,
+            background_delete_entries: DEFAULT_BACKGROUND_DELETE_ENTRIES,
+        }
+    }
+}
+
+fn default_blocking_max_jobs() -> usize {
+    DEFAULT_BLOCKING_MAX_JOBS
+}
+
+fn default_blocking_timeout_secs() -> u64 {
+    DEFAULT_BLOCKING_TIMEOUT_SECS
+}
+
+fn default_background_delete_entries() -> u64 {
+    DEFAULT_BACKGROUND_DELETE_ENTRIES
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +287,301 @@ pub struct MainPageConfig {
     pub title: String,
     pub description: String,
     pub markdown_file: String,
+    #[serde(default)]
+    pub announcement: Option<Announcement>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Announcement {
+    pub message: String,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub expires_at: Option<Datetime>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BrandingConfig {
+    #[serde(default)]
+    pub instance_name: Option<String>,
+    #[serde(default)]
+    pub logo: Option<PathBuf>,
+    #[serde(default)]
+    pub login_markdown: Option<PathBuf>,
+}
+
+pub const DEFAULT_TERMS_VERSION: &str = "1";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PagesConfig {
+    #[serde(default)]
+    pub about: Option<PathBuf>,
+    #[serde(default)]
+    pub terms: Option<PathBuf>,
+    #[serde(default)]
+    pub privacy: Option<PathBuf>,
+    #[serde(default)]
+    pub footer_text: Option<String>,
+    #[serde(default)]
+    pub require_terms_acceptance: bool,
+    #[serde(default = "default_terms_version")]
+    pub terms_version: String,
+}
+
+impl Default for PagesConfig {
+    fn default() -> Self {
+        PagesConfig {
+            about: None,
+            terms: None,
+            privacy: None,
+            footer_text: None,
+            require_terms_acceptance: false,
+            terms_version: DEFAULT_TERMS_VERSION.to_string(),
+        }
+    }
+}
+
+fn default_terms_version() -> String {
+    DEFAULT_TERMS_VERSION.to_string()
+}
+
+impl PagesConfig {
+    pub fn terms_gate_enabled(&self) -> bool {
+        self.require_terms_acceptance && self.terms.is_some()
+    }
+
+    pub fn get(&self, page: &str) -> Option<&PathBuf> {
+        match page {
+            "about" => self.about.as_ref(),
+            "terms" => self.terms.as_ref(),
+            "privacy" => self.privacy.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_LISTING_PER_PAGE: usize = 200;
+pub const DEFAULT_LISTING_MAX_PER_PAGE: usize = 1000;
+pub const DEFAULT_MAX_HIGHLIGHT_SIZE: u64 = 512 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayConfig {
+    #[serde(default)]
+    pub size_units: SizeUnits,
+    #[serde(default = "default_listing_per_page")]
+    pub per_page: usize,
+    #[serde(default = "default_listing_max_per_page")]
+    pub max_per_page: usize,
+    #[serde(default = "default_max_highlight_size")]
+    pub max_highlight_size: u64,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            size_units: SizeUnits::default(),
+            per_page: DEFAULT_LISTING_PER_PAGE,
+            max_per_page: DEFAULT_LISTING_MAX_PER_PAGE,
+            max_highlight_size: DEFAULT_MAX_HIGHLIGHT_SIZE,
+        }
+    }
+}
+
+fn default_listing_per_page() -> usize {
+    DEFAULT_LISTING_PER_PAGE
+}
+
+fn default_listing_max_per_page() -> usize {
+    DEFAULT_LISTING_MAX_PER_PAGE
+}
+
+fn default_max_highlight_size() -> u64 {
+    DEFAULT_MAX_HIGHLIGHT_SIZE
+}
+
+pub const DEFAULT_SEARCH_MAX_DEPTH: usize = 16;
+pub const DEFAULT_SEARCH_MAX_RESULTS: usize = 200;
+pub const DEFAULT_SEARCH_INDEX_INTERVAL_SECS: u64 = 900;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchConfig {
+    #[serde(default = "default_search_max_depth")]
+    pub max_depth: usize,
+    #[serde(default = "default_search_max_results")]
+    pub max_results: usize,
+    #[serde(default = "default_search_index_interval_secs")]
+    pub index_interval_secs: u64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            max_depth: DEFAULT_SEARCH_MAX_DEPTH,
+            max_results: DEFAULT_SEARCH_MAX_RESULTS,
+            index_interval_secs: DEFAULT_SEARCH_INDEX_INTERVAL_SECS,
+        }
+    }
+}
+
+fn default_search_max_depth() -> usize {
+    DEFAULT_SEARCH_MAX_DEPTH
+}
+
+fn default_search_max_results() -> usize {
+    DEFAULT_SEARCH_MAX_RESULTS
+}
+
+fn default_search_index_interval_secs() -> u64 {
+    DEFAULT_SEARCH_INDEX_INTERVAL_SECS
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NestedMountPolicy {
+    #[default]
+    Reject,
+    Warn,
+    Inherit,
+}
+
+pub struct NestedMount {
+    pub inner: String,
+    pub outer: String,
+    pub prefix: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeUnits {
+    #[default]
+    Binary,
+    Decimal,
+}
+
+impl SizeUnits {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SizeUnits::Binary => "binary",
+            SizeUnits::Decimal => "decimal",
+        }
+    }
+}
+
+impl FromStr for SizeUnits {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "binary" | "iec" => Ok(SizeUnits::Binary),
+            "decimal" | "si" => Ok(SizeUnits::Decimal),
+            other => Err(anyhow!("Unknown size units: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanConfig {
+    pub command: Vec<String>,
+}
+
+pub const DEFAULT_NOTIFICATION_BATCH_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    #[serde(default = "default_notification_batch_secs")]
+    pub batch_secs: u64,
+    #[serde(default)]
+    pub sendmail: Option<PathBuf>,
+    #[serde(default)]
+    pub from: Option<String>,
+}
+
+fn default_notification_batch_secs() -> u64 {
+    DEFAULT_NOTIFICATION_BATCH_SECS
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Config {
+    #[serde(default)]
+    pub keys: Vec<S3Key>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Key {
+    pub access_key: String,
+    pub secret_key: String,
+    pub user: String,
+}
+
+impl S3Config {
+    pub fn key(&self, access_key: &str) -> Option<&S3Key> {
+        self.keys.iter().find(|key| key.access_key == access_key)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FtpConfig {
+    #[serde(default = "default_ftp_bind")]
+    pub bind: String,
+    #[serde(default)]
+    pub passive_ports: Option<[u16; 2]>,
+    #[serde(default)]
+    pub public_address: Option<IpAddr>,
+    #[serde(default)]
+    pub allow_plaintext: bool,
+}
+
+fn default_ftp_bind() -> String {
+    "0.0.0.0:2121".to_string()
+}
+
+pub const DEFAULT_FAILED_LOGIN_THRESHOLD: u32 = 5;
+pub const DEFAULT_DENIED_THRESHOLD: u32 = 30;
+pub const DEFAULT_SECURITY_WINDOW_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    #[serde(default = "default_failed_login_threshold")]
+    pub failed_logins: u32,
+    #[serde(default = "default_denied_threshold")]
+    pub denied: u32,
+    #[serde(default = "default_security_window_secs")]
+    pub window_secs: u64,
+}
+
+fn default_failed_login_threshold() -> u32 {
+    DEFAULT_FAILED_LOGIN_THRESHOLD
+}
+
+fn default_denied_threshold() -> u32 {
+    DEFAULT_DENIED_THRESHOLD
+}
+
+fn default_security_window_secs() -> u64 {
+    DEFAULT_SECURITY_WINDOW_SECS
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NegotiateConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub principal: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +589,16 @@ pub struct UserConfig {
     pub password: String,
     pub group: Vec<String>,
     pub hash_algorithm: String,
+    #[serde(default)]
+    pub size_units: Option<SizeUnits>,
+    #[serde(default)]
+    pub webhook: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub max_upload_size: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,9 +608,139 @@ pub struct MountConfig {
     #[serde(default)]
     pub public: bool,
     #[serde(default)]
+    pub public_upload: bool,
+    #[serde(default)]
+    pub moderation: bool,
+    #[serde(default)]
+    pub allow_content: Vec<String>,
+    #[serde(default)]
+    pub deny_content: Vec<String>,
+    #[serde(default)]
+    pub tree: TreeConfig,
+    #[serde(default)]
+    pub releases: ReleasesConfig,
+    #[serde(default)]
     pub group: HashMap<String, PermissionSpec>,
     #[serde(default)]
     pub user: HashMap<String, PermissionSpec>,
+    #[serde(default)]
+    pub rules: Vec<PermissionRule>,
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+    #[serde(default)]
+    pub dedup: bool,
+    #[serde(default)]
+    pub compression: bool,
+    #[serde(default)]
+    pub trash: bool,
+    #[serde(default)]
+    pub checksums: bool,
+    #[serde(default)]
+    pub fulltext: bool,
+    #[serde(default)]
+    pub gitignore: bool,
+    #[serde(default)]
+    pub wiki: bool,
+    #[serde(default)]
+    pub folder_readme: bool,
+    #[serde(default)]
+    pub folder_readme_template: Option<PathBuf>,
+    #[serde(default)]
+    pub max_upload_size: Option<u64>,
+    #[serde(default)]
+    pub order: i64,
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub group: HashMap<String, PermissionSpec>,
+    #[serde(default)]
+    pub user: HashMap<String, PermissionSpec>,
+}
+
+impl PermissionRule {
+    pub fn applies_to(&self, relative: &str) -> bool {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        pattern::matches(&self.pattern, name) || pattern::matches(&self.pattern, relative)
+    }
+}
+
+pub const DEFAULT_TREE_MAX_DEPTH: usize = 12;
+pub const DEFAULT_TREE_MAX_CHILDREN: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeConfig {
+    #[serde(default = "default_tree_max_depth")]
+    pub max_depth: usize,
+    #[serde(default = "default_tree_max_children")]
+    pub max_children: usize,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        TreeConfig {
+            max_depth: DEFAULT_TREE_MAX_DEPTH,
+            max_children: DEFAULT_TREE_MAX_CHILDREN,
+            exclude: Vec::new(),
+        }
+    }
+}
+
+fn default_tree_max_depth() -> usize {
+    DEFAULT_TREE_MAX_DEPTH
+}
+
+fn default_tree_max_children() -> usize {
+    DEFAULT_TREE_MAX_CHILDREN
+}
+
+pub const DEFAULT_RELEASES_VERSIONS: usize = 5;
+pub const DEFAULT_RELEASES_LINK_HOURS: u64 = 24;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleasesConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_releases_versions")]
+    pub versions: usize,
+    #[serde(default = "default_releases_link_hours")]
+    pub link_hours: u64,
+}
+
+impl Default for ReleasesConfig {
+    fn default() -> Self {
+        ReleasesConfig {
+            enabled: false,
+            versions: DEFAULT_RELEASES_VERSIONS,
+            link_hours: DEFAULT_RELEASES_LINK_HOURS,
+        }
+    }
+}
+
+fn default_releases_versions() -> usize {
+    DEFAULT_RELEASES_VERSIONS
+}
+
+fn default_releases_link_hours() -> u64 {
+    DEFAULT_RELEASES_LINK_HOURS
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -57,6 +748,8 @@ pub struct Permission {
     actions: BTreeSet<String>,
 }
 
+type PermissionCheck = (&'static str, fn(&Permission) -> bool);
+
 impl Permission {
     pub fn from_actions<I, S>(actions: I) -> Self
     where
@@ -124,16 +817,52 @@ impl Permission {
         self.allows_any(&["modify", "write"])
     }
 
-    #[allow(dead_code)]
+    pub fn allows_moderate(&self) -> bool {
+        self.allows_action("moderate")
+    }
+
     pub fn allows_create_file(&self) -> bool {
         self.allows_any(&["create_file", "write"])
     }
 
-    #[allow(dead_code)]
     pub fn allows_create_folder(&self) -> bool {
         self.allows_any(&["create_folder", "write"])
     }
 
+    pub fn permits(&self, action: &str) -> bool {
+        match action.to_lowercase().as_str() {
+            "read" => self.allows_read(),
+            "write" => self.allows_write(),
+            "upload" => self.allows_upload(),
+            "delete" => self.allows_delete(),
+            "rename" => self.allows_rename(),
+            "modify" => self.allows_modify(),
+            "moderate" => self.allows_moderate(),
+            "create_file" => self.allows_create_file(),
+            "create_folder" => self.allows_create_folder(),
+            other => self.allows_action(other),
+        }
+    }
+
+    pub fn restrict_to(&self, limit: &Permission) -> Permission {
+        let checks: [PermissionCheck; 8] = [
+            ("read", Permission::allows_read),
+            ("upload", Permission::allows_upload),
+            ("delete", Permission::allows_delete),
+            ("rename", Permission::allows_rename),
+            ("modify", Permission::allows_modify),
+            ("moderate", Permission::allows_moderate),
+            ("create_file", Permission::allows_create_file),
+            ("create_folder", Permission::allows_create_folder),
+        ];
+        Permission::from_actions(
+            checks
+                .iter()
+                .filter(|(_, allows)| allows(self) && allows(limit))
+                .map(|(action, _)| *action),
+        )
+    }
+
     pub fn actions(&self) -> Vec<String> {
         self.actions.iter().cloned().collect()
     }
@@ -255,6 +984,8 @@ impl serde::ser::Serialize for PermissionSpec {
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PermissionProfile {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    inherit: Vec<String>,
     #[serde(flatten)]
     actions: HashMap<String, bool>,
 }
@@ -262,12 +993,13 @@ pub struct PermissionProfile {
 impl PermissionProfile {
     fn normalized(self) -> Self {
         let mut normalized = HashMap::new();
-        for (action, allowed) in self.actions {
-            let key = action.to_lowercase();
-            let entry = normalized.entry(key).or_insert(false);
-            *entry = *entry || allowed;
-        }
+        merge_actions(&mut normalized, self.actions);
         PermissionProfile {
+            inherit: self
+                .inherit
+                .iter()
+                .map(|name| name.trim().to_lowercase())
+                .collect(),
             actions: normalized,
         }
     }
@@ -284,37 +1016,394 @@ impl PermissionProfile {
 }
 
 impl Config {
-    pub fn load(path: &str) -> Result<Self> {
+    pub fn load(path: &Path) -> Result<Self> {
         let content = fs::read_to_string(path)
-            .with_context(|| format!("Failed to read config file: {}", path))?;
+            .with_context(|| format!("Failed to read config file: {:?}", path))?;
 
         let mut config: Config = toml::from_str(&content)
-            .with_context(|| format!("Failed to parse config file: {}", path))?;
+            .with_context(|| format!("Failed to parse config file: {:?}", path))?;
 
-        config.normalize();
+        config.normalize(path);
+        config
+            .validate(&content)
+            .with_context(|| format!("Invalid config file: {:?}", path))?;
 
         Ok(config)
     }
 
-    pub fn save(&self, path: &str) -> Result<()> {
+    pub fn save(&self, path: &Path) -> Result<()> {
         let toml = toml::to_string_pretty(self).context("Failed to serialize configuration")?;
-        fs::write(path, toml).with_context(|| format!("Failed to write config file: {}", path))?;
+        fs::write(path, toml)
+            .with_context(|| format!("Failed to write config file: {:?}", path))?;
         Ok(())
     }
 
-    pub fn load_or_create(path: &str) -> Result<Self> {
-        if Path::new(path).exists() {
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
             Self::load(path)
         } else {
             let mut config = Self::default();
-            config.normalize();
             config.save(path)?;
+            config.normalize(path);
             Ok(config)
         }
     }
 
-    fn normalize(&mut self) {
+    fn normalize(&mut self, path: &Path) {
         self.normalize_permissions();
+        self.normalize_paths(path.parent().unwrap_or_else(|| Path::new("")));
+    }
+
+    fn normalize_paths(&mut self, base_dir: &Path) {
+        let resolve = |path: &Path| {
+            if path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                base_dir.join(path)
+            }
+        };
+
+        for mount in self.mounts.values_mut() {
+            mount.path = resolve(&mount.path);
+            if let Some(template) = &mut mount.folder_readme_template {
+                *template = resolve(template);
+            }
+        }
+        self.main_page.markdown_file = resolve(Path::new(&self.main_page.markdown_file))
+            .to_string_lossy()
+            .to_string();
+        self.server.templates_dir = resolve(&self.server.templates_dir);
+        self.server.static_dir = resolve(&self.server.static_dir);
+        if let Some(temp_dir) = &mut self.server.uploads.temp_dir {
+            *temp_dir = resolve(temp_dir);
+        }
+        self.server.data_dir = Some(match &self.server.data_dir {
+            Some(data_dir) => resolve(data_dir),
+            None => base_dir.join(DEFAULT_DATA_DIR),
+        });
+        if let Some(logo) = &mut self.branding.logo {
+            *logo = resolve(logo);
+        }
+        if let Some(login_markdown) = &mut self.branding.login_markdown {
+            *login_markdown = resolve(login_markdown);
+        }
+        for page in [
+            &mut self.pages.about,
+            &mut self.pages.terms,
+            &mut self.pages.privacy,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *page = resolve(page);
+        }
+        if let Some(tls) = &mut self.server.tls {
+            tls.cert_file = resolve(&tls.cert_file);
+            tls.key_file = resolve(&tls.key_file);
+        }
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.server
+            .data_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR))
+    }
+
+    fn validate(&self, source: &str) -> Result<()> {
+        let mut problems = Vec::new();
+        let groups: BTreeSet<&str> = self
+            .users
+            .values()
+            .flat_map(|user| user.group.iter().map(String::as_str))
+            .chain([ADMIN_GROUP])
+            .collect();
+
+        let tuning = &self.server.tuning;
+        for (key, value) in [
+            ("workers", tuning.workers),
+            ("max_connections", tuning.max_connections),
+        ] {
+            if value == Some(0) {
+                problems.push(ConfigProblem::new(
+                    line_of(source, "[server.tuning]", 0, key),
+                    format!("[server.tuning]: {} must be at least 1", key),
+                ));
+            }
+        }
+
+        let display = &self.display;
+        for (key, value) in [
+            ("per_page", display.per_page),
+            ("max_per_page", display.max_per_page),
+        ] {
+            if value == 0 {
+                problems.push(ConfigProblem::new(
+                    line_of(source, "[display]", 0, key),
+                    format!("[display]: {} must be at least 1", key),
+                ));
+            }
+        }
+        if display.per_page > display.max_per_page {
+            problems.push(ConfigProblem::new(
+                line_of(source, "[display]", 0, "per_page"),
+                "[display]: per_page must not exceed max_per_page".to_string(),
+            ));
+        }
+
+        let search = &self.search;
+        for (key, value) in [
+            ("max_depth", search.max_depth as u64),
+            ("max_results", search.max_results as u64),
+            ("index_interval_secs", search.index_interval_secs),
+        ] {
+            if value == 0 {
+                problems.push(ConfigProblem::new(
+                    line_of(source, "[search]", 0, key),
+                    format!("[search]: {} must be at least 1", key),
+                ));
+            }
+        }
+
+        let mut profile_names: Vec<&String> = self.permissions.keys().collect();
+        profile_names.sort();
+        for name in profile_names {
+            let section = format!("permissions.{}", name);
+            let profile = &self.permissions[name];
+            for action in profile.actions.keys() {
+                if !KNOWN_ACTIONS.contains(&action.as_str()) {
+                    problems.push(ConfigProblem::new(
+                        line_of(source, &format!("[{}]", section), 0, action),
+                        format!("[{}]: unknown action '{}'", section, action),
+                    ));
+                }
+            }
+            for parent in &profile.inherit {
+                if !self.permissions.contains_key(parent) {
+                    problems.push(ConfigProblem::new(
+                        line_of(source, &format!("[{}]", section), 0, "inherit"),
+                        format!(
+                            "[{}]: inherits undefined permission profile '{}'",
+                            section, parent
+                        ),
+                    ));
+                }
+            }
+            if self.inherits_from(name, name, &mut BTreeSet::new()) {
+                problems.push(ConfigProblem::new(
+                    line_of(source, &format!("[{}]", section), 0, "inherit"),
+                    format!("[{}]: inherit includes the profile itself", section),
+                ));
+            }
+        }
+
+        let mut mount_names: Vec<&String> = self.mounts.keys().collect();
+        mount_names.sort();
+        for name in &mount_names {
+            let mount = &self.mounts[*name];
+            let section = format!("mounts.{}", name);
+            self.validate_grants(
+                source,
+                (&format!("[{}]", section), 0),
+                &mount.user,
+                &mount.group,
+                &groups,
+                &mut problems,
+            );
+            for (index, rule) in mount.rules.iter().enumerate() {
+                let rule_section = format!("{}.rules", section);
+                let header = format!("[[{}]]", rule_section);
+                let line = line_of(source, &header, index, "pattern");
+                if rule.pattern.trim().is_empty() {
+                    problems.push(ConfigProblem::new(
+                        line,
+                        format!("[[{}]] #{}: pattern is empty", rule_section, index + 1),
+                    ));
+                }
+                self.validate_grants(
+                    source,
+                    (&header, index),
+                    &rule.user,
+                    &rule.group,
+                    &groups,
+                    &mut problems,
+                );
+            }
+            if let Some(color) = &mount.color {
+                if !is_hex_color(color) {
+                    problems.push(ConfigProblem::new(
+                        line_of(source, &format!("[{}]", section), 0, "color"),
+                        format!(
+                            "[{}]: color '{}' must be a hex color like \"#4f7cff\"",
+                            section, color
+                        ),
+                    ));
+                }
+            }
+            if let Some(encryption) = &mount.encryption {
+                if encryption.key.is_some() == encryption.key_file.is_some() {
+                    problems.push(ConfigProblem::new(
+                        line_of(source, &format!("[{}]", section), 0, "encryption"),
+                        format!(
+                            "[{}]: set exactly one of encryption.key or encryption.key_file",
+                            section
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for nested in self.nested_mounts() {
+            let shared = nested.prefix.as_os_str().is_empty();
+            if shared && nested.inner > nested.outer {
+                continue;
+            }
+            let message = if shared {
+                format!(
+                    "mounts '{}' and '{}' share the same directory",
+                    nested.outer, nested.inner
+                )
+            } else {
+                format!(
+                    "mount '{}' is nested inside mount '{}' at '{}'",
+                    nested.inner,
+                    nested.outer,
+                    nested.prefix.display()
+                )
+            };
+            match self.server.nested_mounts {
+                NestedMountPolicy::Reject => problems.push(ConfigProblem::new(
+                    line_of(source, &format!("[mounts.{}]", nested.inner), 0, "path"),
+                    format!(
+                        "{}; set server.nested_mounts = \"warn\" or \"inherit\" to allow it",
+                        message
+                    ),
+                )),
+                NestedMountPolicy::Warn => log::warn!(
+                    "{}; permissions of '{}' can be bypassed through '{}'",
+                    message,
+                    nested.outer,
+                    nested.inner
+                ),
+                NestedMountPolicy::Inherit => log::info!(
+                    "{}; access through '{}' is limited to what '{}' allows",
+                    message,
+                    nested.inner,
+                    nested.outer
+                ),
+            }
+        }
+
+        if let Some(s3) = &self.s3 {
+            for key in &s3.keys {
+                if !self.users.contains_key(&key.user) {
+                    problems.push(ConfigProblem::new(
+                        find_line(source, &key.access_key),
+                        format!(
+                            "[[s3.keys]] '{}': user '{}' is not defined",
+                            key.access_key, key.user
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        problems.sort_by_key(|problem| problem.line.unwrap_or(usize::MAX));
+        let report = problems
+            .iter()
+            .map(ConfigProblem::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        Err(anyhow!("{} problem(s) found:\n{}", problems.len(), report))
+    }
+
+    pub fn nested_mounts(&self) -> Vec<NestedMount> {
+        let paths: Vec<(&String, PathBuf)> = self
+            .mounts
+            .iter()
+            .map(|(name, mount)| (name, lexical_path(&mount.path)))
+            .collect();
+        let mut nested = Vec::new();
+        for (inner, inner_path) in &paths {
+            for (outer, outer_path) in &paths {
+                if inner == outer {
+                    continue;
+                }
+                if let Ok(prefix) = inner_path.strip_prefix(outer_path) {
+                    nested.push(NestedMount {
+                        inner: inner.to_string(),
+                        outer: outer.to_string(),
+                        prefix: prefix.to_path_buf(),
+                    });
+                }
+            }
+        }
+        nested.sort_by(|a, b| (&a.inner, &a.outer).cmp(&(&b.inner, &b.outer)));
+        nested
+    }
+
+    fn validate_grants(
+        &self,
+        source: &str,
+        (header, occurrence): (&str, usize),
+        users: &HashMap<String, PermissionSpec>,
+        groups: &HashMap<String, PermissionSpec>,
+        known_groups: &BTreeSet<&str>,
+        problems: &mut Vec<ConfigProblem>,
+    ) {
+        let section = header.trim_matches(|c| c == '[' || c == ']');
+        let mut grants: Vec<(&str, &String, &PermissionSpec)> = users
+            .iter()
+            .map(|(name, spec)| ("user", name, spec))
+            .chain(groups.iter().map(|(name, spec)| ("group", name, spec)))
+            .collect();
+        grants.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+        for (kind, name, spec) in grants {
+            let key = format!("{}.{}", kind, name);
+            let line = line_of(source, header, occurrence, &key);
+            let defined = match kind {
+                _ if name == WILDCARD => true,
+                "user" => self.users.contains_key(name),
+                _ => known_groups.contains(name.as_str()),
+            };
+            if !defined {
+                let reason = if kind == "user" {
+                    "is not defined"
+                } else {
+                    "has no members"
+                };
+                problems.push(ConfigProblem::new(
+                    line,
+                    format!("[{}]: {} '{}' {}", section, kind, name, reason),
+                ));
+            }
+            for token in spec
+                .tokens()
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+            {
+                if !self.is_known_permission_token(token) {
+                    problems.push(ConfigProblem::new(
+                        line,
+                        format!(
+                            "[{}]: {} '{}' uses undefined permission profile or action '{}'",
+                            section, kind, name, token
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn is_known_permission_token(&self, token: &str) -> bool {
+        let lower = token.to_lowercase();
+        PERMISSION_ALIASES.contains(&lower.as_str())
+            || KNOWN_ACTIONS.contains(&lower.as_str())
+            || self.permissions.contains_key(&lower)
     }
 
     fn normalize_permissions(&mut self) {
@@ -322,9 +1411,33 @@ impl Config {
         for (name, profile) in mem::take(&mut self.permissions) {
             normalized.insert(name.to_lowercase(), profile.normalized());
         }
+        let resolved: Vec<(String, HashMap<String, bool>)> = normalized
+            .keys()
+            .map(|name| {
+                (
+                    name.clone(),
+                    inherited_actions(&normalized, name, &mut Vec::new()),
+                )
+            })
+            .collect();
+        for (name, actions) in resolved {
+            if let Some(profile) = normalized.get_mut(&name) {
+                profile.actions = actions;
+            }
+        }
         self.permissions = normalized;
     }
 
+    fn inherits_from(&self, name: &str, target: &str, seen: &mut BTreeSet<String>) -> bool {
+        let Some(profile) = self.permissions.get(name) else {
+            return false;
+        };
+        profile.inherit.iter().any(|parent| {
+            parent == target
+                || (seen.insert(parent.clone()) && self.inherits_from(parent, target, seen))
+        })
+    }
+
     pub fn resolve_permission_spec(&self, spec: &PermissionSpec) -> Permission {
         let mut permission = Permission::default();
         for token in spec.tokens() {
@@ -359,6 +1472,111 @@ impl Config {
     }
 }
 
+const ADMIN_GROUP: &str = "admin";
+pub const WILDCARD: &str = "*";
+const PERMISSION_ALIASES: [&str; 7] = ["r", "read", "w", "write", "rw", "readwrite", "read_write"];
+const KNOWN_ACTIONS: [&str; 9] = [
+    "read",
+    "write",
+    "upload",
+    "delete",
+    "rename",
+    "modify",
+    "moderate",
+    "create_file",
+    "create_folder",
+];
+
+struct ConfigProblem {
+    line: Option<usize>,
+    message: String,
+}
+
+impl ConfigProblem {
+    fn new(line: Option<usize>, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "  line {}: {}", line, self.message),
+            None => write!(f, "  {}", self.message),
+        }
+    }
+}
+
+fn find_line(source: &str, needle: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| line.contains(needle))
+        .map(|index| index + 1)
+}
+
+fn line_of(source: &str, header: &str, occurrence: usize, key: &str) -> Option<usize> {
+    let lines: Vec<&str> = source.lines().collect();
+    let start = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.trim_start().starts_with(header))
+        .nth(occurrence)
+        .map(|(index, _)| index)?;
+    let found = lines[start + 1..]
+        .iter()
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .position(|line| line.trim_start().starts_with(key));
+    Some(found.map_or(start + 1, |offset| start + offset + 2))
+}
+
+fn merge_actions(target: &mut HashMap<String, bool>, actions: HashMap<String, bool>) {
+    for (action, allowed) in actions {
+        let entry = target.entry(action.to_lowercase()).or_insert(false);
+        *entry = *entry || allowed;
+    }
+}
+
+fn inherited_actions(
+    profiles: &HashMap<String, PermissionProfile>,
+    name: &str,
+    trail: &mut Vec<String>,
+) -> HashMap<String, bool> {
+    let Some(profile) = profiles.get(name) else {
+        return HashMap::new();
+    };
+    if trail.iter().any(|visited| visited == name) {
+        return HashMap::new();
+    }
+    trail.push(name.to_string());
+    let mut actions = profile.actions.clone();
+    for parent in &profile.inherit {
+        merge_actions(&mut actions, inherited_actions(profiles, parent, trail));
+    }
+    trail.pop();
+    actions
+}
+
+fn is_hex_color(value: &str) -> bool {
+    value
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn lexical_path(path: &Path) -> PathBuf {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
 impl Default for Config {
     fn default() -> Self {
         let mut mounts = HashMap::new();
@@ -368,8 +1586,30 @@ impl Default for Config {
                 path: PathBuf::from("./public"),
                 description: "Public files".to_string(),
                 public: true,
+                public_upload: false,
+                moderation: false,
+                allow_content: Vec::new(),
+                deny_content: Vec::new(),
+                tree: TreeConfig::default(),
+                releases: ReleasesConfig::default(),
                 group: HashMap::new(),
                 user: HashMap::new(),
+                rules: Vec::new(),
+                encryption: None,
+                dedup: false,
+                compression: false,
+                trash: false,
+                checksums: false,
+                fulltext: false,
+                gitignore: false,
+                wiki: false,
+                folder_readme: false,
+                folder_readme_template: None,
+                max_upload_size: None,
+                order: 0,
+                hidden: false,
+                icon: None,
+                color: None,
             },
         );
 
@@ -377,15 +1617,46 @@ impl Default for Config {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 8080,
+                bind: Vec::new(),
+                templates_dir: default_templates_dir(),
+                static_dir: default_static_dir(),
+                data_dir: None,
+                http2: true,
+                read_only: false,
+                dev: false,
+                nested_mounts: NestedMountPolicy::default(),
+                maintenance: false,
+                maintenance_message: None,
+                url_signing_secret: None,
+                session_secret: None,
+                tls: None,
+                blocking: BlockingConfig::default(),
+                limits: LimitsConfig::default(),
+                confirm: ConfirmConfig::default(),
+                tuning: TuningConfig::default(),
+                timeouts: TimeoutsConfig::default(),
+                uploads: UploadsConfig::default(),
             },
             main_page: MainPageConfig {
                 title: "LunaFinder".to_string(),
                 description: "Welcome to LunaFinder".to_string(),
                 markdown_file: "./page.md".to_string(),
+                announcement: None,
             },
             users: HashMap::new(),
             mounts,
             permissions: HashMap::new(),
+            scan: None,
+            display: DisplayConfig::default(),
+            search: SearchConfig::default(),
+            branding: BrandingConfig::default(),
+            pages: PagesConfig::default(),
+            negotiate: None,
+            notifications: None,
+            s3: None,
+            ftp: None,
+            metrics: None,
+            security: None,
         }
     }
 }
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::clock;
+use crate::config::ConfirmConfig;
+use crate::signing::UrlSigner;
+
+const TOKEN_TTL_SECS: u64 = 600;
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Summary {
+    pub entries: u64,
+    pub bytes: u64,
+    pub partial: bool,
+}
+
+pub fn measure(path: &Path, limits: &ConfirmConfig) -> Result<Summary> {
+    let mut summary = Summary::default();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        if limits.exceeded_by(&summary) {
+            summary.partial = true;
+            break;
+        }
+        let metadata = fs::symlink_metadata(&path)?;
+        summary.entries += 1;
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                pending.push(entry?.path());
+            }
+        } else {
+            summary.bytes += metadata.len();
+        }
+    }
+    Ok(summary)
+}
+
+pub fn token(signer: &UrlSigner, mount: &str, subject: &str) -> String {
+    let expires = clock::now_unix() + TOKEN_TTL_SECS;
+    format!(
+        "{}.{}",
+        expires,
+        signer.sign(mount, &message(subject), expires)
+    )
+}
+
+pub fn verify(signer: &UrlSigner, mount: &str, subject: &str, token: &str) -> bool {
+    token
+        .split_once('.')
+        .and_then(|(expires, signature)| Some((expires.parse().ok()?, signature)))
+        .is_some_and(|(expires, signature)| {
+            signer.verify(mount, &message(subject), expires, signature)
+        })
+}
+
+fn message(subject: &str) -> String {
+    format!("confirm\0{}", subject)
+}
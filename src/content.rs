@@ -0,0 +1,132 @@
+use std::fmt;
+
+pub const SNIFF_LEN: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCategory {
+    Image,
+    Video,
+    Audio,
+    Archive,
+    Document,
+    Executable,
+    Script,
+    Text,
+    Unknown,
+}
+
+impl ContentCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentCategory::Image => "image",
+            ContentCategory::Video => "video",
+            ContentCategory::Audio => "audio",
+            ContentCategory::Archive => "archive",
+            ContentCategory::Document => "document",
+            ContentCategory::Executable => "executable",
+            ContentCategory::Script => "script",
+            ContentCategory::Text => "text",
+            ContentCategory::Unknown => "unknown",
+        }
+    }
+
+    fn matches(&self, rule: &str) -> bool {
+        let rule = rule.trim().to_lowercase();
+        let rule = rule.strip_suffix('s').unwrap_or(&rule);
+        rule == self.name()
+    }
+}
+
+impl fmt::Display for ContentCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub fn sniff(head: &[u8]) -> ContentCategory {
+    let starts = |magic: &[u8]| head.starts_with(magic);
+    let riff = |kind: &[u8]| head.len() >= 12 && head.starts_with(b"RIFF") && &head[8..12] == kind;
+
+    if starts(b"\x89PNG\r\n\x1a\n")
+        || starts(b"\xff\xd8\xff")
+        || starts(b"GIF87a")
+        || starts(b"GIF89a")
+        || riff(b"WEBP")
+        || (starts(b"BM") && head.len() >= 10 && head[6..10] == [0, 0, 0, 0])
+        || starts(b"\x00\x00\x01\x00")
+        || starts(b"II*\x00")
+        || starts(b"MM\x00*")
+    {
+        return ContentCategory::Image;
+    }
+
+    if (head.len() >= 8 && &head[4..8] == b"ftyp") || starts(b"\x1a\x45\xdf\xa3") || riff(b"AVI ") {
+        return ContentCategory::Video;
+    }
+
+    if starts(b"ID3")
+        || starts(b"\xff\xfb")
+        || starts(b"\xff\xf3")
+        || starts(b"\xff\xf2")
+        || starts(b"fLaC")
+        || starts(b"OggS")
+        || riff(b"WAVE")
+    {
+        return ContentCategory::Audio;
+    }
+
+    if starts(b"\x7fELF")
+        || starts(b"MZ")
+        || starts(b"\xfe\xed\xfa\xce")
+        || starts(b"\xce\xfa\xed\xfe")
+        || starts(b"\xfe\xed\xfa\xcf")
+        || starts(b"\xcf\xfa\xed\xfe")
+        || starts(b"\xca\xfe\xba\xbe")
+        || starts(b"\x00asm")
+    {
+        return ContentCategory::Executable;
+    }
+
+    if starts(b"PK\x03\x04")
+        || starts(b"\x1f\x8b")
+        || starts(b"7z\xbc\xaf\x27\x1c")
+        || starts(b"Rar!")
+        || starts(b"BZh")
+        || starts(b"\xfd7zXZ\x00")
+        || starts(b"\x28\xb5\x2f\xfd")
+    {
+        return ContentCategory::Archive;
+    }
+
+    if starts(b"%PDF") {
+        return ContentCategory::Document;
+    }
+
+    if starts(b"#!") {
+        return ContentCategory::Script;
+    }
+
+    if !head.is_empty() && !head.contains(&0) && is_utf8_prefix(head) {
+        return ContentCategory::Text;
+    }
+
+    ContentCategory::Unknown
+}
+
+pub fn check(allow: &[String], deny: &[String], head: &[u8]) -> Result<(), ContentCategory> {
+    let category = sniff(head);
+    if deny.iter().any(|rule| category.matches(rule)) {
+        return Err(category);
+    }
+    if !allow.is_empty() && !allow.iter().any(|rule| category.matches(rule)) {
+        return Err(category);
+    }
+    Ok(())
+}
+
+fn is_utf8_prefix(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none(),
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{anyhow, Context, Result};
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::config::{Config, EncryptionConfig};
+
+const MAGIC: &[u8; 8] = b"LFENC\x00\x00\x01";
+const NONCE_LEN: usize = 19;
+const HEADER_LEN: usize = MAGIC.len() + NONCE_LEN;
+const CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+#[derive(Clone)]
+pub struct MountKey([u8; KEY_LEN]);
+
+impl MountKey {
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(GenericArray::from_slice(&self.0))
+    }
+}
+
+pub fn load_keys(config: &Config) -> Result<HashMap<String, MountKey>> {
+    let mut keys = HashMap::new();
+    for (name, mount) in &config.mounts {
+        if let Some(encryption) = &mount.encryption {
+            let key = load_key(encryption)
+                .with_context(|| format!("Invalid encryption key for mount '{}'", name))?;
+            keys.insert(name.clone(), key);
+        }
+    }
+    Ok(keys)
+}
+
+fn load_key(encryption: &EncryptionConfig) -> Result<MountKey> {
+    let material = match (&encryption.key, &encryption.key_file) {
+        (Some(key), None) => key.trim().as_bytes().to_vec(),
+        (None, Some(key_file)) => fs::read(key_file)
+            .with_context(|| format!("Failed to read key file: {:?}", key_file))?,
+        _ => return Err(anyhow!("Set exactly one of key or key_file")),
+    };
+
+    let text = String::from_utf8_lossy(&material);
+    let text = text.trim();
+    let bytes = if text.len() == KEY_LEN * 2 && text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        (0..KEY_LEN)
+            .map(|i| u8::from_str_radix(&text[i * 2..i * 2 + 2], 16))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        material
+    };
+
+    let key: [u8; KEY_LEN] = bytes.try_into().map_err(|_| {
+        anyhow!(
+            "Key must be {} raw bytes or {} hex characters",
+            KEY_LEN,
+            KEY_LEN * 2
+        )
+    })?;
+    Ok(MountKey(key))
+}
+
+pub fn plaintext_len(stored_len: u64) -> u64 {
+    let body = stored_len.saturating_sub(HEADER_LEN as u64);
+    let block = (CHUNK_LEN + TAG_LEN) as u64;
+    let blocks = body.div_ceil(block).max(1);
+    body.saturating_sub(blocks * TAG_LEN as u64)
+}
+
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut magic = [0u8; MAGIC.len()];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == MAGIC
+}
+
+pub struct EncryptingWriter<W: Write> {
+    inner: W,
+    encryptor: Option<EncryptorBE32<XChaCha20Poly1305>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    pub fn new(key: &MountKey, mut inner: W) -> io::Result<Self> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        inner.write_all(MAGIC)?;
+        inner.write_all(&nonce)?;
+        Ok(Self {
+            inner,
+            encryptor: Some(EncryptorBE32::from_aead(
+                key.cipher(),
+                GenericArray::from_slice(&nonce),
+            )),
+            buffer: Vec::with_capacity(CHUNK_LEN * 2),
+        })
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let encryptor = self
+            .encryptor
+            .take()
+            .ok_or_else(|| io::Error::other("Stream already finished"))?;
+        let ciphertext = encryptor
+            .encrypt_last(self.buffer.as_slice())
+            .map_err(|_| io::Error::other("Encryption failed"))?;
+        self.inner.write_all(&ciphertext)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let encryptor = self
+            .encryptor
+            .as_mut()
+            .ok_or_else(|| io::Error::other("Stream already finished"))?;
+        self.buffer.extend_from_slice(buf);
+        while self.buffer.len() > CHUNK_LEN {
+            let chunk: Vec<u8> = self.buffer.drain(..CHUNK_LEN).collect();
+            let ciphertext = encryptor
+                .encrypt_next(chunk.as_slice())
+                .map_err(|_| io::Error::other("Encryption failed"))?;
+            self.inner.write_all(&ciphertext)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub struct DecryptingReader<R: Read> {
+    inner: R,
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    remaining: u64,
+    plaintext: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.plaintext.len() {
+            let Some(decryptor) = self.decryptor.as_mut() else {
+                return Ok(0);
+            };
+            let block_len = self.remaining.min((CHUNK_LEN + TAG_LEN) as u64) as usize;
+            let mut block = vec![0u8; block_len];
+            self.inner.read_exact(&mut block)?;
+            self.remaining -= block_len as u64;
+            let decrypted = if self.remaining == 0 {
+                self.decryptor
+                    .take()
+                    .map(|decryptor| decryptor.decrypt_last(block.as_slice()))
+            } else {
+                Some(decryptor.decrypt_next(block.as_slice()))
+            };
+            self.plaintext = decrypted
+                .transpose()
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Encrypted file is corrupted")
+                })?
+                .unwrap_or_default();
+            self.position = 0;
+        }
+
+        let available = &self.plaintext[self.position..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+pub fn decrypting_reader(
+    key: &MountKey,
+    mut file: fs::File,
+) -> io::Result<(DecryptingReader<fs::File>, u64)> {
+    let stored_len = file.metadata()?.len();
+    let mut header = [0u8; HEADER_LEN];
+    file.read_exact(&mut header)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "File is not encrypted",
+        ));
+    }
+    let reader = DecryptingReader {
+        inner: file,
+        decryptor: Some(DecryptorBE32::from_aead(
+            key.cipher(),
+            GenericArray::from_slice(&header[MAGIC.len()..]),
+        )),
+        remaining: stored_len - HEADER_LEN as u64,
+        plaintext: Vec::new(),
+        position: 0,
+    };
+    Ok((reader, plaintext_len(stored_len)))
+}
@@ -0,0 +1,143 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const HARDLINK_SUFFIX: &str = ".lunafinder_link";
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub hash: String,
+    pub paths: Vec<String>,
+    pub reclaimable: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+    pub reclaimable: u64,
+}
+
+pub fn find(base: &Path) -> Result<DuplicateReport> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    collect_files(base, Path::new(""), &mut by_size)?;
+
+    let mut groups = Vec::new();
+    for (size, paths) in by_size {
+        if size == 0 || paths.len() < 2 {
+            continue;
+        }
+        let mut by_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for relative in paths {
+            match hash_file(&base.join(&relative)) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(relative),
+                Err(e) => log::warn!(
+                    "Skipping {:?} while looking for duplicates: {}",
+                    relative,
+                    e
+                ),
+            }
+        }
+        for (hash, paths) in by_hash {
+            if paths.len() < 2 {
+                continue;
+            }
+            let mut paths = paths
+                .iter()
+                .map(|path| crate::osname::encode_path(path))
+                .collect::<Vec<_>>();
+            paths.sort();
+            groups.push(DuplicateGroup {
+                size,
+                hash,
+                reclaimable: size * (paths.len() as u64 - 1),
+                paths,
+            });
+        }
+    }
+
+    groups.sort_by_key(|group| Reverse(group.reclaimable));
+    let reclaimable = groups.iter().map(|group| group.reclaimable).sum();
+    Ok(DuplicateReport {
+        groups,
+        reclaimable,
+    })
+}
+
+pub fn hardlink(base: &Path, keep: &Path, duplicates: &[PathBuf]) -> Result<usize> {
+    let original = base.join(keep);
+    let expected = hash_file(&original)?;
+    let mut linked = 0;
+    for relative in duplicates {
+        if relative == keep {
+            continue;
+        }
+        let duplicate = base.join(relative);
+        if hash_file(&duplicate)? != expected {
+            return Err(anyhow!("{:?} no longer matches {:?}", relative, keep));
+        }
+        replace_with_link(&original, &duplicate)?;
+        linked += 1;
+    }
+    Ok(linked)
+}
+
+pub fn replace_with_link(original: &Path, duplicate: &Path) -> Result<()> {
+    let mut temporary = duplicate.as_os_str().to_owned();
+    temporary.push(HARDLINK_SUFFIX);
+    let temporary = PathBuf::from(temporary);
+    fs::hard_link(original, &temporary)
+        .with_context(|| format!("Failed to hardlink {:?}", duplicate))?;
+    if let Err(e) = fs::rename(&temporary, duplicate) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e).with_context(|| format!("Failed to replace {:?}", duplicate));
+    }
+    Ok(())
+}
+
+fn collect_files(
+    base: &Path,
+    relative: &Path,
+    by_size: &mut HashMap<u64, Vec<PathBuf>>,
+) -> Result<()> {
+    let current = base.join(relative);
+    for entry in fs::read_dir(&current)
+        .with_context(|| format!("Failed to read directory: {:?}", current))?
+    {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with(".lunafinder_") {
+            continue;
+        }
+        let child_relative = relative.join(&name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(base, &child_relative, by_size)?;
+        } else if file_type.is_file() {
+            by_size
+                .entry(entry.metadata()?.len())
+                .or_default()
+                .push(child_relative);
+        }
+    }
+    Ok(())
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,194 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::StatusCode,
+    middleware::Next,
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use std::fmt;
+
+const API_PREFIX: &str = "/api/";
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+const PROBLEM_TYPE_PREFIX: &str = "urn:lunafinder:error:";
+
+#[derive(Debug, thiserror::Error)]
+pub enum LunaError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Locked(String),
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+    #[error("{0}")]
+    Maintenance(String),
+    #[error("Operation timed out")]
+    Timeout,
+    #[error("{0}")]
+    Internal(String),
+}
+
+#[derive(Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    kind: String,
+    title: &'a str,
+    status: u16,
+    detail: String,
+    code: &'a str,
+    instance: &'a str,
+}
+
+impl LunaError {
+    pub fn bad_request(message: impl fmt::Display) -> Self {
+        Self::BadRequest(message.to_string())
+    }
+
+    pub fn unauthorized(message: impl fmt::Display) -> Self {
+        Self::Unauthorized(message.to_string())
+    }
+
+    pub fn forbidden(message: impl fmt::Display) -> Self {
+        Self::Forbidden(message.to_string())
+    }
+
+    pub fn not_found(message: impl fmt::Display) -> Self {
+        Self::NotFound(message.to_string())
+    }
+
+    pub fn conflict(message: impl fmt::Display) -> Self {
+        Self::Conflict(message.to_string())
+    }
+
+    pub fn locked(message: impl fmt::Display) -> Self {
+        Self::Locked(message.to_string())
+    }
+
+    pub fn payload_too_large(message: impl fmt::Display) -> Self {
+        Self::PayloadTooLarge(message.to_string())
+    }
+
+    pub fn unsupported_media_type(message: impl fmt::Display) -> Self {
+        Self::UnsupportedMediaType(message.to_string())
+    }
+
+    pub fn internal(message: impl fmt::Display) -> Self {
+        Self::Internal(message.to_string())
+    }
+
+    fn from_status(status: StatusCode, message: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized(message),
+            StatusCode::FORBIDDEN => Self::Forbidden(message),
+            StatusCode::NOT_FOUND => Self::NotFound(message),
+            StatusCode::CONFLICT => Self::Conflict(message),
+            StatusCode::LOCKED => Self::Locked(message),
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge(message),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType(message),
+            StatusCode::SERVICE_UNAVAILABLE => Self::Maintenance(message),
+            StatusCode::GATEWAY_TIMEOUT => Self::Timeout,
+            status if status.is_client_error() => Self::BadRequest(message),
+            _ => Self::Internal(message),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest(_) => "bad_request",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::NotFound(_) => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::Locked(_) => "locked",
+            Self::PayloadTooLarge(_) => "payload_too_large",
+            Self::UnsupportedMediaType(_) => "unsupported_media_type",
+            Self::Maintenance(_) => "maintenance",
+            Self::Timeout => "timeout",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        self.status_code().canonical_reason().unwrap_or("Error")
+    }
+
+    pub fn problem_response(&self, instance: &str) -> HttpResponse {
+        let code = self.code();
+        let problem = Problem {
+            kind: format!("{}{}", PROBLEM_TYPE_PREFIX, code),
+            title: self.title(),
+            status: self.status_code().as_u16(),
+            detail: self.to_string(),
+            code,
+            instance,
+        };
+        HttpResponse::build(self.status_code())
+            .content_type(PROBLEM_CONTENT_TYPE)
+            .json(problem)
+    }
+}
+
+impl ResponseError for LunaError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Locked(_) => StatusCode::LOCKED,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Maintenance(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let title = self.title();
+        HttpResponse::build(self.status_code())
+            .content_type("text/html; charset=utf-8")
+            .body(format!(
+                "<!DOCTYPE html>\n<html lang=\"ko\">\n<head><meta charset=\"UTF-8\"><title>{status} {title}</title></head>\n<body>\n<h1>{status} {title}</h1>\n<p>{detail}</p>\n<p><a href=\"/\">홈으로</a></p>\n</body>\n</html>\n",
+                status = self.status_code().as_u16(),
+                title = title,
+                detail = tera::escape_html(&self.to_string()),
+            ))
+    }
+}
+
+pub async fn problem_details(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let is_api = req.path().starts_with(API_PREFIX);
+    let res = next.call(req).await?;
+    if !is_api {
+        return Ok(res.map_into_left_body());
+    }
+
+    let problem = res.response().error().map(|error| {
+        let instance = res.request().path();
+        match error.as_error::<LunaError>() {
+            Some(error) => error.problem_response(instance),
+            None => {
+                LunaError::from_status(res.status(), error.to_string()).problem_response(instance)
+            }
+        }
+    });
+    match problem {
+        Some(response) => Ok(res.into_response(response).map_into_right_body()),
+        None => Ok(res.map_into_left_body()),
+    }
+}
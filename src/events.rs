@@ -0,0 +1,76 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast;
+
+const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Started,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Event {
+    File {
+        mount: String,
+        action: String,
+        path: String,
+        username: Option<String>,
+    },
+    Job {
+        id: u64,
+        username: Option<String>,
+        label: String,
+        status: JobStatus,
+    },
+    Progress {
+        id: u64,
+        username: Option<String>,
+        done: u64,
+        total: u64,
+    },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::File { .. } => "file",
+            Event::Job { .. } => "job",
+            Event::Progress { .. } => "progress",
+        }
+    }
+}
+
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+    next_job: AtomicU64,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            next_job: AtomicU64::new(1),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn next_job_id(&self) -> u64 {
+        self.next_job.fetch_add(1, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use tera::Value;
+
+use crate::config::SizeUnits;
+
+const BINARY_SUFFIXES: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+const DECIMAL_SUFFIXES: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
+
+pub fn format_file_size(bytes: u64, units: SizeUnits) -> String {
+    let (base, suffixes) = match units {
+        SizeUnits::Binary => (1024.0, BINARY_SUFFIXES),
+        SizeUnits::Decimal => (1000.0, DECIMAL_SUFFIXES),
+    };
+
+    let mut value = bytes as f64;
+    let mut index = 0;
+    while value >= base && index < suffixes.len() - 1 {
+        value /= base;
+        index += 1;
+    }
+
+    if index == 0 {
+        format!("{} {}", bytes, suffixes[0])
+    } else {
+        format!("{:.1} {}", value, suffixes[index])
+    }
+}
+
+pub fn format_relative_time(timestamp: u64, now: u64) -> String {
+    let Some(elapsed) = now.checked_sub(timestamp) else {
+        return "방금 전".to_string();
+    };
+    match elapsed {
+        0..=59 => "방금 전".to_string(),
+        60..=3_599 => format!("{}분 전", elapsed / 60),
+        3_600..=86_399 => format!("{}시간 전", elapsed / 3_600),
+        86_400..=2_591_999 => format!("{}일 전", elapsed / 86_400),
+        2_592_000..=31_535_999 => format!("{}개월 전", elapsed / 2_592_000),
+        _ => format!("{}년 전", elapsed / 31_536_000),
+    }
+}
+
+pub fn filesize_filter(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let bytes = value
+        .as_u64()
+        .ok_or_else(|| tera::Error::msg("Filter `filesize` expects an unsigned integer"))?;
+    let units = match args.get("units").and_then(|units| units.as_str()) {
+        Some(units) => units
+            .parse::<SizeUnits>()
+            .map_err(|_| tera::Error::msg(format!("Unknown size units: {}", units)))?,
+        None => SizeUnits::default(),
+    };
+    Ok(Value::String(format_file_size(bytes, units)))
+}
@@ -0,0 +1,908 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+
+use crate::auth::verify_password;
+use crate::config::{FtpConfig, MountConfig, Permission};
+use crate::quarantine::ScanOutcome;
+use crate::{clock, content, moderation, quarantine, stats, storage, AppState};
+
+const DATA_ACCEPT_TIMEOUT_SECS: u64 = 30;
+const FAILED_LOGIN_DELAY_SECS: u64 = 1;
+
+trait ControlStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ControlStream for T {}
+
+type Control = BufReader<Box<dyn ControlStream>>;
+
+enum DataStream {
+    Plain(std::net::TcpStream),
+    Tls(Box<rustls::StreamOwned<rustls::ServerConnection, std::net::TcpStream>>),
+}
+
+impl DataStream {
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(mut stream) => stream.flush(),
+            Self::Tls(mut stream) => {
+                stream.conn.send_close_notify();
+                stream.flush()
+            }
+        }
+    }
+}
+
+impl Read for DataStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.read(buf),
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for DataStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.write(buf),
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(stream) => stream.flush(),
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+pub fn start(state: AppState) -> Result<()> {
+    let Some(settings) = state.config.ftp.clone() else {
+        return Ok(());
+    };
+    let tls = state
+        .config
+        .server
+        .tls
+        .as_ref()
+        .map(crate::tls::load_server_config)
+        .transpose()?
+        .map(Arc::new);
+    if tls.is_none() && !settings.allow_plaintext {
+        return Err(anyhow!(
+            "[ftp] requires [server.tls] for FTPS; set allow_plaintext = true to accept cleartext logins"
+        ));
+    }
+    let listener = std::net::TcpListener::bind(&settings.bind)
+        .with_context(|| format!("Failed to bind FTP listener: {}", settings.bind))?;
+    listener.set_nonblocking(true)?;
+    let listener = TcpListener::from_std(listener)?;
+    log::info!("FTP server listening on {}", settings.bind);
+
+    actix_web::rt::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let session = Session::new(state.clone(), settings.clone(), tls.clone());
+                    actix_web::rt::spawn(async move {
+                        if let Err(e) = session.run(stream).await {
+                            log::warn!("FTP session from {} ended with an error: {}", peer, e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("Failed to accept FTP connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+struct Target<'a> {
+    mount_name: String,
+    mount: &'a MountConfig,
+    base: PathBuf,
+    relative: PathBuf,
+    path: PathBuf,
+    permission: Permission,
+}
+
+struct Session {
+    state: AppState,
+    settings: FtpConfig,
+    tls: Option<Arc<rustls::ServerConfig>>,
+    secure: bool,
+    protect_data: bool,
+    pending_user: Option<String>,
+    username: Option<String>,
+    cwd: String,
+    passive: Option<TcpListener>,
+    peer_ip: Option<IpAddr>,
+}
+
+impl Session {
+    fn new(state: AppState, settings: FtpConfig, tls: Option<Arc<rustls::ServerConfig>>) -> Self {
+        Self {
+            state,
+            settings,
+            tls,
+            secure: false,
+            protect_data: false,
+            pending_user: None,
+            username: None,
+            cwd: "/".to_string(),
+            passive: None,
+            peer_ip: None,
+        }
+    }
+
+    async fn run(mut self, stream: TcpStream) -> Result<()> {
+        let local = stream.local_addr()?;
+        self.peer_ip = Some(stream.peer_addr()?.ip());
+        let mut control: Control = BufReader::new(Box::new(stream));
+
+        reply(&mut control, 220, "LunaFinder FTP ready").await?;
+        let mut buffer = String::new();
+        loop {
+            buffer.clear();
+            if control.read_line(&mut buffer).await? == 0 {
+                break;
+            }
+            let line = buffer.trim_end_matches(['\r', '\n']);
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let command = command.to_ascii_uppercase();
+            if command == "QUIT" {
+                reply(&mut control, 221, "Goodbye").await?;
+                break;
+            }
+            if command == "AUTH" {
+                control = self.start_tls(control, argument.trim()).await?;
+                continue;
+            }
+            self.dispatch(&mut control, local, &command, argument.trim())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn start_tls(&mut self, mut control: Control, mechanism: &str) -> Result<Control> {
+        let Some(tls) = self.tls.clone() else {
+            reply(&mut control, 502, "TLS is not configured").await?;
+            return Ok(control);
+        };
+        if self.secure {
+            reply(&mut control, 503, "TLS is already active").await?;
+            return Ok(control);
+        }
+        if !mechanism.eq_ignore_ascii_case("TLS") && !mechanism.eq_ignore_ascii_case("SSL") {
+            reply(&mut control, 504, "Only AUTH TLS is supported").await?;
+            return Ok(control);
+        }
+        if !control.buffer().is_empty() {
+            return Err(anyhow!("Client sent commands before the TLS handshake"));
+        }
+        reply(&mut control, 234, "Proceed with TLS negotiation").await?;
+        let stream = TlsAcceptor::from(tls)
+            .accept(control.into_inner())
+            .await
+            .context("FTP TLS handshake failed")?;
+        self.secure = true;
+        self.pending_user = None;
+        self.username = None;
+        Ok(BufReader::new(Box::new(stream)))
+    }
+
+    async fn dispatch(
+        &mut self,
+        writer: &mut Control,
+        local: SocketAddr,
+        command: &str,
+        argument: &str,
+    ) -> Result<()> {
+        match command {
+            "USER" | "PASS" if !self.secure && !self.settings.allow_plaintext => {
+                return reply(writer, 530, "Use AUTH TLS before logging in").await;
+            }
+            "USER" => {
+                self.pending_user = Some(argument.to_string());
+                self.username = None;
+                return reply(writer, 331, "Password required").await;
+            }
+            "PASS" => return self.login(writer, argument).await,
+            "PBSZ" | "PROT" if !self.secure => {
+                return reply(writer, 503, "Use AUTH TLS first").await;
+            }
+            "PBSZ" => return reply(writer, 200, "PBSZ=0").await,
+            "PROT" => return self.protection(writer, argument).await,
+            "SYST" => return reply(writer, 215, "UNIX Type: L8").await,
+            "NOOP" => return reply(writer, 200, "OK").await,
+            "FEAT" => {
+                let tls = if self.tls.is_some() {
+                    " AUTH TLS\r\n PBSZ\r\n PROT\r\n"
+                } else {
+                    ""
+                };
+                writer
+                    .write_all(
+                        format!(
+                            "211-Features:\r\n{} EPSV\r\n PASV\r\n SIZE\r\n UTF8\r\n211 End\r\n",
+                            tls
+                        )
+                        .as_bytes(),
+                    )
+                    .await?;
+                writer.flush().await?;
+                return Ok(());
+            }
+            "OPTS" if argument.eq_ignore_ascii_case("UTF8 ON") => {
+                return reply(writer, 200, "UTF8 enabled").await
+            }
+            _ => {}
+        }
+
+        if self.username.as_deref().is_some_and(|username| {
+            self.state
+                .accounts
+                .is_disabled(&self.state.config, username)
+        }) {
+            self.username = None;
+            return reply(writer, 530, "The account is disabled").await;
+        }
+        if self.username.is_none() {
+            return reply(writer, 530, "Please log in with USER and PASS").await;
+        }
+
+        match command {
+            "PWD" | "XPWD" => {
+                reply(
+                    writer,
+                    257,
+                    &format!("\"{}\" is the current directory", self.cwd),
+                )
+                .await
+            }
+            "CWD" | "XCWD" => self.change_directory(writer, argument).await,
+            "CDUP" | "XCUP" => self.change_directory(writer, "..").await,
+            "TYPE" => reply(writer, 200, "Type set").await,
+            "MODE" | "STRU" => reply(writer, 200, "OK").await,
+            "PASV" => self.passive(writer, local, false).await,
+            "EPSV" => self.passive(writer, local, true).await,
+            "LIST" | "NLST" => self.list(writer, argument, command == "NLST").await,
+            "RETR" => self.retrieve(writer, argument).await,
+            "STOR" => self.store(writer, argument).await,
+            "DELE" => self.delete(writer, argument).await,
+            "MKD" | "XMKD" => self.make_directory(writer, argument).await,
+            "RMD" | "XRMD" => self.remove_directory(writer, argument).await,
+            "SIZE" => self.size(writer, argument).await,
+            _ => reply(writer, 502, "Command not implemented").await,
+        }
+    }
+
+    async fn protection(&mut self, writer: &mut Control, level: &str) -> Result<()> {
+        match level.to_ascii_uppercase().as_str() {
+            "P" => {
+                self.protect_data = true;
+                reply(writer, 200, "Data connections will be protected").await
+            }
+            "C" if self.settings.allow_plaintext => {
+                self.protect_data = false;
+                reply(writer, 200, "Data connections will be clear").await
+            }
+            "C" => reply(writer, 534, "Data connections must be protected").await,
+            _ => reply(writer, 504, "Unsupported protection level").await,
+        }
+    }
+
+    async fn login(&mut self, writer: &mut Control, password: &str) -> Result<()> {
+        let Some(username) = self.pending_user.take() else {
+            return reply(writer, 503, "Send USER first").await;
+        };
+        let config = &self.state.config;
+        let valid = config.users.get(&username).is_some_and(|user| {
+            !user.password.is_empty()
+                && !self.state.accounts.is_disabled(config, &username)
+                && verify_password(password, &user.password, &user.hash_algorithm)
+        });
+        if !valid {
+            let source = self
+                .peer_ip
+                .map(|ip| ip.to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            crate::record_failed_login(&self.state, "FTP", &username, &source);
+            tokio::time::sleep(Duration::from_secs(FAILED_LOGIN_DELAY_SECS)).await;
+            return reply(writer, 530, "Login incorrect").await;
+        }
+        if crate::needs_terms_acceptance(&self.state, &username) {
+            return reply(
+                writer,
+                530,
+                "Accept the terms of service in the web interface first",
+            )
+            .await;
+        }
+        log::info!("{} logged in over FTP", username);
+        self.username = Some(username);
+        reply(writer, 230, "Login successful").await
+    }
+
+    fn resolve(&self, argument: &str) -> String {
+        let combined = if argument.starts_with('/') {
+            argument.to_string()
+        } else {
+            format!("{}/{}", self.cwd, argument)
+        };
+        let mut parts: Vec<&str> = Vec::new();
+        for part in combined.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                part => parts.push(part),
+            }
+        }
+        format!("/{}", parts.join("/"))
+    }
+
+    fn locate(&self, virtual_path: &str) -> Option<Target<'_>> {
+        let trimmed = virtual_path.trim_start_matches('/');
+        let (mount_name, rest) = trimmed.split_once('/').unwrap_or((trimmed, ""));
+        let mount = self.state.config.mounts.get(mount_name)?;
+        let relative = crate::normalize_raw_path(rest)?;
+        let base = crate::canonicalize_mount(&mount.path).ok()?;
+        let path = crate::resolve_path(&base, &relative).ok()?;
+        let permission = crate::effective_permission(
+            &self.state,
+            self.username.as_deref(),
+            None,
+            mount_name,
+            &relative,
+        )
+        .unwrap_or_default();
+        Some(Target {
+            mount_name: mount_name.to_string(),
+            mount,
+            base,
+            relative,
+            path,
+            permission,
+        })
+    }
+
+    fn writable(&self) -> std::result::Result<(), String> {
+        if self.state.maintenance.load(Ordering::Relaxed) {
+            return Err(crate::maintenance_message(&self.state.config));
+        }
+        Ok(())
+    }
+
+    async fn change_directory(&mut self, writer: &mut Control, argument: &str) -> Result<()> {
+        let virtual_path = self.resolve(argument);
+        if virtual_path == "/" {
+            self.cwd = virtual_path;
+            return reply(writer, 250, "Directory changed").await;
+        }
+        let allowed = self
+            .locate(&virtual_path)
+            .is_some_and(|target| target.permission.allows_read() && target.path.is_dir());
+        if !allowed {
+            return reply(writer, 550, "No such directory").await;
+        }
+        self.cwd = virtual_path;
+        reply(writer, 250, "Directory changed").await
+    }
+
+    async fn passive(
+        &mut self,
+        writer: &mut Control,
+        local: SocketAddr,
+        extended: bool,
+    ) -> Result<()> {
+        let listener = match self.bind_passive(local.ip()).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::warn!("Failed to open FTP passive port: {}", e);
+                return reply(writer, 425, "Cannot open passive connection").await;
+            }
+        };
+        let port = listener.local_addr()?.port();
+        self.passive = Some(listener);
+
+        if extended {
+            return reply(
+                writer,
+                229,
+                &format!("Entering Extended Passive Mode (|||{}|)", port),
+            )
+            .await;
+        }
+        let address = self.settings.public_address.unwrap_or(local.ip());
+        let IpAddr::V4(address) = address else {
+            self.passive = None;
+            return reply(writer, 522, "Use EPSV for IPv6 connections").await;
+        };
+        let [a, b, c, d] = address.octets();
+        reply(
+            writer,
+            227,
+            &format!(
+                "Entering Passive Mode ({},{},{},{},{},{})",
+                a,
+                b,
+                c,
+                d,
+                port >> 8,
+                port & 0xff
+            ),
+        )
+        .await
+    }
+
+    async fn bind_passive(&self, ip: IpAddr) -> io::Result<TcpListener> {
+        let Some([first, last]) = self.settings.passive_ports else {
+            return TcpListener::bind(SocketAddr::new(ip, 0)).await;
+        };
+        let mut last_error = io::Error::new(io::ErrorKind::AddrInUse, "No passive port available");
+        for port in first..=last {
+            match TcpListener::bind(SocketAddr::new(ip, port)).await {
+                Ok(listener) => return Ok(listener),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn open_data(&mut self, writer: &mut Control) -> Result<Option<DataStream>> {
+        let Some(listener) = self.passive.take() else {
+            reply(writer, 425, "Use PASV or EPSV first").await?;
+            return Ok(None);
+        };
+        if !self.protect_data && !self.settings.allow_plaintext {
+            reply(
+                writer,
+                521,
+                "Data connections must be protected, send PROT P",
+            )
+            .await?;
+            return Ok(None);
+        }
+        reply(writer, 150, "Opening data connection").await?;
+        let accepted = tokio::time::timeout(
+            Duration::from_secs(DATA_ACCEPT_TIMEOUT_SECS),
+            listener.accept(),
+        )
+        .await;
+        let stream = match accepted {
+            Ok(Ok((stream, peer))) if Some(peer.ip()) == self.peer_ip => stream,
+            _ => {
+                reply(writer, 425, "Data connection failed").await?;
+                return Ok(None);
+            }
+        };
+        let stream = stream.into_std()?;
+        stream.set_nonblocking(false)?;
+        match self.tls.clone().filter(|_| self.protect_data) {
+            Some(tls) => {
+                let connection = rustls::ServerConnection::new(tls)?;
+                Ok(Some(DataStream::Tls(Box::new(rustls::StreamOwned::new(
+                    connection, stream,
+                )))))
+            }
+            None => Ok(Some(DataStream::Plain(stream))),
+        }
+    }
+
+    async fn list(&mut self, writer: &mut Control, argument: &str, names_only: bool) -> Result<()> {
+        let argument = if argument.starts_with('-') {
+            ""
+        } else {
+            argument
+        };
+        let virtual_path = self.resolve(argument);
+        let listing = match self.listing(&virtual_path, names_only) {
+            Some(listing) => listing,
+            None => return reply(writer, 550, "No such directory").await,
+        };
+        let Some(mut stream) = self.open_data(writer).await? else {
+            return Ok(());
+        };
+        let sent = tokio::task::spawn_blocking(move || {
+            stream.write_all(listing.as_bytes())?;
+            stream.finish()
+        })
+        .await?;
+        match sent {
+            Ok(()) => reply(writer, 226, "Transfer complete").await,
+            Err(_) => reply(writer, 426, "Transfer aborted").await,
+        }
+    }
+
+    fn listing(&self, virtual_path: &str, names_only: bool) -> Option<String> {
+        let mut entries: Vec<(String, bool, u64, u64)> = Vec::new();
+        if virtual_path == "/" {
+            for (name, mount) in &self.state.config.mounts {
+                if mount.hidden {
+                    continue;
+                }
+                let readable = crate::effective_permission(
+                    &self.state,
+                    self.username.as_deref(),
+                    None,
+                    name,
+                    Path::new(""),
+                )
+                .is_some_and(|permission| permission.allows_read());
+                if readable {
+                    entries.push((name.clone(), true, 0, 0));
+                }
+            }
+        } else {
+            let target = self.locate(virtual_path)?;
+            if !target.permission.allows_read() || !target.path.is_dir() {
+                return None;
+            }
+            for entry in fs::read_dir(&target.path).ok()?.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if crate::is_reserved_name(&name) {
+                    continue;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0);
+                entries.push((name, metadata.is_dir(), metadata.len(), modified));
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut listing = String::new();
+        for (name, is_dir, size, modified) in entries {
+            if names_only {
+                listing.push_str(&format!("{}\r\n", name));
+            } else {
+                listing.push_str(&format!(
+                    "{} 1 lunafinder lunafinder {:>12} {} {}\r\n",
+                    if is_dir { "drwxr-xr-x" } else { "-rw-r--r--" },
+                    size,
+                    clock::format_list_date(modified),
+                    name
+                ));
+            }
+        }
+        Some(listing)
+    }
+
+    async fn retrieve(&mut self, writer: &mut Control, argument: &str) -> Result<()> {
+        let virtual_path = self.resolve(argument);
+        let Some(target) = self
+            .locate(&virtual_path)
+            .filter(|target| target.permission.allows_read() && target.path.is_file())
+        else {
+            return reply(writer, 550, "No such file").await;
+        };
+        let mount_name = target.mount_name;
+        let path = target.path;
+        let codec = crate::storage_codec(&self.state, &mount_name).into_owned();
+        let Some(mut stream) = self.open_data(writer).await? else {
+            return Ok(());
+        };
+
+        let copied = tokio::task::spawn_blocking(move || -> Result<u64> {
+            let (mut reader, _) = storage::open_reader(codec.as_codec(), &path)?;
+            let copied = io::copy(&mut reader, &mut stream)?;
+            stream.finish()?;
+            Ok(copied)
+        })
+        .await?;
+        match copied {
+            Ok(bytes) => {
+                crate::record_access(
+                    &self.state,
+                    &mount_name,
+                    stats::EventKind::Download,
+                    self.username.clone(),
+                    bytes,
+                );
+                reply(writer, 226, "Transfer complete").await
+            }
+            Err(_) => reply(writer, 426, "Transfer aborted").await,
+        }
+    }
+
+    async fn store(&mut self, writer: &mut Control, argument: &str) -> Result<()> {
+        if let Err(message) = self.writable() {
+            return reply(writer, 550, &message).await;
+        }
+        let virtual_path = self.resolve(argument);
+        let Some(target) = self.locate(&virtual_path) else {
+            return reply(writer, 553, "File name not allowed").await;
+        };
+        if target.relative.as_os_str().is_empty() || target.path.is_dir() {
+            return reply(writer, 553, "File name not allowed").await;
+        }
+        if !target.permission.allows_upload() {
+            return reply(writer, 550, "Write permission required").await;
+        }
+        let destination = if target.mount.moderation && !target.permission.allows_moderate() {
+            moderation::queue_root(&target.base).join(&target.relative)
+        } else {
+            target.path.clone()
+        };
+        let Some(parent) = destination.parent().map(Path::to_path_buf) else {
+            return reply(writer, 553, "File name not allowed").await;
+        };
+        if parent.starts_with(moderation::queue_root(&target.base)) {
+            fs::create_dir_all(&parent)?;
+        } else if !parent.is_dir() {
+            return reply(writer, 550, "Directory does not exist").await;
+        }
+
+        let mount_name = target.mount_name.clone();
+        let mount = target.mount;
+        let base = target.base.clone();
+        let relative = target.relative.clone();
+        let key = self.state.mount_keys.get(&mount_name).cloned();
+        let compress = mount.compression;
+        let allow = mount.allow_content.clone();
+        let deny = mount.deny_content.clone();
+        let max_size = [
+            self.state.config.server.limits.max_field_size,
+            crate::max_upload_size(&self.state.config, mount, self.username.as_deref()),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let temp_path = crate::staging_path(&self.state, &parent, "ftp");
+
+        let Some(stream) = self.open_data(writer).await? else {
+            return Ok(());
+        };
+        let upload_path = temp_path.clone();
+        let final_path = destination.clone();
+        let received = tokio::task::spawn_blocking(move || {
+            receive(
+                stream,
+                &upload_path,
+                &final_path,
+                key,
+                compress,
+                &allow,
+                &deny,
+                max_size,
+            )
+        })
+        .await?;
+        let bytes = match received {
+            Ok(Ok(bytes)) => bytes,
+            Ok(Err(message)) => {
+                let _ = fs::remove_file(&temp_path);
+                return reply(writer, 552, &message).await;
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                log::warn!("FTP upload to {:?} failed: {}", destination, e);
+                return reply(writer, 426, "Transfer aborted").await;
+            }
+        };
+
+        let username = self.username.clone();
+        crate::record_access(
+            &self.state,
+            &mount_name,
+            stats::EventKind::Upload,
+            username.clone(),
+            bytes,
+        );
+        crate::publish_file_event(
+            &self.state,
+            &mount_name,
+            "upload",
+            &relative,
+            username.as_deref(),
+        );
+
+        if let Some(scan) = &self.state.config.scan {
+            let command = scan.command.clone();
+            let scan_path = destination.clone();
+            let codec = crate::storage_codec(&self.state, &mount_name).into_owned();
+            let outcome = crate::run_blocking(&self.state, move || {
+                crate::scan_stored_file(&command, codec.as_codec(), &scan_path)
+            })
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+            if let ScanOutcome::Flagged(verdict) = outcome {
+                log::warn!(
+                    "FTP upload {:?} to mount '{}' flagged by scanner: {}",
+                    destination,
+                    mount_name,
+                    verdict
+                );
+                quarantine::isolate(&base, &relative, &destination, &verdict)?;
+            }
+        }
+        let state = self.state.clone();
+        if let Some(mount) = state.config.mounts.get(&mount_name) {
+            crate::store_deduplicated(&state, mount, &base, &destination).await;
+        }
+        reply(writer, 226, "Transfer complete").await
+    }
+
+    async fn delete(&mut self, writer: &mut Control, argument: &str) -> Result<()> {
+        if let Err(message) = self.writable() {
+            return reply(writer, 550, &message).await;
+        }
+        let virtual_path = self.resolve(argument);
+        let Some(target) = self
+            .locate(&virtual_path)
+            .filter(|target| target.path.is_file())
+        else {
+            return reply(writer, 550, "No such file").await;
+        };
+        if !target.permission.allows_delete() {
+            return reply(writer, 550, "Delete permission required").await;
+        }
+        if target.mount.trash {
+            crate::trash::discard(
+                &target.base,
+                &target.relative,
+                &target.path,
+                self.username.as_deref(),
+            )?;
+        } else {
+            fs::remove_file(&target.path)?;
+            crate::schedule_blob_gc(&self.state, target.mount, &target.base);
+        }
+        crate::publish_file_event(
+            &self.state,
+            &target.mount_name,
+            "delete",
+            &target.relative,
+            self.username.as_deref(),
+        );
+        reply(writer, 250, "File deleted").await
+    }
+
+    async fn make_directory(&mut self, writer: &mut Control, argument: &str) -> Result<()> {
+        if let Err(message) = self.writable() {
+            return reply(writer, 550, &message).await;
+        }
+        let virtual_path = self.resolve(argument);
+        let Some(target) = self
+            .locate(&virtual_path)
+            .filter(|target| !target.relative.as_os_str().is_empty())
+        else {
+            return reply(writer, 550, "Directory name not allowed").await;
+        };
+        if !target.permission.allows_create_folder() {
+            return reply(writer, 550, "Create folder permission required").await;
+        }
+        if fs::create_dir(&target.path).is_err() {
+            return reply(writer, 550, "Cannot create directory").await;
+        }
+        crate::publish_file_event(
+            &self.state,
+            &target.mount_name,
+            "create_folder",
+            &target.relative,
+            self.username.as_deref(),
+        );
+        reply(
+            writer,
+            257,
+            &format!("\"{}\" created", virtual_path.replace('"', "\"\"")),
+        )
+        .await
+    }
+
+    async fn remove_directory(&mut self, writer: &mut Control, argument: &str) -> Result<()> {
+        if let Err(message) = self.writable() {
+            return reply(writer, 550, &message).await;
+        }
+        let virtual_path = self.resolve(argument);
+        let Some(target) = self
+            .locate(&virtual_path)
+            .filter(|target| !target.relative.as_os_str().is_empty() && target.path.is_dir())
+        else {
+            return reply(writer, 550, "No such directory").await;
+        };
+        if !target.permission.allows_delete() {
+            return reply(writer, 550, "Delete permission required").await;
+        }
+        if fs::remove_dir(&target.path).is_err() {
+            return reply(writer, 550, "Directory is not empty").await;
+        }
+        crate::publish_file_event(
+            &self.state,
+            &target.mount_name,
+            "delete",
+            &target.relative,
+            self.username.as_deref(),
+        );
+        reply(writer, 250, "Directory removed").await
+    }
+
+    async fn size(&mut self, writer: &mut Control, argument: &str) -> Result<()> {
+        let virtual_path = self.resolve(argument);
+        let size = self.locate(&virtual_path).and_then(|target| {
+            if !target.permission.allows_read() || !target.path.is_file() {
+                return None;
+            }
+            let codec = crate::storage_codec(&self.state, &target.mount_name);
+            let (_, size) = storage::open_reader(codec, &target.path).ok()?;
+            size.or_else(|| fs::metadata(&target.path).ok().map(|m| m.len()))
+        });
+        match size {
+            Some(size) => reply(writer, 213, &size.to_string()).await,
+            None => reply(writer, 550, "No such file").await,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn receive(
+    mut stream: DataStream,
+    temp_path: &Path,
+    destination: &Path,
+    key: Option<crate::crypto::MountKey>,
+    compress: bool,
+    allow: &[String],
+    deny: &[String],
+    max_size: Option<u64>,
+) -> Result<std::result::Result<u64, String>> {
+    let mut head = Vec::with_capacity(content::SNIFF_LEN);
+    (&mut stream)
+        .take(content::SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    if let Err(category) = content::check(allow, deny, &head) {
+        return Ok(Err(format!(
+            "Uploading {} content to this mount is not allowed",
+            category
+        )));
+    }
+
+    let codec = storage::Codec {
+        key: key.as_ref(),
+        compress: compress && storage::should_compress(destination),
+    };
+    let mut file = storage::FileWriter::create(codec, temp_path)?;
+    file.write_all(&head)?;
+    let mut total = head.len() as u64;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        total += read as u64;
+        if max_size.is_some_and(|max_size| total > max_size) {
+            return Ok(Err("Uploaded file exceeds the size limit".to_string()));
+        }
+        file.write_all(&buffer[..read])?;
+    }
+    file.finish()?;
+    storage::persist(temp_path, destination)
+        .with_context(|| format!("Failed to publish upload {:?}", destination))?;
+    Ok(Ok(total))
+}
+
+async fn reply(writer: &mut Control, code: u16, message: &str) -> Result<()> {
+    writer
+        .write_all(format!("{} {}\r\n", code, message).as_bytes())
+        .await?;
+    writer.flush().await?;
+    Ok(())
+}
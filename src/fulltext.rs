@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::storage::Codec;
+
+#[cfg(feature = "fulltext")]
+const INDEX_DIR: &str = "fulltext";
+#[cfg(feature = "fulltext")]
+const MAX_SOURCE_BYTES: u64 = 32 * 1024 * 1024;
+#[cfg(feature = "fulltext")]
+const MAX_INDEXED_CHARS: usize = 1024 * 1024;
+#[cfg(feature = "fulltext")]
+const TEXT_EXTENSIONS: [&str; 16] = [
+    "txt", "md", "markdown", "rst", "adoc", "csv", "tsv", "log", "json", "toml", "yaml", "yml",
+    "xml", "html", "htm", "ini",
+];
+
+#[derive(Debug, Serialize)]
+pub struct ContentHit {
+    pub mount: String,
+    pub path: String,
+    pub score: f32,
+    pub snippet: String,
+}
+
+pub struct FullTextIndex {
+    #[cfg(feature = "fulltext")]
+    root: std::path::PathBuf,
+    #[cfg(feature = "fulltext")]
+    indexes: std::sync::Mutex<std::collections::HashMap<String, tantivy::Index>>,
+}
+
+pub fn available() -> bool {
+    cfg!(feature = "fulltext")
+}
+
+#[cfg(feature = "fulltext")]
+fn is_indexable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .is_some_and(|extension| {
+            extension == "pdf" || TEXT_EXTENSIONS.contains(&extension.as_str())
+        })
+}
+
+#[cfg(feature = "fulltext")]
+fn extract(codec: Codec, path: &Path) -> Result<Option<String>> {
+    use std::io::Read;
+
+    let metadata = std::fs::metadata(path)?;
+    if metadata.len() > MAX_SOURCE_BYTES {
+        return Ok(None);
+    }
+    let (reader, _) = crate::storage::open_reader(codec, path)?;
+    let mut bytes = Vec::new();
+    reader.take(MAX_SOURCE_BYTES).read_to_end(&mut bytes)?;
+    let is_pdf = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"));
+    let mut text = if is_pdf {
+        match pdf_extract::extract_text_from_mem(&bytes) {
+            Ok(text) => text,
+            Err(e) => {
+                log::debug!("Skipping {:?}: {}", path, e);
+                return Ok(None);
+            }
+        }
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+    if let Some((cut, _)) = text.char_indices().nth(MAX_INDEXED_CHARS) {
+        text.truncate(cut);
+    }
+    Ok(Some(text))
+}
+
+#[cfg(feature = "fulltext")]
+impl FullTextIndex {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            root: data_dir.join(INDEX_DIR),
+            indexes: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    fn index(&self, mount: &str) -> Result<tantivy::Index> {
+        let mut indexes = self
+            .indexes
+            .lock()
+            .map_err(|_| anyhow!("Full-text index lock poisoned"))?;
+        if let Some(index) = indexes.get(mount) {
+            return Ok(index.clone());
+        }
+        let path = self.root.join(crate::signing::hex(mount.as_bytes()));
+        std::fs::create_dir_all(&path)?;
+        let directory = tantivy::directory::MmapDirectory::open(&path)?;
+        let index = tantivy::Index::open_or_create(directory, schema())?;
+        indexes.insert(mount.to_string(), index.clone());
+        Ok(index)
+    }
+
+    pub fn rebuild(
+        &self,
+        mount: &str,
+        base: &Path,
+        codec: Codec,
+        gitignore: bool,
+    ) -> Result<usize> {
+        let index = self.index(mount)?;
+        let schema = index.schema();
+        let (path_field, body_field) = (schema.get_field("path")?, schema.get_field("body")?);
+        let mut writer: tantivy::IndexWriter = index.writer(50_000_000)?;
+        writer.delete_all_documents()?;
+
+        let mut indexed = 0;
+        let rules = gitignore.then(|| crate::gitignore::Rules::load(base, Path::new("")));
+        let mut pending = vec![(std::path::PathBuf::new(), rules)];
+        while let Some((relative, rules)) = pending.pop() {
+            let directory = base.join(&relative);
+            for entry in std::fs::read_dir(&directory)? {
+                let entry = entry?;
+                let name = entry.file_name();
+                if crate::is_reserved_name(&name.to_string_lossy()) {
+                    continue;
+                }
+                let child = relative.join(&name);
+                let file_type = entry.file_type()?;
+                if rules
+                    .as_ref()
+                    .is_some_and(|rules| rules.is_ignored(&entry.path(), file_type.is_dir()))
+                {
+                    continue;
+                }
+                if file_type.is_dir() {
+                    let child_rules = rules.as_ref().map(|rules| rules.child(&entry.path()));
+                    pending.push((child, child_rules));
+                    continue;
+                }
+                if !file_type.is_file() || !is_indexable(&child) {
+                    continue;
+                }
+                let Some(body) = extract(codec, &entry.path())? else {
+                    continue;
+                };
+                writer.add_document(tantivy::doc!(
+                    path_field => crate::osname::encode_path(&child),
+                    body_field => body,
+                ))?;
+                indexed += 1;
+            }
+        }
+        writer.commit()?;
+        Ok(indexed)
+    }
+
+    pub fn search(&self, mount: &str, query: &str, limit: usize) -> Result<Vec<ContentHit>> {
+        use tantivy::schema::Value;
+
+        let index = self.index(mount)?;
+        let schema = index.schema();
+        let (path_field, body_field) = (schema.get_field("path")?, schema.get_field("body")?);
+        let reader = index
+            .reader_builder()
+            .reload_policy(tantivy::ReloadPolicy::Manual)
+            .try_into()?;
+        let searcher = reader.searcher();
+        let parser = tantivy::query::QueryParser::for_index(&index, vec![body_field]);
+        let (query, _) = parser.parse_query_lenient(query);
+        let top = searcher.search(&query, &tantivy::collector::TopDocs::with_limit(limit))?;
+        let mut snippets =
+            tantivy::snippet::SnippetGenerator::create(&searcher, &*query, body_field)?;
+        snippets.set_max_num_chars(200);
+
+        let mut hits = Vec::new();
+        for (score, address) in top {
+            let document: tantivy::TantivyDocument = searcher.doc(address)?;
+            let Some(path) = document
+                .get_first(path_field)
+                .and_then(|value| value.as_str())
+            else {
+                continue;
+            };
+            hits.push(ContentHit {
+                mount: mount.to_string(),
+                path: path.to_string(),
+                score,
+                snippet: snippets.snippet_from_doc(&document).to_html(),
+            });
+        }
+        Ok(hits)
+    }
+}
+
+#[cfg(feature = "fulltext")]
+fn schema() -> tantivy::schema::Schema {
+    use tantivy::schema::{Schema, STORED, STRING, TEXT};
+
+    let mut builder = Schema::builder();
+    builder.add_text_field("path", STRING | STORED);
+    builder.add_text_field("body", TEXT | STORED);
+    builder.build()
+}
+
+#[cfg(not(feature = "fulltext"))]
+impl FullTextIndex {
+    pub fn new(_data_dir: &Path) -> Self {
+        Self {}
+    }
+
+    pub fn rebuild(
+        &self,
+        _mount: &str,
+        _base: &Path,
+        _codec: Codec,
+        _gitignore: bool,
+    ) -> Result<usize> {
+        Err(anyhow!(
+            "LunaFinder was built without the `fulltext` feature"
+        ))
+    }
+
+    pub fn search(&self, _mount: &str, _query: &str, _limit: usize) -> Result<Vec<ContentHit>> {
+        Err(anyhow!(
+            "LunaFinder was built without the `fulltext` feature"
+        ))
+    }
+}
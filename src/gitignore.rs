@@ -0,0 +1,54 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::Path;
+use std::sync::Arc;
+
+const GITIGNORE: &str = ".gitignore";
+const GIT_DIR: &str = ".git";
+
+#[derive(Clone, Default)]
+pub struct Rules {
+    layers: Vec<Arc<Gitignore>>,
+}
+
+impl Rules {
+    pub fn load(base: &Path, relative: &Path) -> Self {
+        let mut rules = Self::default().child(base);
+        let mut directory = base.to_path_buf();
+        for component in relative.components() {
+            directory.push(component);
+            rules = rules.child(&directory);
+        }
+        rules
+    }
+
+    pub fn child(&self, directory: &Path) -> Self {
+        let mut layers = self.layers.clone();
+        let file = directory.join(GITIGNORE);
+        if file.is_file() {
+            let mut builder = GitignoreBuilder::new(directory);
+            if let Some(e) = builder.add(&file) {
+                log::warn!("Skipping invalid lines in {:?}: {}", file, e);
+            }
+            match builder.build() {
+                Ok(gitignore) => layers.push(Arc::new(gitignore)),
+                Err(e) => log::warn!("Failed to load {:?}: {}", file, e),
+            }
+        }
+        Self { layers }
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().is_some_and(|name| name == GIT_DIR) {
+            return true;
+        }
+        for layer in self.layers.iter().rev() {
+            match layer.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const PART_EXTENSION: &str = "part";
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    BrokenSymlink,
+    Unreadable,
+    Empty,
+    StalePart,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthIssue {
+    pub path: String,
+    pub kind: IssueKind,
+    pub detail: String,
+    pub removable: bool,
+}
+
+pub fn scan(base: &Path) -> Result<Vec<HealthIssue>> {
+    let mut issues = Vec::new();
+    walk(base, Path::new(""), &mut issues)?;
+    issues.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(issues)
+}
+
+pub fn cleanup(base: &Path, paths: &[PathBuf]) -> Result<usize> {
+    let mut removed = 0;
+    for relative in paths {
+        let path = base.join(relative);
+        match inspect(&path) {
+            Some((kind, _)) if kind != IssueKind::Unreadable => {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {:?}", relative))?;
+                removed += 1;
+            }
+            _ => log::warn!("Skipping {:?}: no longer needs cleanup", relative),
+        }
+    }
+    Ok(removed)
+}
+
+fn walk(base: &Path, relative: &Path, issues: &mut Vec<HealthIssue>) -> Result<()> {
+    let current = base.join(relative);
+    let entries = match fs::read_dir(&current) {
+        Ok(entries) => entries,
+        Err(e) => {
+            issues.push(issue(relative, IssueKind::Unreadable, e.to_string()));
+            return Ok(());
+        }
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with(".lunafinder_") {
+            continue;
+        }
+        let child_relative = relative.join(&name);
+        let path = entry.path();
+        if let Some((kind, detail)) = inspect(&path) {
+            issues.push(issue(&child_relative, kind, detail));
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            walk(base, &child_relative, issues)?;
+        }
+    }
+    Ok(())
+}
+
+fn inspect(path: &Path) -> Option<(IssueKind, String)> {
+    let link_metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return Some((IssueKind::Unreadable, e.to_string())),
+    };
+
+    if link_metadata.file_type().is_symlink() {
+        return match fs::metadata(path) {
+            Ok(_) => None,
+            Err(_) => {
+                let target = fs::read_link(path)
+                    .map(|target| target.display().to_string())
+                    .unwrap_or_default();
+                Some((IssueKind::BrokenSymlink, target))
+            }
+        };
+    }
+
+    if !link_metadata.is_file() {
+        return None;
+    }
+    if let Err(e) = fs::File::open(path) {
+        return Some((IssueKind::Unreadable, e.to_string()));
+    }
+
+    let stale = link_metadata
+        .modified()
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age >= STALE_AFTER);
+    let is_part = path
+        .extension()
+        .is_some_and(|extension| extension == PART_EXTENSION);
+
+    if is_part && stale {
+        Some((IssueKind::StalePart, String::new()))
+    } else if link_metadata.len() == 0 && stale {
+        Some((IssueKind::Empty, String::new()))
+    } else {
+        None
+    }
+}
+
+fn issue(relative: &Path, kind: IssueKind, detail: String) -> HealthIssue {
+    HealthIssue {
+        path: crate::osname::encode_path(relative),
+        kind,
+        detail,
+        removable: kind != IssueKind::Unreadable,
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::Path;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+const THEME: &str = "InspiredGitHub";
+const PLAIN_TEXT: &str = "Plain Text";
+
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        let mut themes = ThemeSet::load_defaults();
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes.remove(THEME).unwrap_or_default(),
+        }
+    }
+
+    pub fn supports(&self, path: &Path) -> bool {
+        self.syntax_for(path, "").is_some()
+    }
+
+    pub fn highlight(&self, path: &Path, content: &str) -> Option<String> {
+        let syntax = self.syntax_for(path, content)?;
+        match highlighted_html_for_string(content, &self.syntaxes, syntax, &self.theme) {
+            Ok(html) => Some(html),
+            Err(e) => {
+                log::warn!("Failed to highlight {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn syntax_for(&self, path: &Path, content: &str) -> Option<&SyntaxReference> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.syntaxes.find_syntax_by_extension(extension))
+            .or_else(|| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| self.syntaxes.find_syntax_by_extension(name))
+            })
+            .or_else(|| {
+                content
+                    .lines()
+                    .next()
+                    .and_then(|line| self.syntaxes.find_syntax_by_first_line(line))
+            })
+            .filter(|syntax| syntax.name != PLAIN_TEXT)
+    }
+}
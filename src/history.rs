@@ -0,0 +1,117 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::clock;
+
+pub const UNDO_WINDOW_SECS: u64 = 600;
+const MAX_ENTRIES_PER_USER: usize = 20;
+
+#[derive(Debug, Clone)]
+pub enum Operation {
+    Rename {
+        mount: String,
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Move {
+        source_mount: String,
+        source: PathBuf,
+        destination_mount: String,
+        destination: PathBuf,
+    },
+    Delete {
+        mount: String,
+        path: PathBuf,
+        trash: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub id: u64,
+    pub at: u64,
+    pub label: String,
+    pub operation: Operation,
+}
+
+#[derive(Serialize)]
+pub struct EntrySummary {
+    pub id: u64,
+    pub at: u64,
+    pub label: String,
+    pub undoable: bool,
+}
+
+#[derive(Default)]
+pub struct History {
+    entries: Mutex<HashMap<String, Vec<Entry>>>,
+    next_id: AtomicU64,
+}
+
+impl History {
+    pub fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn record(&self, username: &str, id: u64, label: String, operation: Operation) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        let log = entries.entry(username.to_string()).or_default();
+        let cutoff = clock::now_unix().saturating_sub(UNDO_WINDOW_SECS);
+        log.retain(|entry| entry.at > cutoff);
+        log.push(Entry {
+            id,
+            at: clock::now_unix(),
+            label,
+            operation,
+        });
+        if log.len() > MAX_ENTRIES_PER_USER {
+            let excess = log.len() - MAX_ENTRIES_PER_USER;
+            log.drain(..excess);
+        }
+    }
+
+    pub fn list(&self, username: &str) -> Vec<EntrySummary> {
+        let cutoff = clock::now_unix().saturating_sub(UNDO_WINDOW_SECS);
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let Some(log) = entries.get(username) else {
+            return Vec::new();
+        };
+        let last = log.last().map(|entry| entry.id);
+        log.iter()
+            .rev()
+            .filter(|entry| entry.at > cutoff)
+            .map(|entry| EntrySummary {
+                id: entry.id,
+                at: entry.at,
+                label: entry.label.clone(),
+                undoable: Some(entry.id) == last,
+            })
+            .collect()
+    }
+
+    pub fn last(&self, username: &str) -> Option<Entry> {
+        let cutoff = clock::now_unix().saturating_sub(UNDO_WINDOW_SECS);
+        self.entries
+            .lock()
+            .ok()?
+            .get(username)?
+            .last()
+            .filter(|entry| entry.at > cutoff)
+            .cloned()
+    }
+
+    pub fn remove(&self, username: &str, id: u64) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if let Some(log) = entries.get_mut(username) {
+                log.retain(|entry| entry.id != id);
+            }
+        }
+    }
+}
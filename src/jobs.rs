@@ -0,0 +1,102 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::events::JobStatus;
+
+const RETAINED_JOBS: usize = 256;
+
+pub struct Job {
+    pub id: u64,
+    pub username: Option<String>,
+    pub label: String,
+    total: AtomicU64,
+    done: AtomicU64,
+    cancelled: AtomicBool,
+    status: Mutex<JobStatus>,
+}
+
+#[derive(Serialize)]
+pub struct JobSnapshot {
+    pub id: u64,
+    pub label: String,
+    pub status: JobStatus,
+    pub done: u64,
+    pub total: u64,
+}
+
+impl Job {
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self) -> u64 {
+        self.done.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.status
+            .lock()
+            .map(|status| *status)
+            .unwrap_or(JobStatus::Failed)
+    }
+
+    pub fn set_status(&self, status: JobStatus) {
+        if let Ok(mut current) = self.status.lock() {
+            *current = status;
+        }
+    }
+
+    pub fn snapshot(&self) -> JobSnapshot {
+        JobSnapshot {
+            id: self.id,
+            label: self.label.clone(),
+            status: self.status(),
+            done: self.done.load(Ordering::Relaxed),
+            total: self.total(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<u64, Arc<Job>>>,
+}
+
+impl JobRegistry {
+    pub fn start(&self, id: u64, username: Option<String>, label: String) -> Arc<Job> {
+        let job = Arc::new(Job {
+            id,
+            username,
+            label,
+            total: AtomicU64::new(0),
+            done: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+            status: Mutex::new(JobStatus::Started),
+        });
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if jobs.len() >= RETAINED_JOBS {
+                jobs.retain(|_, job| job.status() == JobStatus::Started);
+            }
+            jobs.insert(id, job.clone());
+        }
+        job
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs.lock().ok()?.get(&id).cloned()
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::gitignore::Rules;
+use crate::{s3, signing};
+
+pub const DEFAULT_PAGE_LIMIT: usize = 100;
+pub const MAX_PAGE_LIMIT: usize = 1000;
+
+#[derive(Debug, Serialize)]
+pub struct CompactEntry {
+    pub name: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dir: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub mtime: u64,
+}
+
+impl CompactEntry {
+    fn sort_key(&self) -> (bool, &str) {
+        (!self.dir, &self.name)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page {
+    pub entries: Vec<CompactEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+pub fn read(directory: &Path, rules: Option<&Rules>) -> Result<Vec<CompactEntry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(directory)
+        .with_context(|| format!("Failed to read directory: {:?}", directory))?
+    {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        if crate::is_reserved_name(&file_name.to_string_lossy()) {
+            continue;
+        }
+        let name = crate::osname::encode(&file_name);
+        let metadata = entry.metadata()?;
+        let dir = metadata.is_dir();
+        if rules.is_some_and(|rules| rules.is_ignored(&entry.path(), dir)) {
+            continue;
+        }
+        entries.push(CompactEntry {
+            name,
+            dir,
+            size: if dir { None } else { Some(metadata.len()) },
+            mtime: s3::modified_unix(&metadata),
+        });
+    }
+    entries.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    Ok(entries)
+}
+
+pub fn etag(entries: &[CompactEntry], cursor: Option<&str>, limit: usize) -> String {
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        hasher.update(format!(
+            "{}\0{}\0{}\0{}\n",
+            entry.dir,
+            entry.name,
+            entry.size.unwrap_or(0),
+            entry.mtime
+        ));
+    }
+    hasher.update(format!("{}\0{}", cursor.unwrap_or_default(), limit));
+    format!("\"{}\"", &signing::hex(&hasher.finalize())[..32])
+}
+
+pub fn page(entries: Vec<CompactEntry>, cursor: Option<&str>, limit: usize) -> Option<Page> {
+    let start = match cursor {
+        Some(cursor) => {
+            let (dir, name) = decode_cursor(cursor)?;
+            let after = (!dir, name.as_str());
+            entries.partition_point(|entry| entry.sort_key() <= after)
+        }
+        None => 0,
+    };
+
+    let mut entries: Vec<CompactEntry> = entries.into_iter().skip(start).collect();
+    let next = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(encode_cursor)
+    } else {
+        None
+    };
+    Some(Page { entries, next })
+}
+
+fn encode_cursor(entry: &CompactEntry) -> String {
+    let kind = if entry.dir { 'd' } else { 'f' };
+    URL_SAFE_NO_PAD.encode(format!("{}{}", kind, entry.name))
+}
+
+fn decode_cursor(cursor: &str) -> Option<(bool, String)> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let mut chars = decoded.chars();
+    let dir = match chars.next()? {
+        'd' => true,
+        'f' => false,
+        _ => return None,
+    };
+    Some((dir, chars.as_str().to_string()))
+}
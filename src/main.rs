@@ -1,39 +1,169 @@
+mod accounts;
+mod assets;
 mod auth;
+mod cas;
+mod checksums;
+mod cli;
+mod clock;
 mod config;
+mod confirm;
+mod content;
+mod crypto;
+mod duplicates;
+mod error;
+mod events;
+mod format;
+mod ftp;
+mod fulltext;
+mod gitignore;
+mod health;
+mod highlight;
+mod history;
+mod jobs;
+mod listener;
+mod listing;
+mod manifest;
+mod metrics;
+mod moderation;
+mod negotiate;
+mod notify;
+mod osname;
+mod pattern;
+mod permissions;
+mod pins;
+mod quarantine;
+mod releases;
+mod s3;
+mod search;
+mod security;
+mod session;
+mod signing;
+mod stats;
+mod storage;
+mod sync;
+mod tags;
+mod terms;
+mod thumbs;
+mod tls;
+mod tokens;
+mod trash;
+mod upload;
+mod urls;
+mod usage;
+mod webdav;
+mod wiki;
 
 use actix_files::NamedFile;
 use actix_multipart::Multipart;
 use actix_web::{
+    body::{BodySize, MessageBody},
     cookie::{time::Duration, Cookie},
-    error,
-    http::header,
-    middleware::Logger,
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header, KeepAlive},
+    middleware::{from_fn, Condition, DefaultHeaders, Logger, Next},
     web, App, HttpRequest, HttpResponse, HttpServer,
 };
 use anyhow::{anyhow, Context as AnyhowContext};
-use futures_util::TryStreamExt as _;
+use clap::Parser as _;
+use futures_util::{StreamExt as _, TryStreamExt as _};
 use pulldown_cmark::{html, Options, Parser};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
+    collections::HashMap,
+    ffi::OsString,
     fs,
-    io::Write,
+    io::{Read, Write},
     path::{Component, Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration as StdDuration, Instant},
 };
 use tera::{Context as TeraContext, Tera};
+use tokio::io::AsyncWriteExt as _;
+use tokio::sync::Semaphore;
 
 use auth::verify_password;
-use config::{Config, MountConfig, Permission};
+use config::{Config, LimitsConfig, MountConfig, Permission, Severity, SizeUnits, TreeConfig};
+use error::LunaError;
+use quarantine::ScanOutcome;
 
 type ActixResult<T> = Result<T, actix_web::Error>;
 
 const SESSION_COOKIE: &str = "lunafinder_session";
-const TREE_MAX_DEPTH: usize = 12;
+const VIEW_AS_COOKIE: &str = "lunafinder_view_as";
+const VIEW_AS_TTL_SECS: u64 = 1800;
+const LEGAL_PAGES: [(&str, &str); 3] = [
+    ("about", "소개"),
+    ("terms", "이용약관"),
+    ("privacy", "개인정보처리방침"),
+];
+const TERMS_EXEMPT_PATHS: [&str; 7] = [
+    "/login",
+    "/logout",
+    "/terms",
+    "/about",
+    "/privacy",
+    "/static",
+    "/branding",
+];
+const GUEST_TOKEN_ACTIONS: [&str; 4] = ["read", "upload", "rename", "delete"];
+const DEFAULT_GUEST_TOKEN_HOURS: u64 = 24;
+const DEFAULT_PRESIGN_HOURS: u64 = 24;
+const TOKEN_FLUSH_SECS: u64 = 30;
+const SSE_HEARTBEAT_SECS: u64 = 15;
+const DEFAULT_LONG_POLL_SECS: u64 = 30;
+const MAX_LONG_POLL_SECS: u64 = 60;
+const MAX_SUBDIR_LEN: usize = 4096;
+const MAX_UPLOAD_METADATA_LEN: usize = 64 * 1024;
+const MAX_PREVIEW_SIZE: u64 = 16 * 1024 * 1024;
+const FOLDER_README: &str = "README.md";
+const DEFAULT_FOLDER_README: &str = "# {name}\n\n{creator}님이 {date}에 만든 폴더입니다.\n";
+const DELETE_PROGRESS_INTERVAL: u64 = 1000;
+const DEFAULT_MAINTENANCE_MESSAGE: &str = "점검 중입니다. 잠시 동안 파일을 변경할 수 없습니다.";
 
 #[derive(Clone)]
 struct AppState {
     config: Arc<Config>,
-    tera: Arc<Tera>,
+    config_hash: Arc<String>,
+    tera: Arc<RwLock<Tera>>,
+    assets: Arc<assets::AssetManifest>,
+    blocking_jobs: Arc<Semaphore>,
+    maintenance: Arc<AtomicBool>,
+    announcement: Arc<RwLock<Option<ActiveAnnouncement>>>,
+    terms: Arc<terms::TermsAcceptance>,
+    accounts: Arc<accounts::AccountStatus>,
+    pins: Arc<pins::PinStore>,
+    tags: Arc<tags::TagStore>,
+    highlighter: Arc<highlight::Highlighter>,
+    thumbs: Arc<thumbs::ThumbCache>,
+    stats: Arc<stats::StatsLog>,
+    tokens: Arc<tokens::TokenStore>,
+    notifier: Option<notify::Notifier>,
+    events: Arc<events::EventBus>,
+    jobs: Arc<jobs::JobRegistry>,
+    history: Arc<history::History>,
+    journal: Arc<sync::ChangeJournal>,
+    hash_cache: Arc<sync::HashCache>,
+    usage: Arc<usage::UsageCache>,
+    dav_locks: Arc<webdav::LockTable>,
+    uploads: Arc<upload::UploadStore>,
+    fulltext: Arc<fulltext::FullTextIndex>,
+    signer: Arc<signing::UrlSigner>,
+    sessions: Arc<session::SessionSigner>,
+    mount_keys: Arc<HashMap<String, crypto::MountKey>>,
+    metrics: Arc<metrics::Metrics>,
+    security: Arc<security::SecurityMonitor>,
+    permissions: Arc<permissions::PermissionTable>,
+}
+
+#[derive(Clone, Serialize)]
+struct ActiveAnnouncement {
+    message: String,
+    severity: Severity,
+    expires_at: Option<u64>,
 }
 
 #[derive(Deserialize)]
@@ -42,27 +172,491 @@ struct LoginForm {
     password: String,
 }
 
+#[derive(Deserialize)]
+struct NegotiateQuery {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GuestTokenForm {
+    actions: String,
+    expires_in_hours: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DropForm {
+    expires_in_hours: Option<String>,
+    max_size_mb: Option<String>,
+    extensions: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PresignForm {
+    target_path: String,
+    expires_in_hours: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SignedDownloadQuery {
+    expires: u64,
+    sig: String,
+}
+
+#[derive(Deserialize)]
+struct RevokeShareForm {
+    token: String,
+    #[serde(default)]
+    all_users: bool,
+}
+
+#[derive(Deserialize)]
+struct TransferForm {
+    target_path: String,
+    destination_mount: String,
+    destination_path: String,
+    operation: String,
+    #[serde(default)]
+    confirm: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RelocateForm {
+    target_path: String,
+    destination_path: String,
+    #[serde(default)]
+    confirm: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    cursor: String,
+}
+
+#[derive(Deserialize)]
+struct LongPollQuery {
+    since: Option<String>,
+    timeout: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    path: Option<String>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ApiPathQuery {
+    path: Option<String>,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Default, Deserialize)]
+struct UploadMetadata {
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    overwrite: OverwritePolicy,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    files: HashMap<String, FileUploadMetadata>,
+}
+
+#[derive(Default, Deserialize)]
+struct FileUploadMetadata {
+    name: Option<String>,
+    sha256: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OverwritePolicy {
+    #[default]
+    Fail,
+    Replace,
+    Rename,
+}
+
+#[derive(Deserialize)]
+struct ManifestForm {
+    format: Option<String>,
+    expires_in_hours: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ManifestRequest {
+    paths: Vec<String>,
+    expires_in_hours: Option<u64>,
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiPathRequest {
+    path: String,
+    confirm: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ResumableUploadRequest {
+    path: String,
+    size: u64,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Deserialize)]
+struct ApiRenameRequest {
+    path: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ApiTransferRequest {
+    path: String,
+    destination: Option<String>,
+    overwrite: Option<bool>,
+    depth: Option<String>,
+    confirm: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct S3ListQuery {
+    #[serde(default)]
+    prefix: String,
+    delimiter: Option<String>,
+    #[serde(rename = "max-keys")]
+    max_keys: Option<usize>,
+    #[serde(rename = "continuation-token")]
+    continuation_token: Option<String>,
+    #[serde(rename = "start-after")]
+    start_after: Option<String>,
+}
+
+struct DavTarget<'a> {
+    mount_name: String,
+    mount: &'a MountConfig,
+    base: PathBuf,
+    relative: PathBuf,
+    path: PathBuf,
+    permission: Permission,
+    username: Option<String>,
+}
+
+struct S3Target<'a> {
+    mount: &'a MountConfig,
+    username: String,
+    payload_hash: String,
+    relative_path: PathBuf,
+    permission: Permission,
+}
+
 #[derive(Deserialize)]
 struct RenameForm {
     target_path: String,
     new_name: String,
 }
 
+#[derive(Deserialize)]
+struct MkdirForm {
+    name: String,
+}
+
 #[derive(Deserialize)]
 struct DeleteForm {
     target_path: String,
+    #[serde(default)]
+    confirm: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ModerationForm {
+    target_path: String,
+}
+
+#[derive(Deserialize)]
+struct TrashForm {
+    #[serde(default)]
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct MaintenanceForm {
+    enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct AccountForm {
+    username: String,
+    disabled: bool,
+}
+
+#[derive(Deserialize)]
+struct PermissionCheckQuery {
+    #[serde(default)]
+    user: Option<String>,
+    mount: String,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    action: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StaticQuery {
+    v: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ViewAsForm {
+    username: String,
+}
+
+#[derive(Serialize)]
+struct AccountSummary {
+    name: String,
+    disabled: bool,
+}
+
+#[derive(Deserialize)]
+struct PinForm {
+    mount: String,
+    action: String,
+}
+
+#[derive(Deserialize)]
+struct AnnouncementForm {
+    message: String,
+    severity: Severity,
+    expires_in_hours: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    path: Option<String>,
+    mode: Option<String>,
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ThumbQuery {
+    size: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ModelQuery {
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StatsQuery {
+    format: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct EditForm {
     content: String,
+    #[serde(default)]
+    base_version: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DiffLine {
+    kind: &'static str,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ListingQuery {
+    page: Option<usize>,
+    per_page: Option<usize>,
+    sort: Option<String>,
+    order: Option<String>,
+    view: Option<String>,
+    download: Option<String>,
+}
+
+impl ListingQuery {
+    fn wants_download(&self) -> bool {
+        wants_download(self.download.as_deref())
+    }
+}
+
+#[derive(Deserialize)]
+struct DownloadQuery {
+    download: Option<String>,
+}
+
+impl DownloadQuery {
+    fn wants_download(&self) -> bool {
+        wants_download(self.download.as_deref())
+    }
+}
+
+fn wants_download(value: Option<&str>) -> bool {
+    matches!(value, Some("1") | Some("true"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListingSort {
+    Name,
+    Size,
+    Mtime,
+    Created,
+}
+
+impl ListingSort {
+    const ALL: [ListingSort; 4] = [
+        ListingSort::Name,
+        ListingSort::Size,
+        ListingSort::Mtime,
+        ListingSort::Created,
+    ];
+
+    fn parse(value: Option<&str>) -> Result<Self, LunaError> {
+        match value.unwrap_or("name") {
+            "name" => Ok(ListingSort::Name),
+            "size" => Ok(ListingSort::Size),
+            "mtime" => Ok(ListingSort::Mtime),
+            "created" => Ok(ListingSort::Created),
+            other => Err(LunaError::bad_request(format!(
+                "Unsupported sort key: {}",
+                other
+            ))),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ListingSort::Name => "name",
+            ListingSort::Size => "size",
+            ListingSort::Mtime => "mtime",
+            ListingSort::Created => "created",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ListingSort::Name => "이름",
+            ListingSort::Size => "크기",
+            ListingSort::Mtime => "수정 시각",
+            ListingSort::Created => "만든 시각",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ListingView {
+    sort: ListingSort,
+    descending: bool,
+    page: usize,
+    per_page: usize,
+    gallery: bool,
+}
+
+impl ListingView {
+    fn from_query(config: &Config, query: &ListingQuery) -> Result<Self, LunaError> {
+        let descending = match query.order.as_deref().unwrap_or("asc") {
+            "asc" => false,
+            "desc" => true,
+            other => {
+                return Err(LunaError::bad_request(format!(
+                    "Unsupported sort order: {}",
+                    other
+                )))
+            }
+        };
+        let gallery = match query.view.as_deref().unwrap_or("list") {
+            "list" => false,
+            "gallery" => true,
+            other => {
+                return Err(LunaError::bad_request(format!(
+                    "Unsupported view: {}",
+                    other
+                )))
+            }
+        };
+        Ok(ListingView {
+            sort: ListingSort::parse(query.sort.as_deref())?,
+            descending,
+            page: query.page.unwrap_or(1).max(1),
+            per_page: query
+                .per_page
+                .unwrap_or(config.display.per_page)
+                .clamp(1, config.display.max_per_page),
+            gallery,
+        })
+    }
+
+    fn href(&self, config: &Config, guest: Option<&tokens::GuestToken>) -> String {
+        let mut params = Vec::new();
+        if let Some(guest) = guest {
+            params.push(format!("token={}", guest.token));
+        }
+        if self.page > 1 {
+            params.push(format!("page={}", self.page));
+        }
+        if self.per_page != config.display.per_page {
+            params.push(format!("per_page={}", self.per_page));
+        }
+        if self.sort != ListingSort::Name {
+            params.push(format!("sort={}", self.sort.as_str()));
+        }
+        if self.descending {
+            params.push("order=desc".to_string());
+        }
+        if self.gallery {
+            params.push("view=gallery".to_string());
+        }
+        format!("?{}", params.join("&"))
+    }
+}
+
+#[derive(Serialize)]
+struct PageLink {
+    number: usize,
+    href: String,
+    current: bool,
+    gap_before: bool,
+}
+
+#[derive(Serialize)]
+struct SortLink {
+    key: &'static str,
+    label: &'static str,
+    href: String,
+    active: bool,
+    descending: bool,
 }
 
 #[derive(Serialize)]
 struct FileEntry {
     name: String,
+    link: String,
+    #[serde(skip)]
+    file_name: OsString,
     is_dir: bool,
     size: Option<u64>,
+    mtime: u64,
+    created: Option<u64>,
+    modified_relative: String,
+    viewer: Option<&'static str>,
+    thumbnail: bool,
+    can_rename: bool,
+    can_delete: bool,
+    can_modify: bool,
 }
 
 #[derive(Serialize)]
@@ -70,6 +664,20 @@ struct DirectoryNode {
     name: String,
     path: String,
     children: Vec<DirectoryNode>,
+    truncated: bool,
+    omitted: usize,
+}
+
+#[derive(Serialize)]
+struct Breadcrumb {
+    name: String,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct FooterLink {
+    path: String,
+    label: String,
 }
 
 #[derive(Serialize)]
@@ -77,58 +685,384 @@ struct MountSummary {
     name: String,
     description: String,
     public: bool,
+    icon: Option<String>,
+    color: Option<String>,
+    pinned: bool,
+    releases: bool,
+    #[serde(skip)]
+    order: i64,
 }
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
-    if std::env::var_os("RUST_LOG").is_none() {
+    let cli = cli::Cli::parse();
+
+    if let Some(log_level) = &cli.log_level {
+        std::env::set_var("RUST_LOG", log_level);
+    } else if std::env::var_os("RUST_LOG").is_none() {
         std::env::set_var("RUST_LOG", "info");
     }
     env_logger::init();
 
-    let mut config = Config::load_or_create("config.toml")?;
+    let mut config = Config::load_or_create(&cli.config)?;
     ensure_mount_directories(&config)?;
+    purge_undo_leftovers(&config);
+    for (name, mount) in config.mounts.iter().filter(|(_, mount)| mount.dedup) {
+        let removed = cas::collect_garbage(&mount.path)
+            .with_context(|| format!("Failed to clean blob store of mount '{}'", name))?;
+        if removed > 0 {
+            log::info!(
+                "Removed {} unreferenced blobs from mount '{}'",
+                removed,
+                name
+            );
+        }
+    }
+
+    config = Config::load_or_create(&cli.config)?;
+    if let Some(data_dir) = cli.data_dir {
+        config.server.data_dir = Some(data_dir);
+    }
+    if cli.host.is_some() || cli.port.is_some() {
+        config.server.bind.clear();
+    }
+    if let Some(host) = cli.host {
+        config.server.host = host;
+    }
+    if let Some(port) = cli.port {
+        config.server.port = port;
+    }
+    if cli.read_only {
+        config.server.read_only = true;
+    }
+    if cli.dev {
+        config.server.dev = true;
+    }
+
+    let data_dir = config.data_dir();
+    fs::create_dir_all(&data_dir)
+        .with_context(|| format!("Failed to create data directory: {:?}", data_dir))?;
+    if let Some(temp_dir) = &config.server.uploads.temp_dir {
+        fs::create_dir_all(temp_dir)
+            .with_context(|| format!("Failed to create upload temp directory: {:?}", temp_dir))?;
+        purge_staged_uploads(temp_dir);
+    }
 
-    config = Config::load_or_create("config.toml")?;
+    let templates_glob = format!("{}/**/*", config.server.templates_dir.display());
+    let mut tera = Tera::new(&templates_glob).context("Failed to load templates")?;
+    tera.register_filter("filesize", format::filesize_filter);
+    tera.register_filter("url_segment", urls::segment_filter);
+    tera.register_filter("url_path", urls::path_filter);
+    let assets = Arc::new(assets::AssetManifest::load(&config.server.static_dir)?);
+    tera.register_function("static_url", assets::url_function(assets.clone()));
+
+    let terms = terms::TermsAcceptance::load(&data_dir)?;
+    let accounts = accounts::AccountStatus::load(&data_dir)?;
+    let pins = pins::PinStore::load(&data_dir)?;
+    let mount_keys = crypto::load_keys(&config)?;
+    let signer = signing::UrlSigner::load(&data_dir, config.server.url_signing_secret.as_deref())?;
+    let guest_tokens = tokens::TokenStore::load(&data_dir, signer.derive_key("share visitors"))?;
+    let sessions =
+        session::SessionSigner::load(&data_dir, config.server.session_secret.as_deref())?;
+    let blocking_jobs = Semaphore::new(config.server.blocking.max_jobs.max(1));
+    let maintenance = AtomicBool::new(config.server.maintenance);
+    let announcement =
+        config
+            .main_page
+            .announcement
+            .as_ref()
+            .map(|announcement| ActiveAnnouncement {
+                message: announcement.message.clone(),
+                severity: announcement.severity,
+                expires_at: announcement
+                    .expires_at
+                    .as_ref()
+                    .and_then(clock::datetime_to_unix),
+            });
 
-    let tera = Tera::new("templates/**/*").context("Failed to load templates")?;
+    let permissions = permissions::PermissionTable::compile(&config);
+    let config_hash = {
+        let mut hasher = Sha256::new();
+        hasher.update(toml::to_string(&config).unwrap_or_default());
+        hasher.update(clock::now_unix().to_le_bytes());
+        signing::hex(&hasher.finalize())
+    };
+    let config = Arc::new(config);
+    let notifier = notify::Notifier::start(config.clone());
 
     let state = AppState {
-        config: Arc::new(config),
-        tera: Arc::new(tera),
+        config,
+        config_hash: Arc::new(config_hash),
+        tera: Arc::new(RwLock::new(tera)),
+        assets,
+        blocking_jobs: Arc::new(blocking_jobs),
+        maintenance: Arc::new(maintenance),
+        announcement: Arc::new(RwLock::new(announcement)),
+        terms: Arc::new(terms),
+        accounts: Arc::new(accounts),
+        pins: Arc::new(pins),
+        tags: Arc::new(tags::TagStore::load(&data_dir)?),
+        highlighter: Arc::new(highlight::Highlighter::new()),
+        thumbs: Arc::new(thumbs::ThumbCache::new(&data_dir)),
+        stats: Arc::new(stats::StatsLog::new(&data_dir)),
+        tokens: Arc::new(guest_tokens),
+        notifier,
+        events: Arc::new(events::EventBus::default()),
+        jobs: Arc::new(jobs::JobRegistry::default()),
+        history: Arc::new(history::History::default()),
+        journal: Arc::new(sync::ChangeJournal::default()),
+        hash_cache: Arc::new(sync::HashCache::default()),
+        usage: Arc::new(usage::UsageCache::default()),
+        dav_locks: Arc::new(webdav::LockTable::default()),
+        uploads: Arc::new(upload::UploadStore::load(&data_dir)?),
+        fulltext: Arc::new(fulltext::FullTextIndex::new(&data_dir)),
+        signer: Arc::new(signer),
+        sessions: Arc::new(sessions),
+        mount_keys: Arc::new(mount_keys),
+        metrics: Arc::new(metrics::Metrics::default()),
+        security: Arc::new(security::SecurityMonitor::default()),
+        permissions: Arc::new(permissions),
     };
 
-    let server_host = state.config.server.host.clone();
-    let server_port = state.config.server.port;
+    let addresses = state.config.server.listen_addresses()?;
+    let limits = state.config.server.limits.clone();
+    let tuning = state.config.server.tuning.clone();
+    let http2 = state.config.server.http2;
+    let negotiate_enabled = state
+        .config
+        .negotiate
+        .as_ref()
+        .is_some_and(|negotiate| negotiate.enabled);
+    let dev = state.config.server.dev;
+    if dev {
+        log::info!("Development mode: templates are reloaded on every render");
+    }
+    let tls_config = state
+        .config
+        .server
+        .tls
+        .as_ref()
+        .map(tls::load_server_config)
+        .transpose()?;
+
+    ftp::start(state.clone())?;
+    start_fulltext_indexer(state.clone());
+    start_token_flusher(state.clone());
+    let guest_tokens = state.tokens.clone();
 
-    HttpServer::new(move || {
+    let mut server = HttpServer::new(move || {
         App::new()
+            .wrap(Condition::new(
+                dev,
+                DefaultHeaders::new().add((header::CACHE_CONTROL, "no-store")),
+            ))
+            .wrap(from_fn(terms_gate))
+            .wrap(from_fn(view_as_gate))
+            .wrap(from_fn(request_deadline))
+            .wrap(from_fn(error::problem_details))
+            .wrap(from_fn(track_metrics))
+            .wrap(from_fn(watch_denials))
             .wrap(Logger::default())
             .app_data(web::Data::new(state.clone()))
+            .app_data(web::FormConfig::default().limit(limits.max_form_size))
+            .app_data(web::PayloadConfig::new(limits.max_payload_size))
             .route("/", web::get().to(index))
             .service(
                 web::resource("/login")
                     .route(web::get().to(login_page))
                     .route(web::post().to(login)),
             )
+            .route("/login/negotiate", web::get().to(negotiate_login))
             .route("/logout", web::get().to(logout))
+            .service(
+                web::resource("/terms/accept")
+                    .route(web::get().to(terms_page))
+                    .route(web::post().to(accept_terms)),
+            )
+            .route("/branding/logo", web::get().to(branding_logo))
+            .route("/static/{path:.*}", web::get().to(static_asset))
+            .route("/{page:about|terms|privacy}", web::get().to(legal_page))
+            .route("/admin/maintenance", web::post().to(set_maintenance))
+            .route("/account/pins", web::post().to(update_pins))
+            .route("/admin/announcement", web::post().to(set_announcement))
+            .route("/admin/accounts", web::post().to(set_account_disabled))
+            .route("/admin/view-as", web::post().to(start_view_as))
+            .route("/admin/view-as/stop", web::post().to(stop_view_as))
+            .route("/admin/stats", web::get().to(export_stats))
+            .route("/admin/shares", web::get().to(admin_shares_page))
+            .route("/account/shares", web::get().to(account_shares_page))
+            .route("/events", web::get().to(event_stream))
+            .route("/api/history", web::get().to(history_list))
+            .route("/api/history/undo", web::post().to(history_undo_api))
+            .route("/history/undo", web::post().to(history_undo))
+            .route("/api/jobs/{id}", web::get().to(job_status))
+            .route("/api/jobs/{id}/cancel", web::post().to(cancel_job))
+            .route("/metrics", web::get().to(metrics_endpoint))
+            .route("/api/sync/{mount}/manifest", web::get().to(sync_manifest))
+            .route("/api/sync/{mount}/changes", web::get().to(sync_changes))
+            .route("/api/permissions/check", web::get().to(check_permission))
+            .route("/api/mounts/stats", web::get().to(mount_stats))
+            .service(
+                web::resource("/api/v1/uploads/{id}")
+                    .route(web::get().to(resumable_upload_status))
+                    .route(web::head().to(resumable_upload_status))
+                    .route(web::patch().to(resumable_upload_append))
+                    .route(web::delete().to(resumable_upload_cancel)),
+            )
+            .route("/api/v1/mounts", web::get().to(api_mounts))
+            .route("/api/v1/{mount}/list", web::get().to(api_list))
+            .route("/api/v1/{mount}/stat", web::get().to(api_stat))
+            .route("/api/v1/{mount}/download", web::get().to(api_download))
+            .route("/api/v1/{mount}/download", web::head().to(api_download))
+            .route("/api/v1/{mount}/upload", web::put().to(api_upload))
+            .route(
+                "/api/v1/{mount}/upload",
+                web::post().to(api_upload_multipart),
+            )
+            .route("/api/v1/{mount}/paste", web::post().to(api_paste))
+            .route(
+                "/api/v1/{mount}/uploads",
+                web::post().to(resumable_upload_create),
+            )
+            .route("/api/v1/{mount}/delete", web::post().to(api_delete))
+            .route("/api/v1/{mount}/manifest", web::post().to(api_manifest))
+            .route("/api/v1/{mount}/rename", web::post().to(api_rename))
+            .route("/api/v1/{mount}/mkdir", web::post().to(api_mkdir))
+            .route("/api/v1/{mount}/copy", web::post().to(api_copy))
+            .route("/api/v1/{mount}/move", web::post().to(api_move))
+            .route("/api/{mount}/changes", web::get().to(long_poll_changes))
+            .route("/account/shares/revoke", web::post().to(revoke_share))
+            .route("/dl/{mount}/{tail:.*}", web::get().to(signed_download))
+            .service(
+                web::resource("/drop/{token}")
+                    .route(web::get().to(drop_page))
+                    .route(web::post().to(drop_upload)),
+            )
+            .route("/dav/{mount}{tail:.*}", web::route().to(webdav::handle))
+            .route("/s3/{bucket}", web::get().to(s3_list_objects))
+            .service(
+                web::resource("/s3/{bucket}/{key:.*}")
+                    .route(web::get().to(s3_get_object))
+                    .route(web::head().to(s3_get_object))
+                    .route(web::put().to(s3_put_object))
+                    .route(web::delete().to(s3_delete_object)),
+            )
             .service(
                 web::scope("/browse")
                     .route("/{mount}/{tail:.*}", web::get().to(browse))
                     .route("/{mount}/{tail:.*}/upload", web::post().to(upload_file))
                     .route("/{mount}/{tail:.*}/delete", web::post().to(delete_entry))
-                    .route("/{mount}/{tail:.*}/rename", web::post().to(rename_entry)),
+                    .route("/{mount}/{tail:.*}/rename", web::post().to(rename_entry))
+                    .route("/{mount}/{tail:.*}/mkdir", web::post().to(create_folder))
+                    .route("/{mount}/{tail:.*}/token", web::post().to(mint_guest_token))
+                    .route("/{mount}/{tail:.*}/drop", web::post().to(mint_drop_box))
+                    .route(
+                        "/{mount}/{tail:.*}/manifest",
+                        web::post().to(browse_manifest),
+                    )
+                    .route(
+                        "/{mount}/{tail:.*}/presign",
+                        web::post().to(presign_download),
+                    )
+                    .route(
+                        "/{mount}/{tail:.*}/transfer",
+                        web::post().to(transfer_entry),
+                    )
+                    .route("/{mount}/{tail:.*}/move", web::post().to(move_within_mount))
+                    .route("/{mount}/{tail:.*}/copy", web::post().to(copy_within_mount)),
+            )
+            .route("/moderate/{mount}", web::get().to(moderation_page))
+            .route(
+                "/moderate/{mount}/{action}",
+                web::post().to(moderation_action),
+            )
+            .route("/quarantine/{mount}", web::get().to(quarantine_page))
+            .route(
+                "/quarantine/{mount}/{action}",
+                web::post().to(quarantine_action),
+            )
+            .route("/releases/{mount}", web::get().to(releases_page))
+            .route("/model/{mount}/{tail:.*}", web::get().to(model_viewer))
+            .route("/thumb/{mount}/{tail:.*}", web::get().to(thumbnail))
+            .route("/image/{mount}/{tail:.*}", web::get().to(image_viewer))
+            .service(
+                web::resource("/media/{mount}/{tail:.*}")
+                    .route(web::get().to(media_stream))
+                    .route(web::head().to(media_stream)),
+            )
+            .route("/preview/{mount}/{tail:.*}", web::get().to(preview_page))
+            .route("/search", web::get().to(search_all))
+            .route("/search/{mount}", web::get().to(search_mount))
+            .route("/trash/{mount}", web::get().to(trash_page))
+            .route("/trash/{mount}/{action}", web::post().to(trash_action))
+            .route("/duplicates/{mount}", web::get().to(duplicates_page))
+            .route(
+                "/duplicates/{mount}/hardlink",
+                web::post().to(hardlink_duplicates),
+            )
+            .route("/health/{mount}", web::get().to(health_page))
+            .route("/health/{mount}/cleanup", web::post().to(health_cleanup))
+            .route("/checksums/{mount}", web::get().to(checksums_page))
+            .route(
+                "/checksums/{mount}/refresh",
+                web::post().to(checksums_refresh),
             )
             .service(
                 web::resource("/edit/{mount}/{tail:.*}")
                     .route(web::get().to(edit_page))
                     .route(web::post().to(edit_save)),
             )
-    })
-    .bind((server_host.as_str(), server_port))?
-    .run()
-    .await?;
+    });
+
+    if negotiate_enabled && !negotiate::available() {
+        log::warn!("[negotiate] is enabled but this build lacks the `spnego` feature; users will fall back to form login");
+    }
+
+    if tls_config.is_some() && !http2 {
+        log::warn!("http2 = false has no effect on TLS listeners; ALPN still offers h2");
+    }
+
+    if let Some(workers) = tuning.workers {
+        server = server.workers(workers);
+    }
+    if let Some(max_connections) = tuning.max_connections {
+        server = server.max_connections(max_connections);
+    }
+    if let Some(keep_alive_secs) = tuning.keep_alive_secs {
+        server = server.keep_alive(match keep_alive_secs {
+            0 => KeepAlive::Disabled,
+            secs => KeepAlive::Timeout(StdDuration::from_secs(secs)),
+        });
+    }
+    if let Some(client_timeout_secs) = tuning.client_timeout_secs {
+        server = server.client_request_timeout(StdDuration::from_secs(client_timeout_secs));
+    }
+
+    let mut listeners = listener::inherited();
+    if listeners.is_empty() {
+        for address in addresses {
+            listeners.push(
+                listener::bind(address)
+                    .with_context(|| format!("Failed to bind listener: {}", address))?,
+            );
+        }
+    } else {
+        log::info!("Using {} socket(s) inherited from systemd", listeners.len());
+    }
+
+    for listener in listeners {
+        server = match &tls_config {
+            Some(tls_config) => server.listen_rustls_0_23(listener, tls_config.clone())?,
+            None if http2 => server.listen_auto_h2c(listener)?,
+            None => server.listen(listener)?,
+        };
+    }
+
+    server.run().await?;
 
+    if let Err(e) = guest_tokens.flush() {
+        log::warn!("Failed to save share analytics: {}", e);
+    }
     Ok(())
 }
 
@@ -143,62 +1077,223 @@ async fn index(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<Http
         None
     };
 
-    let mut mounts = Vec::new();
-    for (name, mount) in &config.mounts {
-        let permission = effective_permission(config, username.as_deref(), mount);
-        if username.is_some() {
-            if permission.is_some() {
-                mounts.push(MountSummary {
-                    name: name.clone(),
-                    description: mount.description.clone(),
-                    public: mount.public,
-                });
+    let mounts = visible_mounts(&state, username.as_deref());
+
+    let mut context = TeraContext::new();
+    context.insert("title", &config.main_page.title);
+    context.insert("description", &config.main_page.description);
+    context.insert("markdown_content", &markdown_content);
+    context.insert("mounts", &mounts);
+    context.insert("is_admin", &is_admin(config, username.as_deref()));
+    if is_admin(config, username.as_deref()) {
+        let mut accounts: Vec<AccountSummary> = config
+            .users
+            .keys()
+            .map(|name| AccountSummary {
+                name: name.clone(),
+                disabled: state.accounts.is_disabled(config, name),
+            })
+            .collect();
+        accounts.sort_by(|a, b| a.name.cmp(&b.name));
+        context.insert("accounts", &accounts);
+    }
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+    if let Some((admin, _)) = view_as(&req) {
+        context.insert("viewing_as_admin", &admin);
+    }
+
+    let html = render_template(&state, "index.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+fn visible_mounts(state: &AppState, username: Option<&str>) -> Vec<MountSummary> {
+    let config = &state.config;
+    let mut mounts = Vec::new();
+    for (name, mount) in config.mounts.iter().filter(|(_, mount)| !mount.hidden) {
+        let permission = effective_permission(state, username, None, name, Path::new(""));
+        if username.is_some() {
+            if permission.is_some() {
+                mounts.push(MountSummary {
+                    name: name.clone(),
+                    description: mount.description.clone(),
+                    public: mount.public,
+                    icon: mount.icon.clone(),
+                    color: mount.color.clone(),
+                    pinned: false,
+                    releases: mount.releases.enabled,
+                    order: mount.order,
+                });
             }
         } else if mount.public {
             mounts.push(MountSummary {
                 name: name.clone(),
                 description: mount.description.clone(),
                 public: true,
+                icon: mount.icon.clone(),
+                color: mount.color.clone(),
+                pinned: false,
+                releases: mount.releases.enabled,
+                order: mount.order,
             });
         }
     }
 
-    mounts.sort_by(|a, b| a.name.cmp(&b.name));
-
-    let mut context = TeraContext::new();
-    context.insert("title", &config.main_page.title);
-    context.insert("description", &config.main_page.description);
-    context.insert("markdown_content", &markdown_content);
-    context.insert("mounts", &mounts);
-    if let Some(ref username) = username {
-        context.insert("username", username);
+    let pinned = username
+        .map(|username| state.pins.get(username))
+        .unwrap_or_default();
+    for mount in &mut mounts {
+        mount.pinned = pinned.contains(&mount.name);
     }
+    mounts.sort_by_key(|mount| {
+        (
+            pinned
+                .iter()
+                .position(|name| name == &mount.name)
+                .unwrap_or(usize::MAX),
+            mount.order,
+            mount.name.clone(),
+        )
+    });
+    mounts
+}
 
-    let html = state
-        .tera
-        .render("index.html", &context)
-        .map_err(error::ErrorInternalServerError)?;
+async fn api_mounts(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    Ok(HttpResponse::Ok()
+        .append_header((header::CACHE_CONTROL, "private, no-cache"))
+        .json(visible_mounts(&state, username.as_deref())))
+}
 
-    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+#[derive(Serialize)]
+struct MountUsage {
+    name: String,
+    #[serde(flatten)]
+    usage: Option<usage::Usage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+async fn mount_stats(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    let units = size_units_for(&state.config, username.as_deref());
+    let mut stats = Vec::new();
+    for summary in visible_mounts(&state, username.as_deref()) {
+        let Some(mount) = state.config.mounts.get(&summary.name) else {
+            continue;
+        };
+        let measured = match state.usage.get(&summary.name) {
+            Some(usage) => Ok(usage),
+            None => {
+                let path = mount.path.clone();
+                let measured =
+                    run_blocking(&state, move || usage::measure(&canonicalize_mount(&path)?)).await;
+                if let Ok(usage) = &measured {
+                    state.usage.insert(&summary.name, *usage);
+                }
+                measured
+            }
+        };
+        stats.push(match measured {
+            Ok(usage) => MountUsage {
+                name: summary.name,
+                usage: Some(usage),
+                size: Some(format::format_file_size(usage.bytes, units)),
+                error: None,
+            },
+            Err(e) => {
+                log::warn!("Failed to measure mount '{}': {}", summary.name, e);
+                MountUsage {
+                    name: summary.name,
+                    usage: None,
+                    size: None,
+                    error: Some("Failed to measure mount usage".to_string()),
+                }
+            }
+        });
+    }
+    Ok(HttpResponse::Ok()
+        .append_header((
+            header::CACHE_CONTROL,
+            format!("private, max-age={}", usage::CACHE_TTL_SECS),
+        ))
+        .json(stats))
+}
+
+async fn check_permission(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<PermissionCheckQuery>,
+) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    if !is_admin(&state.config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+    let query = query.into_inner();
+    if !state.config.mounts.contains_key(&query.mount) {
+        return Err(LunaError::not_found("Mount not found").into());
+    }
+    let user = query.user.filter(|user| !user.is_empty());
+    let relative = normalize_relative_path(query.path.as_deref().unwrap_or(""))
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let disabled = user
+        .as_deref()
+        .is_some_and(|user| state.accounts.is_disabled(&state.config, user));
+
+    let permission = effective_permission(
+        &state,
+        user.as_deref().filter(|_| !disabled),
+        None,
+        &query.mount,
+        &relative,
+    )
+    .unwrap_or_default();
+    let contributions =
+        state
+            .permissions
+            .explain(&state.config, &query.mount, user.as_deref(), &relative);
+    let allowed = query
+        .action
+        .as_deref()
+        .map(|action| permission.permits(action));
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "user": user,
+        "known_user": user.as_deref().is_some_and(|user| state.config.users.contains_key(user)),
+        "disabled": disabled,
+        "mount": query.mount,
+        "path": osname::encode_path(&relative),
+        "action": query.action,
+        "allowed": allowed,
+        "read_only": state.config.server.read_only,
+        "permission": permission,
+        "contributions": contributions,
+    })))
 }
 
 async fn login_page(state: web::Data<AppState>) -> ActixResult<HttpResponse> {
-    let context = TeraContext::new();
-    let html = state
-        .tera
-        .render("login.html", &context)
-        .map_err(error::ErrorInternalServerError)?;
+    let mut context = login_context(&state.config);
+    let html = render_template(&state, "login.html", &mut context)?;
     Ok(HttpResponse::Ok().content_type("text/html").body(html))
 }
 
 async fn login(
     state: web::Data<AppState>,
+    req: HttpRequest,
     form: web::Form<LoginForm>,
 ) -> ActixResult<HttpResponse> {
     let config = &state.config;
-    let mut context = TeraContext::new();
+    let mut context = login_context(config);
 
-    if let Some(user_config) = config.users.get(&form.username) {
+    if let Some(user_config) = config
+        .users
+        .get(&form.username)
+        .filter(|_| !state.accounts.is_disabled(config, &form.username))
+    {
         if !user_config.password.is_empty()
             && verify_password(
                 &form.password,
@@ -206,421 +1301,7407 @@ async fn login(
                 &user_config.hash_algorithm,
             )
         {
+            let location = if needs_terms_acceptance(&state, &form.username) {
+                "/terms/accept"
+            } else {
+                "/"
+            };
             let mut response = HttpResponse::Found()
-                .append_header((header::LOCATION, "/"))
+                .append_header((header::LOCATION, location))
                 .finish();
-
-            let cookie = Cookie::build(SESSION_COOKIE, form.username.clone())
-                .http_only(true)
-                .path("/")
-                .max_age(Duration::hours(24))
-                .finish();
-
             response
-                .add_cookie(&cookie)
-                .map_err(error::ErrorInternalServerError)?;
+                .add_cookie(&session_cookie(&state, &form.username))
+                .map_err(LunaError::internal)?;
 
             return Ok(response);
         }
     }
 
+    let source = req
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    record_failed_login(&state, "web", &form.username, &source);
     context.insert("error", &true);
-    let html = state
-        .tera
-        .render("login.html", &context)
-        .map_err(error::ErrorInternalServerError)?;
+    let html = render_template(&state, "login.html", &mut context)?;
 
     Ok(HttpResponse::BadRequest()
         .content_type("text/html")
         .body(html))
 }
 
-async fn logout(req: HttpRequest) -> ActixResult<HttpResponse> {
-    let mut response = HttpResponse::Found()
-        .append_header((header::LOCATION, "/"))
-        .finish();
+async fn negotiate_login(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<NegotiateQuery>,
+) -> ActixResult<HttpResponse> {
+    let negotiate_config = state
+        .config
+        .negotiate
+        .as_ref()
+        .filter(|negotiate| negotiate.enabled)
+        .ok_or_else(|| LunaError::not_found("Negotiate authentication is disabled"))?;
+    let next = query
+        .into_inner()
+        .next
+        .filter(|next| next.starts_with('/') && !next.starts_with("//"))
+        .unwrap_or_else(|| "/".to_string());
+
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Negotiate "))
+        .map(str::to_string);
+    let Some(token) = token else {
+        return Ok(HttpResponse::Unauthorized()
+            .append_header((header::WWW_AUTHENTICATE, "Negotiate"))
+            .content_type("text/html")
+            .body(r#"<!DOCTYPE html><meta http-equiv="refresh" content="0; url=/login"><a href="/login">로그인</a>"#));
+    };
 
-    if req.cookie(SESSION_COOKIE).is_some() {
-        let cookie = Cookie::build(SESSION_COOKIE, "")
-            .path("/")
-            .max_age(Duration::seconds(0))
-            .finish();
-        response
-            .add_cookie(&cookie)
-            .map_err(error::ErrorInternalServerError)?;
+    let service_principal = negotiate_config.principal.clone();
+    let accepted = run_blocking(&state, move || {
+        Ok(negotiate::accept(service_principal.as_deref(), &token))
+    })
+    .await?;
+
+    let accepted = match accepted {
+        Ok(accepted) => accepted,
+        Err(e) => {
+            log::warn!("Negotiate authentication failed: {}", e);
+            return Ok(HttpResponse::Found()
+                .append_header((header::LOCATION, "/login"))
+                .finish());
+        }
+    };
+
+    let username = negotiate::username_from_principal(&accepted.principal);
+    if state.accounts.is_disabled(&state.config, username) {
+        log::warn!("Negotiate login refused for disabled user {}", username);
+        return Ok(HttpResponse::Found()
+            .append_header((header::LOCATION, "/login"))
+            .finish());
+    }
+    if !state.config.users.contains_key(username) {
+        log::warn!(
+            "Negotiate principal {} does not match a configured user",
+            accepted.principal
+        );
+        return Ok(HttpResponse::Found()
+            .append_header((header::LOCATION, "/login"))
+            .finish());
+    }
+
+    let location = if needs_terms_acceptance(&state, username) {
+        "/terms/accept".to_string()
+    } else {
+        next
+    };
+    let mut response = HttpResponse::Found();
+    response.append_header((header::LOCATION, location));
+    if let Some(response_token) = &accepted.response_token {
+        response.append_header((
+            header::WWW_AUTHENTICATE,
+            format!("Negotiate {}", response_token),
+        ));
     }
+    let mut response = response.finish();
+    response
+        .add_cookie(&session_cookie(&state, username))
+        .map_err(LunaError::internal)?;
 
     Ok(response)
 }
 
-async fn browse(
+fn session_cookie(state: &AppState, username: &str) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, state.sessions.issue(username))
+        .http_only(true)
+        .path("/")
+        .max_age(Duration::seconds(session::SESSION_TTL_SECS as i64))
+        .finish()
+}
+
+fn login_context(config: &Config) -> TeraContext {
+    let branding = &config.branding;
+    let mut context = TeraContext::new();
+    context.insert(
+        "instance_name",
+        branding.instance_name.as_deref().unwrap_or("LunaFinder"),
+    );
+    context.insert("has_logo", &branding.logo.is_some());
+    if let Some(login_markdown) = &branding.login_markdown {
+        match fs::read_to_string(login_markdown) {
+            Ok(markdown) => context.insert("login_markdown", &render_markdown(&markdown)),
+            Err(e) => log::warn!("Failed to read login markdown {:?}: {}", login_markdown, e),
+        }
+    }
+    context
+}
+
+async fn branding_logo(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    let logo = state
+        .config
+        .branding
+        .logo
+        .as_ref()
+        .ok_or_else(|| LunaError::not_found("No logo configured"))?;
+    let file = NamedFile::open(logo).map_err(LunaError::internal)?;
+    Ok(file.into_response(&req))
+}
+
+async fn static_asset(
     state: web::Data<AppState>,
     req: HttpRequest,
-    path: web::Path<(String, String)>,
+    path: web::Path<String>,
+    query: web::Query<StaticQuery>,
 ) -> ActixResult<HttpResponse> {
-    let (mount_name, tail) = path.into_inner();
-    let config = &state.config;
-    let mount = config
-        .mounts
-        .get(&mount_name)
-        .ok_or_else(|| error::ErrorNotFound("Mount not found"))?;
+    let path = path.into_inner();
+    let file = state
+        .assets
+        .resolve(&path)
+        .ok_or_else(|| LunaError::not_found("Asset not found"))?;
+    let fingerprinted =
+        !state.config.server.dev && query.v.is_some() && query.v == state.assets.fingerprint(&path);
+    let mut response = NamedFile::open(file)
+        .map_err(LunaError::internal)?
+        .into_response(&req);
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static(if fingerprinted {
+            assets::IMMUTABLE_CACHE
+        } else {
+            "no-cache"
+        }),
+    );
+    Ok(response)
+}
 
-    let username = get_username_from_cookie(&req);
-    let permission = effective_permission(config, username.as_deref(), mount);
+async fn legal_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let page = path.into_inner();
+    let markdown_file = state
+        .config
+        .pages
+        .get(&page)
+        .ok_or_else(|| LunaError::not_found("Page not found"))?;
+    let markdown = fs::read_to_string(markdown_file).map_err(LunaError::internal)?;
+    let title = LEGAL_PAGES
+        .iter()
+        .find(|(name, _)| *name == page)
+        .map(|(_, label)| *label)
+        .unwrap_or_default();
 
-    let can_read = permission
+    let mut context = TeraContext::new();
+    context.insert("title", title);
+    context.insert("instance_name", &state.config.main_page.title);
+    context.insert("content", &render_markdown(&markdown));
+    if let Some(username) = get_username_from_cookie(&req) {
+        context.insert("username", &username);
+    }
+
+    let html = render_template(&state, "page.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn terms_page(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    let terms_file = state
+        .config
+        .pages
+        .terms
         .as_ref()
-        .map(|p| p.allows_read())
-        .unwrap_or(false);
+        .ok_or_else(|| LunaError::not_found("Page not found"))?;
+    let markdown = fs::read_to_string(terms_file).map_err(LunaError::internal)?;
 
-    if !can_read {
-        return Ok(HttpResponse::Found()
-            .append_header((header::LOCATION, "/login"))
-            .finish());
+    let mut context = TeraContext::new();
+    context.insert("title", "이용약관");
+    context.insert("instance_name", &state.config.main_page.title);
+    context.insert("content", &render_markdown(&markdown));
+    context.insert("username", &username);
+    context.insert("terms_pending", &needs_terms_acceptance(&state, &username));
+
+    let html = render_template(&state, "page.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn accept_terms(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    if !state.config.pages.terms_gate_enabled() {
+        return Err(LunaError::not_found("Terms acceptance is not required").into());
     }
 
-    let relative_path =
-        normalize_relative_path(&tail).ok_or_else(|| error::ErrorBadRequest("Invalid path"))?;
+    let terms = state.terms.clone();
+    let version = state.config.pages.terms_version.clone();
+    run_blocking(&state, move || terms.accept(&username, &version)).await?;
 
-    let base_path = canonicalize_mount(&mount.path).map_err(error::ErrorInternalServerError)?;
-    let target_path = resolve_path(&base_path, &relative_path)
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, "/"))
+        .finish())
+}
 
-    if target_path.is_file() {
-        let file = NamedFile::open(&target_path).map_err(error::ErrorInternalServerError)?;
-        return Ok(file.into_response(&req));
+async fn terms_gate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> ActixResult<ServiceResponse<impl MessageBody>> {
+    let path = req.path();
+    let gated = !TERMS_EXEMPT_PATHS.iter().any(|exempt| {
+        path.strip_prefix(exempt)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    });
+    let blocked = gated
+        && match (
+            req.app_data::<web::Data<AppState>>(),
+            session_username(req.request()),
+        ) {
+            (Some(state), Some(username)) => needs_terms_acceptance(state, &username),
+            _ => false,
+        };
+    if blocked {
+        let response = HttpResponse::Found()
+            .append_header((header::LOCATION, "/terms/accept"))
+            .finish();
+        return Ok(req.into_response(response).map_into_right_body());
     }
 
-    if !target_path.is_dir() {
-        return Err(error::ErrorNotFound("Path not found"));
-    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
 
-    let can_write = permission
-        .as_ref()
-        .map(|p| p.allows_write())
-        .unwrap_or(false);
-    let permission_label = permission
-        .as_ref()
-        .map(|p| p.actions().join(", "))
-        .unwrap_or_default();
-    let has_permission = can_read;
+async fn track_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> ActixResult<ServiceResponse<impl MessageBody>> {
+    let res = next.call(req).await?;
+    let request = res.request();
+    let Some(state) = request
+        .app_data::<web::Data<AppState>>()
+        .filter(|state| state.config.metrics.is_some())
+    else {
+        return Ok(res);
+    };
+    let operation = request
+        .match_pattern()
+        .and_then(|pattern| metrics::operation(&pattern, request.method()));
+    let mount = request
+        .match_info()
+        .get("mount")
+        .or_else(|| request.match_info().get("bucket"))
+        .filter(|mount| state.config.mounts.contains_key(*mount));
+    if let (Some(mount), Some(operation)) = (mount, operation) {
+        let bytes = match res.response().body().size() {
+            BodySize::Sized(size) => size,
+            _ => 0,
+        };
+        let failed = res.status().is_client_error() || res.status().is_server_error();
+        state.metrics.record(mount, operation, bytes, failed);
+    }
+    Ok(res)
+}
 
-    let entries = collect_entries(&target_path).map_err(error::ErrorInternalServerError)?;
+fn is_long_running(req: &ServiceRequest) -> bool {
+    let method = req.method().as_str();
+    let pattern = req.match_pattern().unwrap_or_default();
+    if pattern.starts_with("/dav/") {
+        return matches!(method, "PUT" | "MOVE" | "COPY" | "DELETE");
+    }
+    matches!(
+        metrics::operation(&pattern, req.method()),
+        Some("upload") | Some("transfer") | Some("delete") | Some("s3_put")
+    ) || pattern.ends_with("/move")
+        || pattern.ends_with("/copy")
+        || pattern.ends_with("/upload")
+        || pattern.ends_with("/paste")
+        || pattern == "/api/v1/uploads/{id}"
+        || (pattern == "/drop/{token}" && method == "POST")
+}
 
-    let current_path_string = if relative_path.as_os_str().is_empty() {
-        ".".to_string()
-    } else {
-        pathbuf_to_string(&relative_path)
+async fn request_deadline(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> ActixResult<ServiceResponse<impl MessageBody>> {
+    let Some(timeouts) = req
+        .app_data::<web::Data<AppState>>()
+        .map(|state| state.config.server.timeouts.clone())
+    else {
+        return next.call(req).await;
     };
-
-    let parent_path = if relative_path.as_os_str().is_empty() {
-        None
+    if req.match_pattern().as_deref() == Some("/api/{mount}/changes") {
+        return next.call(req).await;
+    }
+    let limit = if is_long_running(&req) {
+        timeouts.upload_secs
     } else {
-        let mut parent = relative_path.clone();
-        parent.pop();
-        Some(if parent.as_os_str().is_empty() {
-            ".".to_string()
-        } else {
-            pathbuf_to_string(&parent)
-        })
+        timeouts.request_secs
+    };
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let username = get_username_from_cookie(req.request());
+    let started = Instant::now();
+
+    let result = match limit {
+        Some(secs) => {
+            match tokio::time::timeout(StdDuration::from_secs(secs), next.call(req)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    log::warn!(
+                        target: "slow",
+                        "{} {} by {} timed out after {}s",
+                        method,
+                        path,
+                        username.as_deref().unwrap_or("-"),
+                        secs
+                    );
+                    return Err(LunaError::Timeout.into());
+                }
+            }
+        }
+        None => next.call(req).await,
     };
 
-    let directory_tree = build_directory_tree(&base_path, Path::new(""), 0)
-        .map_err(error::ErrorInternalServerError)?;
-    let open_paths = build_open_paths(&current_path_string);
+    let elapsed = started.elapsed();
+    if timeouts
+        .slow_request_ms
+        .is_some_and(|threshold| elapsed >= StdDuration::from_millis(threshold))
+    {
+        log::warn!(
+            target: "slow",
+            "{} {} by {} took {}ms",
+            method,
+            path,
+            username.as_deref().unwrap_or("-"),
+            elapsed.as_millis()
+        );
+    }
+    result
+}
 
-    let mut context = TeraContext::new();
-    context.insert("mount_name", &mount_name);
-    context.insert("mount_description", &mount.description);
-    context.insert("current_path", &current_path_string);
-    context.insert("entries", &entries);
-    if let Some(parent_path) = &parent_path {
-        context.insert("parent_path", parent_path);
+async fn view_as_gate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> ActixResult<ServiceResponse<impl MessageBody>> {
+    if let Some((admin, target)) = view_as(req.request()) {
+        log::info!(
+            target: "security",
+            "{} viewing as {}: {} {}",
+            admin,
+            target,
+            req.method(),
+            req.path()
+        );
+        let read_only = matches!(
+            *req.method(),
+            actix_web::http::Method::GET
+                | actix_web::http::Method::HEAD
+                | actix_web::http::Method::OPTIONS
+        );
+        if !read_only && req.path() != "/admin/view-as/stop" {
+            let response = HttpResponse::Forbidden()
+                .content_type("text/plain; charset=utf-8")
+                .body("다른 사용자로 보기 중에는 변경할 수 없습니다.");
+            return Ok(req.into_response(response).map_into_right_body());
+        }
     }
-    if let Some(ref username) = username {
-        context.insert("username", username);
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+async fn watch_denials(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> ActixResult<ServiceResponse<impl MessageBody>> {
+    let res = next.call(req).await?;
+    if res.status() != actix_web::http::StatusCode::FORBIDDEN {
+        return Ok(res);
     }
-    context.insert("is_public", &mount.public);
-    context.insert("can_write", &can_write);
-    context.insert("has_permission", &has_permission);
-    context.insert("permission", &permission_label);
-    context.insert("tree", &directory_tree);
-    context.insert("open_paths", &open_paths);
+    let request = res.request();
+    let Some(state) = request.app_data::<web::Data<AppState>>() else {
+        return Ok(res);
+    };
+    let Some(settings) = &state.config.security else {
+        return Ok(res);
+    };
+    let subject = match get_username_from_cookie(request) {
+        Some(username) => format!("user '{}'", username),
+        None => request
+            .peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+    };
+    log::warn!(target: "security", "Permission denied for {} on {}", subject, request.path());
+    if let Some(alert) = state.security.denied(settings, &subject) {
+        report_security(state, alert);
+    }
+    Ok(res)
+}
 
-    let html = state
-        .tera
-        .render("browse.html", &context)
-        .map_err(error::ErrorInternalServerError)?;
+fn record_failed_login(state: &AppState, channel: &str, username: &str, source: &str) {
+    let Some(settings) = &state.config.security else {
+        return;
+    };
+    log::warn!(
+        target: "security",
+        "Failed {} login for '{}' from {}",
+        channel,
+        username,
+        source
+    );
+    if let Some(alert) = state
+        .security
+        .failed_login(settings, channel, username, source)
+    {
+        report_security(state, alert);
+    }
+}
 
-    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+fn report_security(state: &AppState, alert: String) {
+    log::warn!(target: "security", "{}", alert);
+    let Some(notifier) = &state.notifier else {
+        return;
+    };
+    for (name, user) in &state.config.users {
+        if user.group.iter().any(|group| group == "admin") {
+            notifier.alert(name, alert.clone());
+        }
+    }
 }
 
-async fn upload_file(
+async fn metrics_endpoint(
     state: web::Data<AppState>,
     req: HttpRequest,
-    path: web::Path<(String, String)>,
-    mut payload: Multipart,
 ) -> ActixResult<HttpResponse> {
-    let (mount_name, tail) = path.into_inner();
-    let config = &state.config;
-    let mount = config
-        .mounts
-        .get(&mount_name)
-        .ok_or_else(|| error::ErrorNotFound("Mount not found"))?;
-
-    let username = get_username_from_cookie(&req);
-    let permission = effective_permission(config, username.as_deref(), mount)
-        .ok_or_else(|| error::ErrorForbidden("Write permission required"))?;
-    if !permission.allows_upload() {
-        return Err(error::ErrorForbidden("Write permission required"));
+    let settings = state
+        .config
+        .metrics
+        .as_ref()
+        .ok_or_else(|| LunaError::not_found("Metrics are disabled"))?;
+    if let Some(token) = &settings.token {
+        let provided = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        if !signing::constant_time_eq(provided.as_bytes(), token.as_bytes()) {
+            return Err(LunaError::unauthorized("Invalid metrics token").into());
+        }
     }
+    Ok(HttpResponse::Ok()
+        .content_type(metrics::CONTENT_TYPE)
+        .body(state.metrics.render()))
+}
 
-    let relative_path =
-        normalize_relative_path(&tail).ok_or_else(|| error::ErrorBadRequest("Invalid path"))?;
-
-    let base_path = canonicalize_mount(&mount.path).map_err(error::ErrorInternalServerError)?;
-    let directory_path = resolve_path(&base_path, &relative_path)
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+fn needs_terms_acceptance(state: &AppState, username: &str) -> bool {
+    let pages = &state.config.pages;
+    pages.terms_gate_enabled() && !state.terms.has_accepted(username, &pages.terms_version)
+}
 
-    if !directory_path.is_dir() {
-        return Err(error::ErrorBadRequest("Target is not a directory"));
-    }
+async fn logout(req: HttpRequest) -> ActixResult<HttpResponse> {
+    let mut response = HttpResponse::Found()
+        .append_header((header::LOCATION, "/"))
+        .finish();
 
-    while let Some(mut field) = payload
-        .try_next()
-        .await
-        .map_err(error::ErrorInternalServerError)?
-    {
-        if let Some(filename) = field.content_disposition().and_then(|cd| cd.get_filename()) {
-            if let Some(sanitized) = sanitize_file_name(filename) {
-                let file_path = directory_path.join(sanitized);
-                let mut file =
-                    fs::File::create(&file_path).map_err(error::ErrorInternalServerError)?;
-                while let Some(chunk) = field
-                    .try_next()
-                    .await
-                    .map_err(error::ErrorInternalServerError)?
-                {
-                    file.write_all(&chunk)
-                        .map_err(error::ErrorInternalServerError)?;
-                }
-            }
+    for name in [SESSION_COOKIE, VIEW_AS_COOKIE] {
+        if req.cookie(name).is_some() {
+            let cookie = Cookie::build(name, "")
+                .path("/")
+                .max_age(Duration::seconds(0))
+                .finish();
+            response.add_cookie(&cookie).map_err(LunaError::internal)?;
         }
     }
 
-    Ok(HttpResponse::Found()
-        .append_header((header::LOCATION, format!("/browse/{}/{}", mount_name, tail)))
-        .finish())
+    Ok(response)
 }
 
-async fn delete_entry(
+async fn browse(
     state: web::Data<AppState>,
     req: HttpRequest,
     path: web::Path<(String, String)>,
-    form: web::Form<DeleteForm>,
+    query: web::Query<ListingQuery>,
 ) -> ActixResult<HttpResponse> {
     let (mount_name, tail) = path.into_inner();
     let config = &state.config;
     let mount = config
         .mounts
         .get(&mount_name)
-        .ok_or_else(|| error::ErrorNotFound("Mount not found"))?;
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
 
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &relative_path,
+    );
+    if let Some(guest) = &guest {
+        let visitor = req.peer_addr().map(|addr| addr.ip().to_string());
+        let referrer = share_referrer(&req);
+        match state
+            .tokens
+            .record_hit(&guest.token, visitor.as_deref(), referrer.as_deref())
+        {
+            Ok(1) => notify_creator(
+                &state,
+                guest,
+                format!(
+                    "게스트 링크 {}/{}에 처음 접근했습니다.",
+                    guest.mount,
+                    pathbuf_to_string(&guest.path)
+                ),
+            ),
+            Ok(_) => {}
+            Err(e) => log::warn!("Failed to record guest token hit: {}", e),
+        }
+    }
+
+    let can_read = permission
+        .as_ref()
+        .map(|p| p.allows_read())
+        .unwrap_or(false);
+
+    if !can_read {
+        return Ok(HttpResponse::Found()
+            .append_header((
+                header::LOCATION,
+                login_location(config, username.as_deref(), &req),
+            ))
+            .finish());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+
+    if target_path.is_file() {
+        if mount.wiki && wiki::is_page(&relative_path) && !query.wants_download() {
+            let can_edit = guest.is_none()
+                && !state.maintenance.load(Ordering::Relaxed)
+                && permission.as_ref().is_some_and(|p| p.allows_modify());
+            return wiki_page(
+                &state,
+                &mount_name,
+                &base_path,
+                &relative_path,
+                &target_path,
+                username,
+                guest,
+                can_edit,
+            )
+            .await;
+        }
+        if let Some(kind) =
+            media_kind(&relative_path).filter(|_| !query.wants_download() && accepts_html(&req))
+        {
+            return media_player(
+                &state,
+                &mount_name,
+                &relative_path,
+                &target_path,
+                kind,
+                username,
+                guest,
+            )
+            .await;
+        }
+        let (mut response, bytes) =
+            file_response(&state, &req, &mount_name, &target_path).map_err(LunaError::internal)?;
+        if query.wants_download() && response.status().is_success() {
+            set_content_disposition(&mut response, &target_path, true)
+                .map_err(LunaError::internal)?;
+        }
+        record_access(
+            &state,
+            &mount_name,
+            stats::EventKind::Download,
+            username,
+            bytes,
+        );
+        if let Some(guest) = &guest {
+            if let Err(e) = state.tokens.record_bytes(&guest.token, bytes) {
+                log::warn!("Failed to record guest token bytes: {}", e);
+            }
+        }
+        return Ok(response);
+    }
+
+    if !target_path.is_dir() {
+        return Err(LunaError::not_found("Path not found").into());
+    }
+
+    let writable = !state.maintenance.load(Ordering::Relaxed);
+    let can_upload = writable
+        && permission
+            .as_ref()
+            .map(|p| p.allows_upload())
+            .unwrap_or(false);
+    let can_create_folder = writable
+        && permission
+            .as_ref()
+            .map(|p| p.allows_create_folder())
+            .unwrap_or(false);
+    let can_moderate = writable
+        && mount.moderation
+        && permission
+            .as_ref()
+            .map(|p| p.allows_moderate())
+            .unwrap_or(false);
+    let permission_label = permission
+        .as_ref()
+        .map(|p| p.actions().join(", "))
+        .unwrap_or_default();
+    let has_permission = can_read;
+
+    let mut view = ListingView::from_query(config, &query)?;
+    let listing_path = target_path.clone();
+    let listing_relative = relative_path.clone();
+    let listing_base = mount.gitignore.then(|| base_path.clone());
+    let (sort, descending) = (view.sort, view.descending);
+    let entries = run_blocking(&state, move || {
+        let rules = listing_base.map(|base| gitignore::Rules::load(&base, &listing_relative));
+        collect_entries(
+            &listing_path,
+            &listing_relative,
+            sort,
+            descending,
+            rules.as_ref(),
+        )
+    })
+    .await?;
+    let total_entries = entries.len();
+    let file_count = entries.iter().filter(|entry| !entry.is_dir).count();
+    let image_count = entries.iter().filter(|entry| entry.thumbnail).count();
+    let total_pages = total_entries.div_ceil(view.per_page).max(1);
+    view.page = view.page.min(total_pages);
+    let mut entries = entries
+        .into_iter()
+        .skip((view.page - 1) * view.per_page)
+        .take(view.per_page)
+        .collect::<Vec<_>>();
+    for entry in &mut entries {
+        if entry.viewer.is_none()
+            && !entry.is_dir
+            && state.highlighter.supports(Path::new(&entry.file_name))
+        {
+            entry.viewer = Some("code");
+        }
+    }
+    if writable {
+        for entry in &mut entries {
+            let entry_permission = effective_permission(
+                &state,
+                username.as_deref(),
+                guest.as_ref(),
+                &mount_name,
+                &relative_path.join(&entry.file_name),
+            );
+            if let Some(entry_permission) = entry_permission {
+                entry.can_rename = entry_permission.allows_rename();
+                entry.can_delete = entry_permission.allows_delete();
+                entry.can_modify = !entry.is_dir && entry_permission.allows_modify();
+            }
+        }
+    }
+
+    let show_tree = effective_permission(
+        &state,
+        username.as_deref(),
+        None,
+        &mount_name,
+        Path::new(""),
+    )
+    .is_some_and(|p| p.allows_read());
+    let etag = (!config.server.dev).then(|| {
+        let mut hasher = Sha256::new();
+        hasher.update(state.config_hash.as_bytes());
+        let modified = fs::metadata(&target_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_nanos())
+            .unwrap_or(0);
+        hasher.update(format!(
+            "{}\0{}\0{}\0{}\0{}\0{}\n",
+            view.href(config, None),
+            modified,
+            username.as_deref().unwrap_or_default(),
+            guest
+                .as_ref()
+                .map(|guest| guest.token.as_str())
+                .unwrap_or_default(),
+            permission_label,
+            writable
+        ));
+        if let Some(announcement) = current_announcement(&state) {
+            hasher.update(format!(
+                "{:?}\0{}\n",
+                announcement.severity, announcement.message
+            ));
+        }
+        if let Some(entry) = username
+            .as_deref()
+            .and_then(|username| state.history.last(username))
+        {
+            hasher.update(format!("{}\n", entry.id));
+        }
+        if show_tree {
+            hasher.update(state.journal.cursor());
+        }
+        for entry in &entries {
+            hasher.update(format!(
+                "{}\0{}\0{}\0{}{}{}\n",
+                entry.link,
+                entry.size.unwrap_or(0),
+                entry.modified_relative,
+                entry.can_rename,
+                entry.can_delete,
+                entry.can_modify
+            ));
+        }
+        format!("W/\"{}\"", &signing::hex(&hasher.finalize())[..32])
+    });
+    if let Some(etag) = &etag {
+        let not_modified = req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|tag| tag.trim().trim_start_matches("W/") == etag.trim_start_matches("W/"))
+            });
+        if not_modified {
+            return Ok(HttpResponse::NotModified()
+                .insert_header((header::ETAG, etag.as_str()))
+                .finish());
+        }
+    }
+
+    let current_path_string = pathbuf_to_string(&relative_path);
+    let current_link = osname::encode_path(&relative_path);
+
+    let parent_path = if relative_path.as_os_str().is_empty() {
+        None
+    } else {
+        let mut parent = relative_path.clone();
+        parent.pop();
+        Some(osname::encode_path(&parent))
+    };
+
+    let mut accumulated = PathBuf::new();
+    let breadcrumbs: Vec<Breadcrumb> = relative_path
+        .components()
+        .map(|component| {
+            accumulated.push(component);
+            Breadcrumb {
+                name: component.as_os_str().to_string_lossy().to_string(),
+                path: osname::encode_path(&accumulated),
+            }
+        })
+        .collect();
+
+    let directory_tree = if show_tree {
+        let tree_base = base_path.clone();
+        let tree_limits = mount.tree.clone();
+        Some(
+            run_blocking(&state, move || {
+                build_directory_tree(&tree_base, Path::new(""), 0, &tree_limits)
+            })
+            .await?,
+        )
+    } else {
+        None
+    };
+    let open_paths = build_open_paths(&current_link);
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("mount_description", &mount.description);
+    context.insert("mount_icon", &mount.icon);
+    context.insert("mount_color", &mount.color);
+    context.insert("current_path", &current_path_string);
+    context.insert("current_link", &current_link);
+    if let Some(entry) = username
+        .as_deref()
+        .and_then(|username| state.history.last(username))
+    {
+        context.insert("last_operation", &entry.label);
+    }
+    context.insert("breadcrumbs", &breadcrumbs);
+    context.insert("entries", &entries);
+    context.insert("total_entries", &total_entries);
+    context.insert("page", &view.page);
+    context.insert("total_pages", &total_pages);
+    context.insert("per_page", &view.per_page);
+    context.insert("sort", view.sort.as_str());
+    context.insert("order", if view.descending { "desc" } else { "asc" });
+    context.insert(
+        "sort_links",
+        &ListingSort::ALL
+            .iter()
+            .map(|&sort| {
+                let active = sort == view.sort;
+                let descending = active && !view.descending;
+                SortLink {
+                    key: sort.as_str(),
+                    label: sort.label(),
+                    href: ListingView {
+                        sort,
+                        descending,
+                        page: 1,
+                        ..view
+                    }
+                    .href(config, guest.as_ref()),
+                    active,
+                    descending: active && view.descending,
+                }
+            })
+            .collect::<Vec<_>>(),
+    );
+    let page_href = |page: usize| ListingView { page, ..view }.href(config, guest.as_ref());
+    let mut page_links = Vec::new();
+    let mut previous = 0;
+    for number in 1..=total_pages {
+        if number != 1 && number != total_pages && number.abs_diff(view.page) > 2 {
+            continue;
+        }
+        page_links.push(PageLink {
+            number,
+            href: page_href(number),
+            current: number == view.page,
+            gap_before: number > previous + 1,
+        });
+        previous = number;
+    }
+    context.insert("page_links", &page_links);
+    if view.page > 1 {
+        context.insert("previous_page", &page_href(view.page - 1));
+    }
+    if view.page < total_pages {
+        context.insert("next_page", &page_href(view.page + 1));
+    }
+    context.insert("gallery", &view.gallery);
+    context.insert("mostly_images", &(image_count * 2 > file_count));
+    context.insert(
+        "view_toggle",
+        &ListingView {
+            gallery: !view.gallery,
+            page: 1,
+            ..view
+        }
+        .href(config, guest.as_ref()),
+    );
+    context.insert(
+        "viewer_query",
+        &ListingView {
+            gallery: false,
+            page: 1,
+            ..view
+        }
+        .href(config, guest.as_ref()),
+    );
+    context.insert(
+        "folder_query",
+        &ListingView { page: 1, ..view }.href(config, guest.as_ref()),
+    );
+    if let Some(parent_path) = &parent_path {
+        context.insert("parent_path", parent_path);
+    }
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+    context.insert("is_public", &mount.public);
+    context.insert("can_upload", &can_upload);
+    context.insert("can_create_folder", &can_create_folder);
+    context.insert(
+        "can_open_trash",
+        &(mount.trash
+            && (is_admin(config, username.as_deref())
+                || trash_permission(&state, username.as_deref(), &mount_name, "").is_some())),
+    );
+    context.insert("has_releases", &mount.releases.enabled);
+    context.insert("can_moderate", &can_moderate);
+    context.insert("moderation", &mount.moderation);
+    context.insert("is_admin", &is_admin(config, username.as_deref()));
+    context.insert("has_permission", &has_permission);
+    context.insert("permission", &permission_label);
+    if let Some(directory_tree) = &directory_tree {
+        context.insert("tree", directory_tree);
+    }
+    context.insert("can_share", &username.is_some());
+    let mut transfer_mounts = config
+        .mounts
+        .iter()
+        .filter(|_| writable)
+        .filter(|(name, candidate)| !candidate.hidden || **name == mount_name)
+        .filter(|(name, candidate)| {
+            effective_permission(&state, username.as_deref(), None, name, Path::new(""))
+                .is_some_and(|p| {
+                    p.allows_upload() && (!candidate.moderation || p.allows_moderate())
+                })
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    transfer_mounts.sort();
+    context.insert("transfer_mounts", &transfer_mounts);
+    context.insert("token_query", &token_query(guest.as_ref()));
+    context.insert("search_token", &guest.as_ref().map(|guest| &guest.token));
+    context.insert(
+        "can_search_content",
+        &(mount.fulltext && fulltext::available()),
+    );
+    context.insert("open_paths", &open_paths);
+
+    context.insert(
+        "size_units",
+        size_units_for(config, username.as_deref()).as_str(),
+    );
+
+    let template = if view.gallery {
+        "gallery.html"
+    } else {
+        "browse.html"
+    };
+    let html = render_template(&state, template, &mut context)?;
+
+    let mut response = HttpResponse::Ok();
+    if let Some(etag) = etag {
+        response
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, "private, no-cache"));
+    }
+    Ok(response.content_type("text/html").body(html))
+}
+
+async fn media_player(
+    state: &AppState,
+    mount_name: &str,
+    relative_path: &Path,
+    target_path: &Path,
+    kind: &str,
+    username: Option<String>,
+    guest: Option<tokens::GuestToken>,
+) -> ActixResult<HttpResponse> {
+    let config = &state.config;
+    let parent_path = relative_path
+        .parent()
+        .map(osname::encode_path)
+        .unwrap_or_else(|| ".".to_string());
+    let filename = target_path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let size = fs::metadata(target_path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", mount_name);
+    context.insert("target_link", &osname::encode_path(relative_path));
+    context.insert("parent_path", &parent_path);
+    context.insert("filename", &filename);
+    context.insert("file_size", &size);
+    context.insert("media_kind", kind);
+    context.insert(
+        "media_type",
+        &mime_guess::from_path(relative_path)
+            .first_or_octet_stream()
+            .essence_str(),
+    );
+    context.insert("token_query", &token_query(guest.as_ref()));
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+    context.insert(
+        "size_units",
+        size_units_for(config, username.as_deref()).as_str(),
+    );
+
+    let html = render_template(state, "player.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn media_stream(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<DownloadQuery>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    if media_kind(&relative_path).is_none() {
+        return Err(
+            LunaError::unsupported_media_type("Only audio and video files can be played").into(),
+        );
+    }
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let can_read = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &relative_path,
+    )
+    .is_some_and(|permission| permission.allows_read());
+    if !can_read {
+        return Err(match (&username, &guest) {
+            (None, None) => LunaError::unauthorized("Login required"),
+            _ => LunaError::forbidden("Read permission required"),
+        }
+        .into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !target_path.is_file() {
+        return Err(LunaError::not_found("Media not found").into());
+    }
+
+    let (mut response, bytes) =
+        file_response(&state, &req, &mount_name, &target_path).map_err(LunaError::internal)?;
+    if response.status().is_success() {
+        set_content_disposition(&mut response, &target_path, query.wants_download())
+            .map_err(LunaError::internal)?;
+        response.headers_mut().insert(
+            header::X_CONTENT_TYPE_OPTIONS,
+            header::HeaderValue::from_static("nosniff"),
+        );
+    }
+    record_access(
+        &state,
+        &mount_name,
+        stats::EventKind::Download,
+        username,
+        bytes,
+    );
+    if let Some(guest) = &guest {
+        if let Err(e) = state.tokens.record_bytes(&guest.token, bytes) {
+            log::warn!("Failed to record guest token bytes: {}", e);
+        }
+    }
+    Ok(response)
+}
+
+fn accepts_html(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/html"))
+}
+
+fn media_kind(path: &Path) -> Option<&'static str> {
+    let mime = mime_guess::from_path(path).first()?;
+    match mime.type_().as_str() {
+        "audio" => Some("audio"),
+        "video" => Some("video"),
+        _ => None,
+    }
+}
+
+fn set_content_disposition(
+    response: &mut HttpResponse,
+    path: &Path,
+    attachment: bool,
+) -> anyhow::Result<()> {
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let disposition = header::ContentDisposition {
+        disposition: if attachment {
+            header::DispositionType::Attachment
+        } else {
+            header::DispositionType::Inline
+        },
+        parameters: vec![header::DispositionParam::FilenameExt(
+            header::ExtendedValue {
+                charset: header::Charset::Ext("UTF-8".to_string()),
+                language_tag: None,
+                value: filename.into_bytes(),
+            },
+        )],
+    };
+    response.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        header::HeaderValue::from_str(&disposition.to_string())?,
+    );
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn wiki_page(
+    state: &AppState,
+    mount_name: &str,
+    base_path: &Path,
+    relative_path: &Path,
+    target_path: &Path,
+    username: Option<String>,
+    guest: Option<tokens::GuestToken>,
+    can_edit: bool,
+) -> ActixResult<HttpResponse> {
+    let codec = storage_codec(state, mount_name).into_owned();
+    let query = token_query(guest.as_ref());
+    let (base, mount, source, page_dir, link_query) = (
+        base_path.to_path_buf(),
+        mount_name.to_string(),
+        target_path.to_path_buf(),
+        relative_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf(),
+        query.clone(),
+    );
+    let content = run_blocking(state, move || {
+        let mut bytes = Vec::new();
+        storage::open_reader(codec.as_codec(), &source)?
+            .0
+            .read_to_end(&mut bytes)?;
+        let site = wiki::Site {
+            base: &base,
+            mount: &mount,
+            query: &link_query,
+        };
+        Ok(wiki::render(
+            &String::from_utf8_lossy(&bytes),
+            &page_dir,
+            &site,
+        ))
+    })
+    .await?;
+
+    let parent_path = relative_path
+        .parent()
+        .map(osname::encode_path)
+        .unwrap_or_else(|| ".".to_string());
+    let title = relative_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", mount_name);
+    context.insert("target_path", &pathbuf_to_string(relative_path));
+    context.insert("target_link", &osname::encode_path(relative_path));
+    context.insert("parent_path", &parent_path);
+    context.insert("title", &title);
+    context.insert("content", &content);
+    context.insert("can_edit", &can_edit);
+    context.insert("token_query", &query);
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+
+    let html = render_template(state, "wiki.html", &mut context)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/html")
+        .insert_header((header::CACHE_CONTROL, "private, no-cache"))
+        .body(html))
+}
+
+async fn upload_file(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    mut payload: Multipart,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &relative_path,
+    )
+    .ok_or_else(|| LunaError::forbidden("Write permission required"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let directory_path =
+        resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+
+    if !directory_path.is_dir() {
+        return Err(LunaError::bad_request("Target is not a directory").into());
+    }
+
+    let queue_uploads = mount.moderation && !permission.allows_moderate();
+    let mut directory_path = if queue_uploads {
+        let queued = moderation::queue_root(&base_path).join(&relative_path);
+        fs::create_dir_all(&queued).map_err(LunaError::internal)?;
+        queued
+    } else {
+        directory_path
+    };
+    let mut target_relative = relative_path.clone();
+
+    let limits = &config.server.limits;
+    if let Some(max_request_size) = limits.max_request_size {
+        let content_length = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if content_length.is_some_and(|length| length > max_request_size) {
+            return Err(
+                LunaError::payload_too_large("Upload exceeds the request size limit").into(),
+            );
+        }
+    }
+
+    let max_upload_size = max_upload_size(config, mount, username.as_deref());
+    let mut total_bytes: u64 = 0;
+    let mut file_count: usize = 0;
+
+    while let Some(mut field) = payload.try_next().await.map_err(LunaError::internal)? {
+        let is_subdir = field
+            .content_disposition()
+            .is_some_and(|cd| cd.get_filename().is_none() && cd.get_name() == Some("subdir"));
+        if is_subdir {
+            if file_count > 0 {
+                return Err(
+                    LunaError::bad_request("The subdir field must precede the files").into(),
+                );
+            }
+            let mut value = Vec::new();
+            while let Some(chunk) = field.try_next().await.map_err(LunaError::internal)? {
+                value.extend_from_slice(&chunk);
+                if value.len() > MAX_SUBDIR_LEN {
+                    return Err(LunaError::bad_request("Subdirectory name is too long").into());
+                }
+            }
+            let value = String::from_utf8(value)
+                .map_err(|_| LunaError::bad_request("Invalid subdirectory"))?;
+            if value.trim().is_empty() {
+                continue;
+            }
+            let subdir = normalize_relative_path(value.trim())
+                .filter(|subdir| !subdir.as_os_str().is_empty())
+                .ok_or_else(|| LunaError::bad_request("Invalid subdirectory"))?;
+            target_relative = relative_path.join(&subdir);
+            let subdir_permission = effective_permission(
+                &state,
+                username.as_deref(),
+                guest.as_ref(),
+                &mount_name,
+                &target_relative,
+            )
+            .ok_or_else(|| LunaError::forbidden("Write permission required"))?;
+            let subdir_path =
+                resolve_path(&base_path, &target_relative).map_err(LunaError::bad_request)?;
+            if !subdir_path.is_dir() {
+                if subdir_path.exists() {
+                    return Err(LunaError::conflict("Subdirectory is not a directory").into());
+                }
+                if !subdir_permission.allows_create_folder() {
+                    return Err(LunaError::forbidden("Create folder permission required").into());
+                }
+            }
+            directory_path = if queue_uploads {
+                moderation::queue_root(&base_path).join(&target_relative)
+            } else {
+                subdir_path
+            };
+            if !directory_path.is_dir() {
+                fs::create_dir_all(&directory_path).map_err(LunaError::internal)?;
+                if !queue_uploads {
+                    publish_file_event(
+                        &state,
+                        &mount_name,
+                        "create_folder",
+                        &target_relative,
+                        username.as_deref(),
+                    );
+                }
+            }
+            continue;
+        }
+
+        if let Some(filename) = field.content_disposition().and_then(|cd| cd.get_filename()) {
+            if let Some(sanitized) = sanitize_file_name(filename) {
+                effective_permission(
+                    &state,
+                    username.as_deref(),
+                    guest.as_ref(),
+                    &mount_name,
+                    &target_relative.join(&sanitized),
+                )
+                .filter(Permission::allows_upload)
+                .ok_or_else(|| {
+                    LunaError::forbidden(format!("Uploading {} is not allowed", sanitized))
+                })?;
+                file_count += 1;
+                if limits
+                    .max_files
+                    .is_some_and(|max_files| file_count > max_files)
+                {
+                    return Err(LunaError::payload_too_large(
+                        "Upload exceeds the file count limit",
+                    )
+                    .into());
+                }
+
+                let mut field_bytes: u64 = 0;
+                let mut head = Vec::new();
+                let mut exhausted = false;
+                while head.len() < content::SNIFF_LEN {
+                    match field.try_next().await.map_err(LunaError::internal)? {
+                        Some(chunk) => {
+                            field_bytes += chunk.len() as u64;
+                            total_bytes += chunk.len() as u64;
+                            check_upload_limits(limits, field_bytes, total_bytes)?;
+                            check_upload_size(
+                                config,
+                                max_upload_size,
+                                total_bytes,
+                                username.as_deref(),
+                            )?;
+                            head.extend_from_slice(&chunk);
+                        }
+                        None => {
+                            exhausted = true;
+                            break;
+                        }
+                    }
+                }
+
+                if let Err(category) =
+                    content::check(&mount.allow_content, &mount.deny_content, &head)
+                {
+                    return Err(LunaError::unsupported_media_type(format!(
+                        "Uploading {} content to this mount is not allowed",
+                        category
+                    ))
+                    .into());
+                }
+
+                let file_path = directory_path.join(&sanitized);
+                let staged_path = staging_path(&state, &directory_path, "upload");
+                let mut file = storage::QueuedWriter::spawn(
+                    storage::FileWriter::create(
+                        staged_codec(&state, &mount_name, &file_path),
+                        &staged_path,
+                    )
+                    .map_err(LunaError::internal)?,
+                    config.server.uploads.queue_chunks,
+                );
+                let result: ActixResult<()> = async {
+                    file.write(head.into()).await.map_err(LunaError::internal)?;
+                    if !exhausted {
+                        while let Some(chunk) =
+                            field.try_next().await.map_err(LunaError::internal)?
+                        {
+                            field_bytes += chunk.len() as u64;
+                            total_bytes += chunk.len() as u64;
+                            check_upload_limits(limits, field_bytes, total_bytes)?;
+                            check_upload_size(
+                                config,
+                                max_upload_size,
+                                total_bytes,
+                                username.as_deref(),
+                            )?;
+                            file.write(chunk).await.map_err(LunaError::internal)?;
+                        }
+                    }
+                    Ok(())
+                }
+                .await;
+                let result = match result {
+                    Ok(()) => file
+                        .finish()
+                        .await
+                        .map_err(|e| LunaError::internal(e).into()),
+                    Err(e) => Err(e),
+                }
+                .and_then(|_| {
+                    storage::persist(&staged_path, &file_path)
+                        .map_err(|e| LunaError::internal(e).into())
+                });
+                if let Err(e) = result {
+                    let _ = fs::remove_file(&staged_path);
+                    return Err(e);
+                }
+                record_access(
+                    &state,
+                    &mount_name,
+                    stats::EventKind::Upload,
+                    username.clone(),
+                    field_bytes,
+                );
+                publish_file_event(
+                    &state,
+                    &mount_name,
+                    "upload",
+                    &target_relative.join(&sanitized),
+                    username.as_deref(),
+                );
+                if let Some(guest) = &guest {
+                    notify_creator(
+                        &state,
+                        guest,
+                        format!(
+                            "게스트 링크 {}/{}를 통해 {} 파일이 업로드되었습니다.",
+                            guest.mount,
+                            pathbuf_to_string(&guest.path),
+                            sanitized
+                        ),
+                    );
+                }
+
+                if let Some(scan) = &config.scan {
+                    let command = scan.command.clone();
+                    let scan_path = file_path.clone();
+                    let codec = storage_codec(&state, &mount_name).into_owned();
+                    let outcome = run_blocking(&state, move || {
+                        scan_stored_file(&command, codec.as_codec(), &scan_path)
+                    })
+                    .await?;
+                    if let ScanOutcome::Flagged(verdict) = outcome {
+                        log::warn!(
+                            "Upload {:?} to mount '{}' flagged by scanner: {}",
+                            file_path,
+                            mount_name,
+                            verdict
+                        );
+                        quarantine::isolate(
+                            &base_path,
+                            &target_relative.join(&sanitized),
+                            &file_path,
+                            &verdict,
+                        )
+                        .map_err(LunaError::internal)?;
+                    }
+                }
+                store_deduplicated(&state, mount, &base_path, &file_path).await;
+                write_checksum(&state, &mount_name, mount, &base_path, &file_path).await;
+            }
+        }
+    }
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            header::LOCATION,
+            format!(
+                "{}{}",
+                urls::entry(
+                    "browse",
+                    &mount_name,
+                    if queue_uploads {
+                        &relative_path
+                    } else {
+                        &target_relative
+                    }
+                ),
+                token_query(guest.as_ref())
+            ),
+        ))
+        .finish())
+}
+
+fn check_upload_limits(
+    limits: &LimitsConfig,
+    field_bytes: u64,
+    total_bytes: u64,
+) -> ActixResult<()> {
+    if limits
+        .max_field_size
+        .is_some_and(|max_field_size| field_bytes > max_field_size)
+    {
+        return Err(LunaError::payload_too_large("Uploaded file exceeds the size limit").into());
+    }
+    if limits
+        .max_request_size
+        .is_some_and(|max_request_size| total_bytes > max_request_size)
+    {
+        return Err(LunaError::payload_too_large("Upload exceeds the request size limit").into());
+    }
+    Ok(())
+}
+
+fn max_upload_size(config: &Config, mount: &MountConfig, username: Option<&str>) -> Option<u64> {
+    username
+        .and_then(|username| config.users.get(username))
+        .and_then(|user| user.max_upload_size)
+        .or(mount.max_upload_size)
+        .or(config.server.limits.max_upload_size)
+}
+
+fn check_upload_size(
+    config: &Config,
+    max_upload_size: Option<u64>,
+    bytes: u64,
+    username: Option<&str>,
+) -> ActixResult<()> {
+    match max_upload_size {
+        Some(max_upload_size) if bytes > max_upload_size => {
+            Err(LunaError::payload_too_large(format!(
+                "Upload exceeds the maximum upload size of {}",
+                format::format_file_size(max_upload_size, size_units_for(config, username))
+            ))
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn job_accepted(job_id: u64) -> HttpResponse {
+    let status = format!("/api/jobs/{}", job_id);
+    HttpResponse::Accepted()
+        .insert_header((header::LOCATION, status.clone()))
+        .json(serde_json::json!({ "job": job_id, "status": status }))
+}
+
+async fn remove_entry(
+    state: &AppState,
+    target: DavTarget<'_>,
+    submitted: &[String],
+) -> ActixResult<Option<u64>> {
+    let DavTarget {
+        mount_name,
+        mount,
+        base: base_path,
+        relative: target_relative,
+        path: target_path,
+        username,
+        ..
+    } = target;
+    if target_relative.as_os_str().is_empty() {
+        return Err(LunaError::forbidden("The mount root cannot be deleted").into());
+    }
+    let metadata =
+        fs::symlink_metadata(&target_path).map_err(|_| LunaError::not_found("Path not found"))?;
+    if state.dav_locks.conflicts(
+        &mount_name,
+        &target_relative,
+        username.as_deref(),
+        submitted,
+    ) {
+        return Err(LunaError::locked("The entry is locked").into());
+    }
+
+    if mount.trash {
+        let trash_base = base_path.clone();
+        let trash_relative = target_relative.clone();
+        let trash_user = username.clone();
+        let trashed = run_blocking(state, move || {
+            trash::discard(
+                &trash_base,
+                &trash_relative,
+                &target_path,
+                trash_user.as_deref(),
+            )
+        })
+        .await?;
+        if let Some(username) = &username {
+            state.history.record(
+                username,
+                state.history.next_id(),
+                format!(
+                    "삭제: {}/{}",
+                    mount_name,
+                    pathbuf_to_string(&target_relative)
+                ),
+                history::Operation::Delete {
+                    mount: mount_name.clone(),
+                    path: target_relative.clone(),
+                    trash: trash::entry_path(&base_path, &trashed),
+                },
+            );
+        }
+    } else {
+        let threshold = state.config.server.blocking.background_delete_entries;
+        let count_path = target_path.clone();
+        let entries = run_blocking(state, move || count_entries(&count_path, threshold)).await?;
+        if entries > threshold {
+            let job_id = start_background_delete(
+                state,
+                &mount_name,
+                &base_path,
+                &target_path,
+                &target_relative,
+                username.clone(),
+            )
+            .map_err(LunaError::internal)?;
+            publish_file_event(
+                state,
+                &mount_name,
+                "delete",
+                &target_relative,
+                username.as_deref(),
+            );
+            return Ok(Some(job_id));
+        }
+
+        match &username {
+            Some(username) => {
+                let id = state.history.next_id();
+                let trash = base_path.join(format!(".lunafinder_undo_{}", id));
+                fs::rename(&target_path, &trash).map_err(LunaError::internal)?;
+                state.history.record(
+                    username,
+                    id,
+                    format!(
+                        "삭제: {}/{}",
+                        mount_name,
+                        pathbuf_to_string(&target_relative)
+                    ),
+                    history::Operation::Delete {
+                        mount: mount_name.clone(),
+                        path: target_relative.clone(),
+                        trash: trash.clone(),
+                    },
+                );
+                schedule_trash_purge(state, &mount_name, &base_path, trash);
+            }
+            None => {
+                run_blocking(state, move || {
+                    if metadata.is_dir() {
+                        fs::remove_dir_all(&target_path)
+                    } else {
+                        fs::remove_file(&target_path)
+                    }
+                    .with_context(|| format!("Failed to delete {:?}", target_path))
+                })
+                .await?;
+                schedule_blob_gc(state, mount, &base_path);
+            }
+        }
+    }
+    publish_file_event(
+        state,
+        &mount_name,
+        "delete",
+        &target_relative,
+        username.as_deref(),
+    );
+    Ok(None)
+}
+
+async fn delete_entry(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<DeleteForm>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let current_relative =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let target_relative = normalize_relative_path(&form.target_path)
+        .ok_or_else(|| LunaError::bad_request("Invalid target path"))?;
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &target_relative,
+    )
+    .ok_or_else(|| LunaError::forbidden("Write permission required"))?;
+    if !permission.allows_delete() {
+        return Err(LunaError::forbidden("Write permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let current_directory =
+        resolve_path(&base_path, &current_relative).map_err(LunaError::bad_request)?;
+    let target_path = resolve_path(&base_path, &target_relative).map_err(LunaError::bad_request)?;
+
+    if !target_path.starts_with(&current_directory)
+        && target_path.parent() != Some(&current_directory)
+    {
+        return Err(LunaError::bad_request("Target outside directory").into());
+    }
+
+    let subject = confirm_subject("delete", username.as_deref(), &target_relative);
+    if let Some((summary, token)) = confirmation_required(
+        &state,
+        &mount_name,
+        &subject,
+        &target_path,
+        form.confirm.as_deref(),
+    )
+    .await?
+    {
+        return confirmation_page(
+            &state,
+            &req,
+            "삭제 확인",
+            format!(
+                "{}/{}을(를) 삭제하려고 합니다.",
+                mount_name,
+                pathbuf_to_string(&target_relative)
+            ),
+            summary,
+            token,
+            vec![("target_path", form.target_path.clone())],
+        );
+    }
+
+    let target = DavTarget {
+        mount_name: mount_name.clone(),
+        mount,
+        base: base_path,
+        relative: target_relative,
+        path: target_path,
+        permission,
+        username,
+    };
+    if let Some(job_id) = remove_entry(&state, target, &[]).await? {
+        return Ok(job_accepted(job_id));
+    }
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            header::LOCATION,
+            format!(
+                "{}{}",
+                urls::entry("browse", &mount_name, &current_relative),
+                token_query(guest.as_ref())
+            ),
+        ))
+        .finish())
+}
+
+fn record_move(
+    state: &AppState,
+    username: Option<&str>,
+    source_mount: &str,
+    source: &Path,
+    destination_mount: &str,
+    destination: &Path,
+) {
+    let Some(username) = username else {
+        return;
+    };
+    state.history.record(
+        username,
+        state.history.next_id(),
+        format!(
+            "이동: {}/{} → {}/{}",
+            source_mount,
+            pathbuf_to_string(source),
+            destination_mount,
+            pathbuf_to_string(destination)
+        ),
+        history::Operation::Move {
+            source_mount: source_mount.to_string(),
+            source: source.to_path_buf(),
+            destination_mount: destination_mount.to_string(),
+            destination: destination.to_path_buf(),
+        },
+    );
+}
+
+fn schedule_trash_purge(state: &AppState, mount_name: &str, base_path: &Path, trash: PathBuf) {
+    let state = state.clone();
+    let mount_name = mount_name.to_string();
+    let base = base_path.to_path_buf();
+    actix_web::rt::spawn(async move {
+        tokio::time::sleep(StdDuration::from_secs(history::UNDO_WINDOW_SECS)).await;
+        let removed = trash.clone();
+        let result = web::block(move || {
+            match fs::symlink_metadata(&removed) {
+                Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&removed),
+                Ok(_) => fs::remove_file(&removed),
+                Err(_) => return Ok(false),
+            }
+            .map(|_| true)
+        })
+        .await;
+        match result {
+            Ok(Ok(true)) => {
+                if let Some(mount) = state.config.mounts.get(&mount_name) {
+                    schedule_blob_gc(&state, mount, &base);
+                }
+            }
+            Ok(Ok(false)) => {}
+            Ok(Err(e)) => log::warn!("Failed to purge {:?}: {}", trash, e),
+            Err(e) => log::warn!("Failed to purge {:?}: {}", trash, e),
+        }
+    });
+}
+
+fn purge_undo_leftovers(config: &Config) {
+    for mount in config.mounts.values() {
+        let Ok(entries) = fs::read_dir(&mount.path) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".lunafinder_undo_")
+            {
+                continue;
+            }
+            let path = entry.path();
+            let result = match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => fs::remove_dir_all(&path),
+                _ => fs::remove_file(&path),
+            };
+            if let Err(e) = result {
+                log::warn!("Failed to remove leftover {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+fn purge_staged_uploads(temp_dir: &Path) {
+    let Ok(entries) = fs::read_dir(temp_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(".lunafinder_")
+            || !entry.file_type().is_ok_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        let path = entry.path();
+        if let Err(e) = fs::remove_file(&path) {
+            log::warn!("Failed to remove leftover upload {:?}: {}", path, e);
+        }
+    }
+}
+
+fn staging_path(state: &AppState, parent: &Path, kind: &str) -> PathBuf {
+    state
+        .config
+        .server
+        .uploads
+        .temp_dir
+        .as_deref()
+        .unwrap_or(parent)
+        .join(format!(
+            ".lunafinder_{}_{:016x}",
+            kind,
+            rand::random::<u64>()
+        ))
+}
+
+async fn undo_last(state: &AppState, username: &str) -> ActixResult<(String, PathBuf)> {
+    ensure_writable(state)?;
+    let entry = state
+        .history
+        .last(username)
+        .ok_or_else(|| LunaError::not_found("Nothing to undo"))?;
+
+    let restored = match entry.operation.clone() {
+        history::Operation::Rename { mount, from, to } => {
+            effective_permission(state, Some(username), None, &mount, &to)
+                .filter(|permission| permission.allows_rename())
+                .ok_or_else(|| LunaError::forbidden("Rename permission required"))?;
+            let base = mount_base(state, &mount)?;
+            let current = resolve_path(&base, &to).map_err(LunaError::bad_request)?;
+            let original = resolve_path(&base, &from).map_err(LunaError::bad_request)?;
+            if fs::symlink_metadata(&original).is_ok() {
+                return Err(LunaError::conflict("The original name is taken").into());
+            }
+            fs::rename(&current, &original).map_err(LunaError::internal)?;
+            publish_file_event(state, &mount, "rename", &from, Some(username));
+            (mount, from)
+        }
+        history::Operation::Move {
+            source_mount,
+            source,
+            destination_mount,
+            destination,
+        } => {
+            effective_permission(
+                state,
+                Some(username),
+                None,
+                &destination_mount,
+                &destination,
+            )
+            .filter(|permission| permission.allows_read() && permission.allows_delete())
+            .ok_or_else(|| LunaError::forbidden("Permission required on destination"))?;
+            effective_permission(state, Some(username), None, &source_mount, &source)
+                .filter(|permission| permission.allows_upload())
+                .ok_or_else(|| LunaError::forbidden("Write permission required on source"))?;
+            let current = resolve_path(&mount_base(state, &destination_mount)?, &destination)
+                .map_err(LunaError::bad_request)?;
+            let original = resolve_path(&mount_base(state, &source_mount)?, &source)
+                .map_err(LunaError::bad_request)?;
+            if fs::symlink_metadata(&original).is_ok() {
+                return Err(LunaError::conflict("The original location is taken").into());
+            }
+            run_blocking(state, move || move_entry(&current, &original)).await?;
+            publish_file_event(
+                state,
+                &destination_mount,
+                "delete",
+                &destination,
+                Some(username),
+            );
+            publish_file_event(state, &source_mount, "upload", &source, Some(username));
+            (source_mount, source)
+        }
+        history::Operation::Delete { mount, path, trash } => {
+            effective_permission(state, Some(username), None, &mount, &path)
+                .filter(|permission| permission.allows_delete())
+                .ok_or_else(|| LunaError::forbidden("Delete permission required"))?;
+            let original =
+                resolve_path(&mount_base(state, &mount)?, &path).map_err(LunaError::bad_request)?;
+            if fs::symlink_metadata(&trash).is_err() {
+                state.history.remove(username, entry.id);
+                return Err(LunaError::conflict("The deleted entry is gone").into());
+            }
+            if fs::symlink_metadata(&original).is_ok() {
+                return Err(LunaError::conflict("The original location is taken").into());
+            }
+            fs::rename(&trash, &original).map_err(LunaError::internal)?;
+            publish_file_event(state, &mount, "upload", &path, Some(username));
+            (mount, path)
+        }
+    };
+    state.history.remove(username, entry.id);
+    Ok(restored)
+}
+
+fn mount_base(state: &AppState, mount_name: &str) -> Result<PathBuf, LunaError> {
+    let mount = state
+        .config
+        .mounts
+        .get(mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    canonicalize_mount(&mount.path).map_err(LunaError::internal)
+}
+
+async fn history_list(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    Ok(HttpResponse::Ok()
+        .append_header((header::CACHE_CONTROL, "no-cache"))
+        .json(state.history.list(&username)))
+}
+
+async fn history_undo_api(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    let (mount, path) = undo_last(&state, &username).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "mount": mount,
+        "path": osname::encode_path(&path),
+    })))
+}
+
+async fn history_undo(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    let (mount, path) = undo_last(&state, &username).await?;
+    let parent = path.parent().unwrap_or(Path::new(""));
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, urls::entry("browse", &mount, parent)))
+        .finish())
+}
+
+fn confirm_subject(operation: &str, username: Option<&str>, relative: &Path) -> String {
+    format!(
+        "{}\0{}\0{}",
+        operation,
+        username.unwrap_or_default(),
+        pathbuf_to_string(relative)
+    )
+}
+
+async fn confirmation_required(
+    state: &AppState,
+    mount_name: &str,
+    subject: &str,
+    target_path: &Path,
+    confirm: Option<&str>,
+) -> ActixResult<Option<(confirm::Summary, String)>> {
+    let limits = &state.config.server.confirm;
+    if !limits.is_enabled()
+        || confirm.is_some_and(|token| confirm::verify(&state.signer, mount_name, subject, token))
+    {
+        return Ok(None);
+    }
+    let measured = target_path.to_path_buf();
+    let thresholds = limits.clone();
+    let summary = run_blocking(state, move || confirm::measure(&measured, &thresholds)).await?;
+    if !limits.exceeded_by(&summary) {
+        return Ok(None);
+    }
+    Ok(Some((
+        summary,
+        confirm::token(&state.signer, mount_name, subject),
+    )))
+}
+
+fn confirmation_page(
+    state: &AppState,
+    req: &HttpRequest,
+    title: &str,
+    description: String,
+    summary: confirm::Summary,
+    token: String,
+    fields: Vec<(&str, String)>,
+) -> ActixResult<HttpResponse> {
+    let fields: Vec<_> = fields
+        .into_iter()
+        .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+        .collect();
+    let username = get_username_from_cookie(req);
+    let mut context = TeraContext::new();
+    context.insert("title", title);
+    context.insert("instance_name", &state.config.main_page.title);
+    context.insert("content", "");
+    context.insert("username", &username);
+    context.insert(
+        "size_units",
+        size_units_for(&state.config, username.as_deref()).as_str(),
+    );
+    context.insert(
+        "confirm",
+        &serde_json::json!({
+            "action": req.uri().to_string(),
+            "description": description,
+            "summary": summary,
+            "token": token,
+            "fields": fields,
+        }),
+    );
+
+    let html = render_template(state, "page.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+fn start_background_delete(
+    state: &AppState,
+    mount_name: &str,
+    base_path: &Path,
+    target_path: &Path,
+    target_relative: &Path,
+    username: Option<String>,
+) -> anyhow::Result<u64> {
+    let job_id = state.events.next_job_id();
+    let trash = base_path.join(format!(".lunafinder_trash_{}", job_id));
+    fs::rename(target_path, &trash)
+        .with_context(|| format!("Failed to move {:?} aside for deletion", target_path))?;
+
+    let job = state.jobs.start(
+        job_id,
+        username,
+        format!(
+            "delete {}/{}",
+            mount_name,
+            pathbuf_to_string(target_relative)
+        ),
+    );
+    let publish_job = {
+        let state = state.clone();
+        let job = job.clone();
+        move |status| {
+            job.set_status(status);
+            state.events.publish(events::Event::Job {
+                id: job.id,
+                username: job.username.clone(),
+                label: job.label.clone(),
+                status,
+            });
+        }
+    };
+    publish_job(events::JobStatus::Started);
+
+    let state = state.clone();
+    let mount_name = mount_name.to_string();
+    let base_path = base_path.to_path_buf();
+    let original = target_path.to_path_buf();
+    actix_web::rt::spawn(async move {
+        let bus = state.events.clone();
+        let worker = job.clone();
+        let removed = web::block(move || {
+            worker.set_total(count_entries(&trash, u64::MAX).unwrap_or(0));
+            let progress = |done: u64| {
+                if done.is_multiple_of(DELETE_PROGRESS_INTERVAL) || done == worker.total() {
+                    bus.publish(events::Event::Progress {
+                        id: worker.id,
+                        username: worker.username.clone(),
+                        done,
+                        total: worker.total(),
+                    });
+                }
+            };
+            let outcome = remove_tree(&trash, &worker, progress);
+            if matches!(outcome, Ok(false))
+                && (original.exists() || fs::rename(&trash, &original).is_err())
+            {
+                log::warn!(
+                    "Cancelled deletion of {:?} left the remaining entries in {:?}",
+                    original,
+                    trash
+                );
+            }
+            outcome
+        })
+        .await;
+        match removed {
+            Ok(Ok(true)) => publish_job(events::JobStatus::Finished),
+            Ok(Ok(false)) => publish_job(events::JobStatus::Cancelled),
+            Ok(Err(e)) => {
+                log::error!("Background deletion job {} failed: {}", job.id, e);
+                publish_job(events::JobStatus::Failed);
+            }
+            Err(e) => {
+                log::error!("Background deletion job {} failed: {}", job.id, e);
+                publish_job(events::JobStatus::Failed);
+            }
+        }
+        if let Some(mount) = state.config.mounts.get(&mount_name) {
+            schedule_blob_gc(&state, mount, &base_path);
+        }
+    });
+
+    Ok(job_id)
+}
+
+fn visible_job(state: &AppState, req: &HttpRequest, id: u64) -> Result<Arc<jobs::Job>, LunaError> {
+    let username = get_username_from_cookie(req);
+    state
+        .jobs
+        .get(id)
+        .filter(|job| {
+            username.is_some()
+                && (job.username == username || is_admin(&state.config, username.as_deref()))
+        })
+        .ok_or_else(|| LunaError::not_found("Job not found"))
+}
+
+async fn job_status(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<u64>,
+) -> ActixResult<HttpResponse> {
+    let job = visible_job(&state, &req, path.into_inner())?;
+    Ok(HttpResponse::Ok()
+        .append_header((header::CACHE_CONTROL, "no-cache"))
+        .json(job.snapshot()))
+}
+
+async fn cancel_job(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<u64>,
+) -> ActixResult<HttpResponse> {
+    let job = visible_job(&state, &req, path.into_inner())?;
+    if job.status() != events::JobStatus::Started {
+        return Err(LunaError::conflict("Job has already ended").into());
+    }
+    job.cancel();
+    Ok(HttpResponse::Accepted().json(job.snapshot()))
+}
+
+async fn rename_entry(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<RenameForm>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let current_relative =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let target_relative = normalize_relative_path(&form.target_path)
+        .ok_or_else(|| LunaError::bad_request("Invalid target path"))?;
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &target_relative,
+    )
+    .ok_or_else(|| LunaError::forbidden("Write permission required"))?;
+    if !permission.allows_rename() {
+        return Err(LunaError::forbidden("Write permission required").into());
+    }
+
+    let new_name = sanitize_file_name(&form.new_name)
+        .ok_or_else(|| LunaError::bad_request("Invalid new name"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let current_directory =
+        resolve_path(&base_path, &current_relative).map_err(LunaError::bad_request)?;
+    let source_path = resolve_path(&base_path, &target_relative).map_err(LunaError::bad_request)?;
+
+    if source_path.parent() != Some(&current_directory) {
+        return Err(LunaError::bad_request("Target outside directory").into());
+    }
+
+    let destination = current_directory.join(&new_name);
+    fs::rename(&source_path, &destination).map_err(LunaError::internal)?;
+    if let Some(username) = &username {
+        state.history.record(
+            username,
+            state.history.next_id(),
+            format!(
+                "이름 변경: {}/{} → {}",
+                mount_name,
+                pathbuf_to_string(&target_relative),
+                new_name
+            ),
+            history::Operation::Rename {
+                mount: mount_name.clone(),
+                from: target_relative.clone(),
+                to: current_relative.join(&new_name),
+            },
+        );
+    }
+    publish_file_event(
+        &state,
+        &mount_name,
+        "rename",
+        &current_relative.join(&new_name),
+        username.as_deref(),
+    );
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            header::LOCATION,
+            format!(
+                "{}{}",
+                urls::entry("browse", &mount_name, &current_relative),
+                token_query(guest.as_ref())
+            ),
+        ))
+        .finish())
+}
+
+async fn create_folder(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<MkdirForm>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let current_relative =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let name = sanitize_file_name(&form.name)
+        .ok_or_else(|| LunaError::bad_request("Invalid folder name"))?;
+    let folder_relative = current_relative.join(&name);
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &folder_relative,
+    )
+    .ok_or_else(|| LunaError::forbidden("Create folder permission required"))?;
+    if !permission.allows_create_folder() {
+        return Err(LunaError::forbidden("Create folder permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let current_directory =
+        resolve_path(&base_path, &current_relative).map_err(LunaError::bad_request)?;
+    if !current_directory.is_dir() {
+        return Err(LunaError::not_found("Path not found").into());
+    }
+    let folder_path = current_directory.join(&name);
+    if fs::symlink_metadata(&folder_path).is_ok() {
+        return Err(LunaError::conflict("An entry with that name already exists").into());
+    }
+    fs::create_dir(&folder_path).map_err(LunaError::internal)?;
+    publish_file_event(
+        &state,
+        &mount_name,
+        "create_folder",
+        &folder_relative,
+        username.as_deref(),
+    );
+    if mount.folder_readme {
+        let creator = match (&username, &guest) {
+            (Some(username), _) => username.as_str(),
+            (None, Some(_)) => "게스트",
+            (None, None) => "익명",
+        };
+        if write_folder_readme(
+            &state,
+            &mount_name,
+            mount,
+            &base_path,
+            &folder_relative,
+            creator,
+        )
+        .await
+        {
+            publish_file_event(
+                &state,
+                &mount_name,
+                "upload",
+                &folder_relative.join(FOLDER_README),
+                username.as_deref(),
+            );
+        }
+    }
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            header::LOCATION,
+            format!(
+                "{}{}",
+                urls::entry("browse", &mount_name, &current_relative),
+                token_query(guest.as_ref())
+            ),
+        ))
+        .finish())
+}
+
+async fn write_folder_readme(
+    state: &AppState,
+    mount_name: &str,
+    mount: &MountConfig,
+    base_path: &Path,
+    folder_relative: &Path,
+    creator: &str,
+) -> bool {
+    let template = match &mount.folder_readme_template {
+        Some(template) => match fs::read_to_string(template) {
+            Ok(template) => template,
+            Err(e) => {
+                log::warn!(
+                    "Failed to read folder README template {:?}: {}",
+                    template,
+                    e
+                );
+                return false;
+            }
+        },
+        None => DEFAULT_FOLDER_README.to_string(),
+    };
+    let name = folder_relative
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let content = template
+        .replace("{name}", &name)
+        .replace("{path}", &pathbuf_to_string(folder_relative))
+        .replace("{creator}", creator)
+        .replace("{date}", &clock::format_iso8601(clock::now_unix())[..10]);
+
+    let readme_path = base_path.join(folder_relative).join(FOLDER_README);
+    let written = storage::FileWriter::create(storage_codec(state, mount_name), &readme_path)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.finish()
+        });
+    if let Err(e) = written {
+        log::warn!("Failed to write folder README {:?}: {}", readme_path, e);
+        return false;
+    }
+    store_deduplicated(state, mount, base_path, &readme_path).await;
+    write_checksum(state, mount_name, mount, base_path, &readme_path).await;
+    true
+}
+
+async fn transfer_entry(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<TransferForm>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let form = form.into_inner();
+    let is_move = match form.operation.as_str() {
+        "copy" => false,
+        "move" => true,
+        _ => return Err(LunaError::bad_request("Unknown operation").into()),
+    };
+
+    let source_mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    let destination_mount = config
+        .mounts
+        .get(&form.destination_mount)
+        .ok_or_else(|| LunaError::not_found("Destination mount not found"))?;
+    if form.destination_mount != mount_name
+        && (state.mount_keys.contains_key(&mount_name)
+            || state.mount_keys.contains_key(&form.destination_mount))
+    {
+        return Err(LunaError::bad_request(
+            "Entries cannot be transferred to or from an encrypted mount",
+        )
+        .into());
+    }
+
+    let current_relative =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let source_relative = normalize_relative_path(&form.target_path)
+        .ok_or_else(|| LunaError::bad_request("Invalid target path"))?;
+    let destination_relative = normalize_relative_path(&form.destination_path)
+        .ok_or_else(|| LunaError::bad_request("Invalid destination path"))?;
+
+    let username = get_username_from_cookie(&req);
+    let source_permission = effective_permission(
+        &state,
+        username.as_deref(),
+        None,
+        &mount_name,
+        &source_relative,
+    )
+    .ok_or_else(|| LunaError::forbidden("Read permission required"))?;
+    if !source_permission.allows_read() || (is_move && !source_permission.allows_delete()) {
+        return Err(LunaError::forbidden("Permission required on source").into());
+    }
+    let destination_permission = effective_permission(
+        &state,
+        username.as_deref(),
+        None,
+        &form.destination_mount,
+        &destination_relative,
+    )
+    .ok_or_else(|| LunaError::forbidden("Write permission required"))?;
+    if !destination_permission.allows_upload()
+        || (destination_mount.moderation && !destination_permission.allows_moderate())
+    {
+        return Err(LunaError::forbidden("Write permission required on destination").into());
+    }
+
+    let source_base = canonicalize_mount(&source_mount.path).map_err(LunaError::internal)?;
+    let destination_base =
+        canonicalize_mount(&destination_mount.path).map_err(LunaError::internal)?;
+    let current_directory =
+        resolve_path(&source_base, &current_relative).map_err(LunaError::bad_request)?;
+    let source_path =
+        resolve_path(&source_base, &source_relative).map_err(LunaError::bad_request)?;
+    let destination_directory =
+        resolve_path(&destination_base, &destination_relative).map_err(LunaError::bad_request)?;
+
+    if source_relative.as_os_str().is_empty() || source_path.parent() != Some(&current_directory) {
+        return Err(LunaError::bad_request("Target outside directory").into());
+    }
+    if !destination_directory.is_dir() {
+        return Err(LunaError::bad_request("Destination is not a directory").into());
+    }
+    if destination_directory.starts_with(&source_path) {
+        return Err(LunaError::bad_request("Cannot copy a directory into itself").into());
+    }
+
+    let file_name = source_path
+        .file_name()
+        .map(PathBuf::from)
+        .ok_or_else(|| LunaError::bad_request("Invalid target path"))?;
+    let destination_path = destination_directory.join(&file_name);
+    if destination_path.exists() {
+        return Err(LunaError::conflict("Destination already exists").into());
+    }
+
+    if is_move {
+        let subject = confirm_subject("move", username.as_deref(), &source_relative);
+        if let Some((summary, token)) = confirmation_required(
+            &state,
+            &mount_name,
+            &subject,
+            &source_path,
+            form.confirm.as_deref(),
+        )
+        .await?
+        {
+            return confirmation_page(
+                &state,
+                &req,
+                "이동 확인",
+                format!(
+                    "{}/{}을(를) {}/{}(으)로 옮기려고 합니다.",
+                    mount_name,
+                    pathbuf_to_string(&source_relative),
+                    form.destination_mount,
+                    pathbuf_to_string(&destination_relative)
+                ),
+                summary,
+                token,
+                vec![
+                    ("target_path", form.target_path.clone()),
+                    ("destination_mount", form.destination_mount.clone()),
+                    ("destination_path", form.destination_path.clone()),
+                    ("operation", form.operation.clone()),
+                ],
+            );
+        }
+    }
+
+    let job_id = state.events.next_job_id();
+    let publish_job = |status| {
+        state.events.publish(events::Event::Job {
+            id: job_id,
+            username: username.clone(),
+            label: format!(
+                "{} {}/{} → {}",
+                form.operation, mount_name, form.target_path, form.destination_mount
+            ),
+            status,
+        })
+    };
+    publish_job(events::JobStatus::Started);
+    let result = run_blocking(&state, move || {
+        if is_move {
+            move_entry(&source_path, &destination_path)
+        } else {
+            copy_entry(&source_path, &destination_path)
+        }
+    })
+    .await;
+    if let Err(e) = result {
+        publish_job(events::JobStatus::Failed);
+        return Err(e);
+    }
+    publish_job(events::JobStatus::Finished);
+
+    if is_move {
+        schedule_blob_gc(&state, source_mount, &source_base);
+        publish_file_event(
+            &state,
+            &mount_name,
+            "delete",
+            &source_relative,
+            username.as_deref(),
+        );
+        record_move(
+            &state,
+            username.as_deref(),
+            &mount_name,
+            &source_relative,
+            &form.destination_mount,
+            &destination_relative.join(&file_name),
+        );
+    }
+    publish_file_event(
+        &state,
+        &form.destination_mount,
+        "upload",
+        &destination_relative.join(&file_name),
+        username.as_deref(),
+    );
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            header::LOCATION,
+            urls::entry("browse", &mount_name, &current_relative),
+        ))
+        .finish())
+}
+
+async fn move_within_mount(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<RelocateForm>,
+) -> ActixResult<HttpResponse> {
+    relocate_within_mount(state, req, path, form.into_inner(), "move").await
+}
+
+async fn copy_within_mount(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<RelocateForm>,
+) -> ActixResult<HttpResponse> {
+    relocate_within_mount(state, req, path, form.into_inner(), "copy").await
+}
+
+async fn relocate_within_mount(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: RelocateForm,
+    operation: &str,
+) -> ActixResult<HttpResponse> {
+    let destination_mount = path.0.clone();
+    transfer_entry(
+        state,
+        req,
+        path,
+        web::Form(TransferForm {
+            target_path: form.target_path,
+            destination_mount,
+            destination_path: form.destination_path,
+            operation: operation.to_string(),
+            confirm: form.confirm,
+        }),
+    )
+    .await
+}
+
+fn expires_after(hours: u64) -> Result<u64, LunaError> {
+    hours
+        .checked_mul(3600)
+        .and_then(|seconds| clock::now_unix().checked_add(seconds))
+        .ok_or_else(|| LunaError::bad_request("Invalid expiry"))
+}
+
+async fn mint_guest_token(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<GuestTokenForm>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    let permission =
+        effective_permission(&state, Some(&username), None, &mount_name, &relative_path)
+            .ok_or_else(|| LunaError::forbidden("Permission required"))?;
+
+    let form = form.into_inner();
+    let actions = form
+        .actions
+        .split(',')
+        .map(|action| action.trim().to_lowercase())
+        .filter(|action| !action.is_empty())
+        .collect::<Vec<_>>();
+    if actions.is_empty() {
+        return Err(LunaError::bad_request("No actions selected").into());
+    }
+    for action in &actions {
+        if !GUEST_TOKEN_ACTIONS.contains(&action.as_str()) {
+            return Err(LunaError::bad_request(format!(
+                "Action cannot be granted to guests: {}",
+                action
+            ))
+            .into());
+        }
+        let allowed = match action.as_str() {
+            "read" => permission.allows_read(),
+            "upload" => permission.allows_upload(),
+            "rename" => permission.allows_rename(),
+            _ => permission.allows_delete(),
+        };
+        if !allowed {
+            return Err(LunaError::forbidden(format!(
+                "You cannot grant the {} permission",
+                action
+            ))
+            .into());
+        }
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let directory_path =
+        resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !directory_path.is_dir() {
+        return Err(LunaError::bad_request("Target is not a directory").into());
+    }
+
+    let hours = form
+        .expires_in_hours
+        .and_then(|hours| hours.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_GUEST_TOKEN_HOURS)
+        .min(state.config.server.limits.max_link_hours);
+    let expires_at = expires_after(hours)?;
+
+    let guest_tokens = state.tokens.clone();
+    let token_mount = mount_name.clone();
+    let token_path = relative_path.clone();
+    let token_owner = username.clone();
+    let guest = run_blocking(&state, move || {
+        guest_tokens.mint(
+            &token_mount,
+            &token_path,
+            actions,
+            expires_at,
+            &token_owner,
+            None,
+        )
+    })
+    .await?;
+    log::info!(
+        "{} created a guest token for {}/{} ({})",
+        username,
+        mount_name,
+        pathbuf_to_string(&relative_path),
+        guest.actions.join(", ")
+    );
+
+    let link = format!(
+        "{}{}",
+        urls::entry("browse", &mount_name, &relative_path),
+        token_query(Some(&guest))
+    );
+
+    let mut context = TeraContext::new();
+    context.insert("title", "게스트 링크");
+    context.insert("instance_name", &config.main_page.title);
+    context.insert("guest_link", &link);
+    context.insert("guest_actions", &guest.actions.join(", "));
+    context.insert("guest_hours", &hours);
+    context.insert("content", "");
+    context.insert("username", &username);
+
+    let html = render_template(&state, "page.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn mint_drop_box(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<DropForm>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    effective_permission(&state, Some(&username), None, &mount_name, &relative_path)
+        .filter(Permission::allows_upload)
+        .ok_or_else(|| LunaError::forbidden("You cannot grant the upload permission"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let directory_path =
+        resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !directory_path.is_dir() {
+        return Err(LunaError::bad_request("Target is not a directory").into());
+    }
+
+    let form = form.into_inner();
+    let max_size = match form.max_size_mb.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(value) => Some(
+            value
+                .parse::<u64>()
+                .ok()
+                .filter(|megabytes| *megabytes > 0)
+                .and_then(|megabytes| megabytes.checked_mul(1024 * 1024))
+                .ok_or_else(|| LunaError::bad_request("Invalid size limit"))?,
+        ),
+    };
+    let extensions = form
+        .extensions
+        .unwrap_or_default()
+        .split(',')
+        .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+        .filter(|extension| !extension.is_empty())
+        .collect::<Vec<_>>();
+    let drop = tokens::DropBox {
+        max_size,
+        extensions,
+        received: 0,
+    };
+
+    let hours = form
+        .expires_in_hours
+        .and_then(|hours| hours.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_GUEST_TOKEN_HOURS)
+        .min(state.config.server.limits.max_link_hours);
+    let expires_at = expires_after(hours)?;
+
+    let guest_tokens = state.tokens.clone();
+    let token_mount = mount_name.clone();
+    let token_path = relative_path.clone();
+    let token_owner = username.clone();
+    let guest = run_blocking(&state, move || {
+        guest_tokens.mint(
+            &token_mount,
+            &token_path,
+            vec!["upload".to_string()],
+            expires_at,
+            &token_owner,
+            Some(drop),
+        )
+    })
+    .await?;
+    log::info!(
+        "{} created a drop box for {}/{}",
+        username,
+        mount_name,
+        pathbuf_to_string(&relative_path)
+    );
+
+    let mut context = TeraContext::new();
+    context.insert("title", "업로드 전용 링크");
+    context.insert("instance_name", &config.main_page.title);
+    context.insert("guest_link", &format!("/drop/{}", guest.token));
+    context.insert("guest_actions", "업로드 전용");
+    context.insert("guest_hours", &hours);
+    context.insert("content", "");
+    context.insert("username", &username);
+
+    let html = render_template(&state, "page.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+fn drop_box(
+    state: &AppState,
+    token: &str,
+) -> Result<(tokens::GuestToken, tokens::DropBox), LunaError> {
+    state
+        .tokens
+        .get(token)
+        .and_then(|guest| guest.drop.clone().map(|drop| (guest, drop)))
+        .ok_or_else(|| LunaError::not_found("Drop box not found or expired"))
+}
+
+fn render_drop_page(
+    state: &AppState,
+    guest: &tokens::GuestToken,
+    drop: &tokens::DropBox,
+    received: &[String],
+) -> ActixResult<HttpResponse> {
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&guest.mount)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    let max_size = [drop.max_size, max_upload_size(config, mount, None)]
+        .into_iter()
+        .flatten()
+        .min();
+
+    let mut context = TeraContext::new();
+    context.insert("instance_name", &config.main_page.title);
+    context.insert("token", &guest.token);
+    context.insert("created_by", &guest.created_by);
+    context.insert("expires_at", &guest.expires_at);
+    context.insert("max_size", &max_size);
+    context.insert("extensions", &drop.extensions);
+    context.insert("received", received);
+    context.insert("size_units", size_units_for(config, None).as_str());
+
+    let html = render_template(state, "drop.html", &mut context)?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .content_type("text/html")
+        .body(html))
+}
+
+async fn drop_page(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let (guest, drop) = drop_box(&state, &path.into_inner())?;
+    render_drop_page(&state, &guest, &drop, &[])
+}
+
+async fn drop_upload(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    mut payload: Multipart,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (guest, drop) = drop_box(&state, &path.into_inner())?;
+    let config = &state.config;
+    let mount_name = guest.mount.clone();
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    effective_permission(&state, None, Some(&guest), &mount_name, &guest.path)
+        .filter(Permission::allows_upload)
+        .ok_or_else(|| LunaError::forbidden("Write permission required"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let directory_path = resolve_path(&base_path, &guest.path).map_err(LunaError::bad_request)?;
+    if !directory_path.is_dir() {
+        return Err(LunaError::not_found("Drop box folder no longer exists").into());
+    }
+    let directory_path = if mount.moderation {
+        let queued = moderation::queue_root(&base_path).join(&guest.path);
+        fs::create_dir_all(&queued).map_err(LunaError::internal)?;
+        queued
+    } else {
+        directory_path
+    };
+
+    let limits = &config.server.limits;
+    if let Some(max_request_size) = limits.max_request_size {
+        let content_length = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if content_length.is_some_and(|length| length > max_request_size) {
+            return Err(
+                LunaError::payload_too_large("Upload exceeds the request size limit").into(),
+            );
+        }
+    }
+
+    let max_upload_size = max_upload_size(config, mount, None);
+    let mut total_bytes: u64 = 0;
+    let mut received = Vec::new();
+
+    while let Some(mut field) = payload.try_next().await.map_err(LunaError::internal)? {
+        let Some(sanitized) = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename())
+            .and_then(sanitize_file_name)
+        else {
+            continue;
+        };
+        if !drop.allows_name(&sanitized) {
+            return Err(LunaError::unsupported_media_type(format!(
+                "Only these file types are accepted: {}",
+                drop.extensions.join(", ")
+            ))
+            .into());
+        }
+        if limits
+            .max_files
+            .is_some_and(|max_files| received.len() >= max_files)
+        {
+            return Err(LunaError::payload_too_large("Upload exceeds the file count limit").into());
+        }
+
+        let file_path = unclaimed_path(&directory_path, &sanitized);
+        let stored_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let staged_path = staging_path(&state, &directory_path, "upload");
+        let mut file = storage::QueuedWriter::spawn(
+            storage::FileWriter::create(
+                staged_codec(&state, &mount_name, &file_path),
+                &staged_path,
+            )
+            .map_err(LunaError::internal)?,
+            config.server.uploads.queue_chunks,
+        );
+        let mut field_bytes: u64 = 0;
+        let mut head = Vec::new();
+        let result: ActixResult<()> = async {
+            while let Some(chunk) = field.try_next().await.map_err(LunaError::internal)? {
+                field_bytes += chunk.len() as u64;
+                total_bytes += chunk.len() as u64;
+                check_upload_limits(limits, field_bytes, total_bytes)?;
+                check_upload_size(config, max_upload_size, total_bytes, None)?;
+                check_upload_size(config, drop.max_size, field_bytes, None)?;
+                if head.len() < content::SNIFF_LEN {
+                    head.extend_from_slice(
+                        &chunk[..chunk.len().min(content::SNIFF_LEN - head.len())],
+                    );
+                    if head.len() == content::SNIFF_LEN {
+                        check_drop_content(mount, &head)?;
+                    }
+                }
+                file.write(chunk).await.map_err(LunaError::internal)?;
+            }
+            if head.len() < content::SNIFF_LEN {
+                check_drop_content(mount, &head)?;
+            }
+            file.finish().await.map_err(LunaError::internal)?;
+            Ok(())
+        }
+        .await
+        .and_then(|_| {
+            storage::persist(&staged_path, &file_path).map_err(|e| LunaError::internal(e).into())
+        });
+        if let Err(e) = result {
+            let _ = fs::remove_file(&staged_path);
+            return Err(e);
+        }
+
+        let target_relative = guest.path.join(&stored_name);
+        record_access(
+            &state,
+            &mount_name,
+            stats::EventKind::Upload,
+            None,
+            field_bytes,
+        );
+        if !mount.moderation {
+            publish_file_event(&state, &mount_name, "upload", &target_relative, None);
+        }
+        if let Err(e) = state.tokens.record_drop(&guest.token) {
+            log::warn!("Failed to record drop box upload: {}", e);
+        }
+        notify_creator(
+            &state,
+            &guest,
+            format!(
+                "업로드 전용 링크 {}/{}로 {} 파일을 받았습니다.",
+                guest.mount,
+                pathbuf_to_string(&guest.path),
+                stored_name
+            ),
+        );
+
+        if let Some(scan) = &config.scan {
+            let command = scan.command.clone();
+            let scan_path = file_path.clone();
+            let codec = storage_codec(&state, &mount_name).into_owned();
+            let outcome = run_blocking(&state, move || {
+                scan_stored_file(&command, codec.as_codec(), &scan_path)
+            })
+            .await?;
+            if let ScanOutcome::Flagged(verdict) = outcome {
+                log::warn!(
+                    "Drop box upload {:?} to mount '{}' flagged by scanner: {}",
+                    file_path,
+                    mount_name,
+                    verdict
+                );
+                quarantine::isolate(&base_path, &target_relative, &file_path, &verdict)
+                    .map_err(LunaError::internal)?;
+            }
+        }
+        store_deduplicated(&state, mount, &base_path, &file_path).await;
+        write_checksum(&state, &mount_name, mount, &base_path, &file_path).await;
+        received.push(sanitized);
+    }
+
+    if received.is_empty() {
+        return Err(LunaError::bad_request("No files were uploaded").into());
+    }
+    render_drop_page(&state, &guest, &drop, &received)
+}
+
+fn check_drop_content(mount: &MountConfig, head: &[u8]) -> ActixResult<()> {
+    content::check(&mount.allow_content, &mount.deny_content, head).map_err(|category| {
+        LunaError::unsupported_media_type(format!(
+            "Uploading {} content to this mount is not allowed",
+            category
+        ))
+        .into()
+    })
+}
+
+fn unclaimed_path(directory: &Path, name: &str) -> PathBuf {
+    let candidate = directory.join(name);
+    if fs::symlink_metadata(&candidate).is_err() {
+        return candidate;
+    }
+    let original = Path::new(name);
+    let stem = original
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = original
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| directory.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .unwrap_or(candidate)
+}
+
+async fn presign_download(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<PresignForm>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, _) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let form = form.into_inner();
+    let relative_path = normalize_relative_path(&form.target_path)
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    let can_read = effective_permission(&state, Some(&username), None, &mount_name, &relative_path)
+        .is_some_and(|permission| permission.allows_read());
+    if !can_read {
+        return Err(LunaError::forbidden("Read permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let file_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !file_path.is_file() {
+        return Err(LunaError::bad_request("Target is not a file").into());
+    }
+
+    let hours = form
+        .expires_in_hours
+        .and_then(|hours| hours.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_PRESIGN_HOURS)
+        .min(state.config.server.limits.max_link_hours);
+    let expires = expires_after(hours)?;
+    let signed_path = pathbuf_to_string(&relative_path);
+    let signature = state.signer.sign(&mount_name, &signed_path, expires);
+    log::info!(
+        "{} signed a download URL for {}/{} valid for {} hours",
+        username,
+        mount_name,
+        signed_path,
+        hours
+    );
+
+    let connection = req.connection_info();
+    let link = format!(
+        "{}://{}{}?expires={}&sig={}",
+        connection.scheme(),
+        connection.host(),
+        urls::entry("dl", &mount_name, &relative_path),
+        expires,
+        signature
+    );
+
+    let mut context = TeraContext::new();
+    context.insert("title", "서명된 다운로드 링크");
+    context.insert("instance_name", &config.main_page.title);
+    context.insert("guest_link", &link);
+    context.insert("guest_actions", "다운로드");
+    context.insert("guest_hours", &hours);
+    context.insert("content", "");
+    context.insert("username", &username);
+
+    let html = render_template(&state, "page.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn browse_manifest(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<ManifestForm>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let form = form.into_inner();
+    let hours = form
+        .expires_in_hours
+        .and_then(|hours| hours.trim().parse::<u64>().ok());
+    manifest_response(
+        &state,
+        &req,
+        mount_name,
+        vec![relative_path],
+        hours,
+        form.format.as_deref(),
+    )
+    .await
+}
+
+async fn api_manifest(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ManifestRequest>,
+) -> ActixResult<HttpResponse> {
+    let body = body.into_inner();
+    if body.paths.is_empty() {
+        return Err(LunaError::bad_request("No paths selected").into());
+    }
+    let selection = body
+        .paths
+        .iter()
+        .map(|path| normalize_relative_path(path))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    manifest_response(
+        &state,
+        &req,
+        path.into_inner(),
+        selection,
+        body.expires_in_hours,
+        body.format.as_deref(),
+    )
+    .await
+}
+
+async fn manifest_response(
+    state: &AppState,
+    req: &HttpRequest,
+    mount_name: String,
+    selection: Vec<PathBuf>,
+    hours: Option<u64>,
+    format: Option<&str>,
+) -> ActixResult<HttpResponse> {
+    let mount = state
+        .config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    let format = manifest::Format::parse(format)
+        .ok_or_else(|| LunaError::bad_request("Unsupported manifest format"))?;
+    let username =
+        get_username_from_cookie(req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    for relative in &selection {
+        effective_permission(state, Some(&username), None, &mount_name, relative)
+            .filter(Permission::allows_read)
+            .ok_or_else(|| LunaError::forbidden("Read permission required"))?;
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    for relative in &selection {
+        let target = resolve_path(&base_path, relative).map_err(LunaError::bad_request)?;
+        if !target.exists() {
+            return Err(LunaError::not_found("Path not found").into());
+        }
+    }
+
+    let permission_state = state.clone();
+    let permission_user = username.clone();
+    let permission_mount = mount_name.clone();
+    let files = run_blocking(state, move || {
+        manifest::collect(&base_path, &selection, |relative| {
+            effective_permission(
+                &permission_state,
+                Some(&permission_user),
+                None,
+                &permission_mount,
+                relative,
+            )
+            .is_some_and(|permission| permission.allows_read())
+        })
+    })
+    .await?;
+    if files.len() > manifest::MAX_FILES {
+        return Err(LunaError::payload_too_large(format!(
+            "Selection has more than {} files",
+            manifest::MAX_FILES
+        ))
+        .into());
+    }
+
+    let hours = hours
+        .unwrap_or(DEFAULT_PRESIGN_HOURS)
+        .min(state.config.server.limits.max_link_hours);
+    let expires = expires_after(hours)?;
+    let connection = req.connection_info();
+    let entries = files
+        .iter()
+        .map(|relative| {
+            let signature = state
+                .signer
+                .sign(&mount_name, &pathbuf_to_string(relative), expires);
+            let url = format!(
+                "{}://{}{}?expires={}&sig={}",
+                connection.scheme(),
+                connection.host(),
+                urls::entry("dl", &mount_name, relative),
+                expires,
+                signature
+            );
+            (url, osname::encode_path(relative))
+        })
+        .collect::<Vec<_>>();
+    log::info!(
+        "{} signed a manifest of {} download URLs on {} valid for {} hours",
+        username,
+        entries.len(),
+        mount_name,
+        hours
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .append_header((
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"lunafinder-urls.txt\"",
+        ))
+        .body(manifest::render(format, &entries)))
+}
+
+async fn signed_download(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<SignedDownloadQuery>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let mount = state
+        .config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    if !state.signer.verify(
+        &mount_name,
+        &pathbuf_to_string(&relative_path),
+        query.expires,
+        &query.sig,
+    ) {
+        return Err(LunaError::forbidden("Invalid or expired download link").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let file_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !file_path.is_file() {
+        return Err(LunaError::not_found("File not found").into());
+    }
+
+    let (response, bytes) =
+        file_response(&state, &req, &mount_name, &file_path).map_err(LunaError::internal)?;
+    record_access(&state, &mount_name, stats::EventKind::Download, None, bytes);
+    Ok(response)
+}
+
+async fn account_shares_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    let shares = state.tokens.list(Some(&username));
+    render_shares(&state, &username, shares, false)
+}
+
+async fn admin_shares_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    if !is_admin(&state.config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+    let username = username.unwrap_or_default();
+    let shares = state.tokens.list(None);
+    render_shares(&state, &username, shares, true)
+}
+
+fn render_shares(
+    state: &AppState,
+    username: &str,
+    shares: Vec<tokens::GuestToken>,
+    all_users: bool,
+) -> ActixResult<HttpResponse> {
+    let mut context = TeraContext::new();
+    context.insert("username", username);
+    context.insert("shares", &shares);
+    context.insert("all_users", &all_users);
+    context.insert("now", &clock::now_unix());
+
+    let html = render_template(state, "shares.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn revoke_share(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    form: web::Form<RevokeShareForm>,
+) -> ActixResult<HttpResponse> {
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    let admin = is_admin(&state.config, Some(&username));
+
+    let guest_tokens = state.tokens.clone();
+    let RevokeShareForm { token, all_users } = form.into_inner();
+    let owner = (!admin).then_some(username);
+    let revoked = run_blocking(&state, move || {
+        guest_tokens.revoke(&token, owner.as_deref())
+    })
+    .await?;
+    if !revoked {
+        return Err(LunaError::not_found("Share link not found").into());
+    }
+
+    let location = if all_users && admin {
+        "/admin/shares"
+    } else {
+        "/account/shares"
+    };
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, location))
+        .finish())
+}
+
+async fn moderation_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        None,
+        &mount_name,
+        Path::new(""),
+    )
+    .ok_or_else(|| LunaError::forbidden("Moderate permission required"))?;
+    if !mount.moderation || !permission.allows_moderate() {
+        return Err(LunaError::forbidden("Moderate permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let pending = run_blocking(&state, move || moderation::list_pending(&base_path)).await?;
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("mount_description", &mount.description);
+    context.insert("pending", &pending);
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+
+    context.insert(
+        "size_units",
+        size_units_for(config, username.as_deref()).as_str(),
+    );
+
+    let html = render_template(&state, "moderate.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn moderation_action(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<ModerationForm>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, action) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        None,
+        &mount_name,
+        Path::new(""),
+    )
+    .ok_or_else(|| LunaError::forbidden("Moderate permission required"))?;
+    if !mount.moderation || !permission.allows_moderate() {
+        return Err(LunaError::forbidden("Moderate permission required").into());
+    }
+
+    let target_relative = normalize_relative_path(&form.target_path)
+        .ok_or_else(|| LunaError::bad_request("Invalid target path"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    match action.as_str() {
+        "approve" => moderation::approve(&base_path, &target_relative),
+        "reject" => moderation::reject(&base_path, &target_relative),
+        _ => return Err(LunaError::not_found("Unknown moderation action").into()),
+    }
+    .map_err(LunaError::bad_request)?;
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, urls::mount("moderate", &mount_name)))
+        .finish())
+}
+
+async fn quarantine_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    if !is_admin(config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let quarantined =
+        run_blocking(&state, move || quarantine::list_quarantined(&base_path)).await?;
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("mount_description", &mount.description);
+    context.insert("quarantined", &quarantined);
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+
+    context.insert(
+        "size_units",
+        size_units_for(config, username.as_deref()).as_str(),
+    );
+
+    let html = render_template(&state, "quarantine.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+fn trash_permission(
+    state: &AppState,
+    username: Option<&str>,
+    mount_name: &str,
+    path: &str,
+) -> Option<Permission> {
+    let relative = normalize_relative_path(path)?;
+    effective_permission(state, username, None, mount_name, &relative)
+        .filter(|permission| permission.allows_delete())
+}
+
+async fn search_mount(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<SearchQuery>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let query = query.into_inner();
+    let start = normalize_relative_path(query.path.as_deref().unwrap_or("."))
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let can_read = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &start,
+    )
+    .is_some_and(|permission| permission.allows_read());
+    if !can_read {
+        return Err(match username {
+            Some(_) => LunaError::forbidden("Read permission required"),
+            None => LunaError::unauthorized("Login required"),
+        }
+        .into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let directory = resolve_path(&base_path, &start).map_err(LunaError::bad_request)?;
+    if !directory.is_dir() {
+        return Err(LunaError::bad_request("Target is not a directory").into());
+    }
+
+    let search_query = query.q.trim().to_string();
+    let content_mode = search_mode(query.mode.as_deref())?;
+    if content_mode && !mount.fulltext {
+        return Err(LunaError::not_found("Content search is not enabled for this mount").into());
+    }
+    let mut results = search::SearchResults::default();
+    let mut content_hits = Vec::new();
+    if content_mode && !search_query.is_empty() {
+        content_hits = content_search(
+            &state,
+            vec![mount_name.clone()],
+            &search_query,
+            &start,
+            username.as_deref(),
+            guest.as_ref(),
+        )
+        .await?;
+    } else if !search_query.is_empty() {
+        let matcher = search::Matcher::new(&search_query);
+        let (max_depth, max_results) = (config.search.max_depth, config.search.max_results);
+        let gitignore = mount.gitignore;
+        let permission_state = state.clone();
+        let permission_user = username.clone();
+        let permission_guest = guest.clone();
+        let permission_mount = mount_name.clone();
+        let search_start = start.clone();
+        results = run_blocking(&state, move || {
+            search::find(
+                &base_path,
+                &search_start,
+                &matcher,
+                max_depth,
+                max_results,
+                gitignore,
+                |relative| {
+                    effective_permission(
+                        &permission_state,
+                        permission_user.as_deref(),
+                        permission_guest.as_ref(),
+                        &permission_mount,
+                        relative,
+                    )
+                    .is_some_and(|permission| permission.allows_read())
+                },
+            )
+        })
+        .await?;
+    }
+
+    let search_path = osname::encode_path(&start);
+    match query.format.as_deref().unwrap_or("html") {
+        "json" if content_mode => Ok(HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(serde_json::json!({
+                "mount": mount_name,
+                "path": search_path,
+                "query": search_query,
+                "mode": "content",
+                "hits": content_hits,
+            }))),
+        "json" => Ok(HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(serde_json::json!({
+                "mount": mount_name,
+                "path": search_path,
+                "query": search_query,
+                "mode": "name",
+                "hits": results.hits,
+                "truncated": results.truncated,
+            }))),
+        "html" => {
+            let mut context = TeraContext::new();
+            context.insert("mount_name", &mount_name);
+            context.insert("mount_description", &mount.description);
+            context.insert("search_query", &search_query);
+            context.insert("search_path", &search_path);
+            context.insert("search_mode", if content_mode { "content" } else { "name" });
+            context.insert("content_search", &(mount.fulltext && fulltext::available()));
+            context.insert("results", &results);
+            context.insert("content_hits", &content_hits);
+            context.insert("token_query", &token_query(guest.as_ref()));
+            context.insert("search_token", &guest.as_ref().map(|guest| &guest.token));
+            if let Some(ref username) = username {
+                context.insert("username", username);
+            }
+            context.insert(
+                "size_units",
+                size_units_for(config, username.as_deref()).as_str(),
+            );
+
+            let html = render_template(&state, "search.html", &mut context)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(html))
+        }
+        other => Err(LunaError::bad_request(format!("Unsupported format: {}", other)).into()),
+    }
+}
+
+async fn search_all(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+) -> ActixResult<HttpResponse> {
+    let config = &state.config;
+    let query = query.into_inner();
+    if !search_mode(query.mode.as_deref().or(Some("content")))? {
+        return Err(
+            LunaError::bad_request("Filename search needs a mount: use /search/{mount}").into(),
+        );
+    }
+
+    let username = get_username_from_cookie(&req);
+    let mut mounts = config
+        .mounts
+        .iter()
+        .filter(|(_, mount)| mount.fulltext)
+        .filter(|(name, _)| {
+            effective_permission(&state, username.as_deref(), None, name, Path::new(""))
+                .is_some_and(|permission| permission.allows_read())
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    mounts.sort();
+    if mounts.is_empty() && username.is_none() {
+        return Err(LunaError::unauthorized("Login required").into());
+    }
+
+    let search_query = query.q.trim().to_string();
+    let content_hits = if search_query.is_empty() {
+        Vec::new()
+    } else {
+        content_search(
+            &state,
+            mounts.clone(),
+            &search_query,
+            Path::new(""),
+            username.as_deref(),
+            None,
+        )
+        .await?
+    };
+
+    match query.format.as_deref().unwrap_or("html") {
+        "json" => Ok(HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(serde_json::json!({
+                "mounts": mounts,
+                "query": search_query,
+                "mode": "content",
+                "hits": content_hits,
+            }))),
+        "html" => {
+            let mut context = TeraContext::new();
+            context.insert("search_query", &search_query);
+            context.insert("search_mode", "content");
+            context.insert("content_search", &fulltext::available());
+            context.insert("results", &search::SearchResults::default());
+            context.insert("content_hits", &content_hits);
+            context.insert("token_query", "");
+            if let Some(ref username) = username {
+                context.insert("username", username);
+            }
+            context.insert(
+                "size_units",
+                size_units_for(config, username.as_deref()).as_str(),
+            );
+
+            let html = render_template(&state, "search.html", &mut context)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(html))
+        }
+        other => Err(LunaError::bad_request(format!("Unsupported format: {}", other)).into()),
+    }
+}
+
+fn search_mode(mode: Option<&str>) -> Result<bool, LunaError> {
+    match mode.unwrap_or("name") {
+        "name" => Ok(false),
+        "content" => Ok(true),
+        other => Err(LunaError::bad_request(format!(
+            "Unsupported search mode: {}",
+            other
+        ))),
+    }
+}
+
+async fn content_search(
+    state: &AppState,
+    mounts: Vec<String>,
+    query: &str,
+    start: &Path,
+    username: Option<&str>,
+    guest: Option<&tokens::GuestToken>,
+) -> ActixResult<Vec<fulltext::ContentHit>> {
+    if !fulltext::available() {
+        return Err(LunaError::not_found("This build does not include content search").into());
+    }
+    let limit = state.config.search.max_results;
+    let index = state.fulltext.clone();
+    let search_query = query.to_string();
+    let hits = run_blocking(state, move || {
+        let mut hits = Vec::new();
+        for mount in &mounts {
+            hits.extend(index.search(mount, &search_query, limit)?);
+        }
+        Ok(hits)
+    })
+    .await?;
+
+    let mut hits = hits
+        .into_iter()
+        .filter(|hit| {
+            let Some(mount) = state.config.mounts.get(&hit.mount) else {
+                return false;
+            };
+            normalize_relative_path(&hit.path).is_some_and(|relative| {
+                relative.starts_with(start)
+                    && mount.path.join(&relative).is_file()
+                    && effective_permission(state, username, guest, &hit.mount, &relative)
+                        .is_some_and(|permission| permission.allows_read())
+            })
+        })
+        .collect::<Vec<_>>();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+async fn image_viewer(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<ListingQuery>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    if thumbs::source_format(&relative_path).is_none() {
+        return Err(LunaError::unsupported_media_type(
+            "Only JPEG, PNG, WebP and GIF images can be viewed",
+        )
+        .into());
+    }
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let can_read = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &relative_path,
+    )
+    .is_some_and(|permission| permission.allows_read());
+    if !can_read {
+        return Ok(HttpResponse::Found()
+            .append_header((
+                header::LOCATION,
+                login_location(config, username.as_deref(), &req),
+            ))
+            .finish());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !target_path.is_file() {
+        return Err(LunaError::not_found("Image not found").into());
+    }
+    let file_name = relative_path
+        .file_name()
+        .map(OsString::from)
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let parent_relative = relative_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+
+    let view = ListingView::from_query(config, &query)?;
+    let listing_path = target_path
+        .parent()
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?
+        .to_path_buf();
+    let listing_relative = parent_relative.clone();
+    let listing_base = mount.gitignore.then(|| base_path.clone());
+    let (sort, descending) = (view.sort, view.descending);
+    let entries = run_blocking(&state, move || {
+        let rules = listing_base.map(|base| gitignore::Rules::load(&base, &listing_relative));
+        collect_entries(
+            &listing_path,
+            &listing_relative,
+            sort,
+            descending,
+            rules.as_ref(),
+        )
+    })
+    .await?;
+    let position = entries
+        .iter()
+        .position(|entry| entry.file_name == file_name)
+        .ok_or_else(|| LunaError::not_found("Image not found"))?;
+    let images = entries
+        .iter()
+        .filter(|entry| entry.thumbnail)
+        .collect::<Vec<_>>();
+    let index = images
+        .iter()
+        .position(|entry| entry.file_name == file_name)
+        .ok_or_else(|| LunaError::not_found("Image not found"))?;
+
+    let viewer_query = ListingView {
+        gallery: false,
+        page: 1,
+        ..view
+    }
+    .href(config, guest.as_ref());
+    let gallery_query = ListingView {
+        gallery: true,
+        page: position / view.per_page + 1,
+        ..view
+    }
+    .href(config, guest.as_ref());
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("target_link", &osname::encode_path(&relative_path));
+    context.insert("parent_path", &osname::encode_path(&parent_relative));
+    context.insert("filename", &file_name.to_string_lossy());
+    context.insert("index", &(index + 1));
+    context.insert("image_count", &images.len());
+    if let Some(previous) = index.checked_sub(1).map(|i| images[i]) {
+        context.insert("previous_link", &previous.link);
+    }
+    if let Some(next) = images.get(index + 1) {
+        context.insert("next_link", &next.link);
+    }
+    context.insert("viewer_query", &viewer_query);
+    context.insert("gallery_query", &gallery_query);
+    context.insert("token_query", &token_query(guest.as_ref()));
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+
+    let html = render_template(&state, "image.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn thumbnail(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<ThumbQuery>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let format = thumbs::source_format(&relative_path).ok_or_else(|| {
+        LunaError::unsupported_media_type(
+            "Thumbnails are available for JPEG, PNG, WebP and GIF images",
+        )
+    })?;
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let can_read = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &relative_path,
+    )
+    .is_some_and(|permission| permission.allows_read());
+    if !can_read {
+        return Err(match (&username, &guest) {
+            (None, None) => LunaError::unauthorized("Login required"),
+            _ => LunaError::forbidden("Read permission required"),
+        }
+        .into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    let metadata = fs::metadata(&target_path)
+        .ok()
+        .filter(fs::Metadata::is_file)
+        .ok_or_else(|| LunaError::not_found("Image not found"))?;
+    if metadata.len() > thumbs::MAX_SOURCE_SIZE {
+        return Err(LunaError::payload_too_large("Image is too large to thumbnail").into());
+    }
+
+    let size = thumbs::size_bucket(query.size);
+    let (version, modified) = file_validators(&metadata);
+    let etag = format!("\"{}-{}\"", version.trim_matches('"'), size);
+    let last_modified = header::HttpDate::from(modified).to_string();
+    if is_not_modified(&req, &etag, modified) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::LAST_MODIFIED, last_modified))
+            .finish());
+    }
+
+    let codec = storage_codec(&state, &mount_name).into_owned();
+    let cache = state.thumbs.clone();
+    let cache_mount = mount_name.clone();
+    let thumbnail = run_blocking(&state, move || {
+        let cacheable = codec.as_codec().key.is_none();
+        if cacheable {
+            if let Some(thumbnail) =
+                cache.cached(&cache_mount, &relative_path, &version, size, format)
+            {
+                return Ok(Some(thumbnail));
+            }
+        }
+        let (reader, _) = storage::open_reader(codec.as_codec(), &target_path)?;
+        let thumbnail = thumbs::render(reader, format, size)?;
+        if let Some(thumbnail) = thumbnail.as_ref().filter(|_| cacheable) {
+            if let Err(e) = cache.store(
+                &cache_mount,
+                &relative_path,
+                &version,
+                size,
+                format,
+                thumbnail,
+            ) {
+                log::warn!("Failed to cache thumbnail: {}", e);
+            }
+        }
+        Ok(thumbnail)
+    })
+    .await?
+    .ok_or_else(|| LunaError::unsupported_media_type("The image could not be decoded"))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(thumbnail.content_type)
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::LAST_MODIFIED, last_modified))
+        .insert_header((header::CACHE_CONTROL, "private, max-age=86400"))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .body(thumbnail.bytes))
+}
+
+async fn model_viewer(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<ModelQuery>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let content_type = model_content_type(&relative_path).ok_or_else(|| {
+        LunaError::unsupported_media_type("Only STL and OBJ models can be previewed")
+    })?;
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let can_read = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &relative_path,
+    )
+    .is_some_and(|permission| permission.allows_read());
+    if !can_read {
+        return Ok(HttpResponse::Found()
+            .append_header((
+                header::LOCATION,
+                login_location(config, username.as_deref(), &req),
+            ))
+            .finish());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !target_path.is_file() {
+        return Err(LunaError::not_found("Model not found").into());
+    }
+
+    let filename = target_path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let target_link = osname::encode_path(&relative_path);
+
+    match query.format.as_deref().unwrap_or("html") {
+        "raw" => {
+            let (mut response, bytes) = file_response(&state, &req, &mount_name, &target_path)
+                .map_err(LunaError::internal)?;
+            if response.status().is_success() {
+                let headers = response.headers_mut();
+                headers.insert(
+                    header::CONTENT_TYPE,
+                    header::HeaderValue::from_static(content_type),
+                );
+                headers.insert(
+                    header::CONTENT_DISPOSITION,
+                    header::HeaderValue::from_static("inline"),
+                );
+                headers.insert(
+                    header::X_CONTENT_TYPE_OPTIONS,
+                    header::HeaderValue::from_static("nosniff"),
+                );
+            }
+            record_access(
+                &state,
+                &mount_name,
+                stats::EventKind::Download,
+                username,
+                bytes,
+            );
+            if let Some(guest) = &guest {
+                if let Err(e) = state.tokens.record_bytes(&guest.token, bytes) {
+                    log::warn!("Failed to record guest token bytes: {}", e);
+                }
+            }
+            Ok(response)
+        }
+        "html" => {
+            let parent_path = relative_path
+                .parent()
+                .map(osname::encode_path)
+                .unwrap_or_else(|| ".".to_string());
+            let size = fs::metadata(&target_path)
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+
+            let mut context = TeraContext::new();
+            context.insert("mount_name", &mount_name);
+            context.insert("target_link", &target_link);
+            context.insert("parent_path", &parent_path);
+            context.insert("filename", &filename);
+            context.insert("file_size", &size);
+            context.insert(
+                "model_kind",
+                if content_type == "model/stl" {
+                    "stl"
+                } else {
+                    "obj"
+                },
+            );
+            context.insert("token_query", &token_query(guest.as_ref()));
+            if let Some(ref username) = username {
+                context.insert("username", username);
+            }
+            context.insert(
+                "size_units",
+                size_units_for(config, username.as_deref()).as_str(),
+            );
+
+            let html = render_template(&state, "model.html", &mut context)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(html))
+        }
+        other => Err(LunaError::bad_request(format!("Unsupported format: {}", other)).into()),
+    }
+}
+
+fn model_content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "stl" => Some("model/stl"),
+        "obj" => Some("model/obj"),
+        _ => None,
+    }
+}
+
+async fn releases_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .filter(|mount| mount.releases.enabled)
+        .ok_or_else(|| LunaError::not_found("Releases not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    let can_read = |relative: &Path| {
+        effective_permission(&state, username.as_deref(), None, &mount_name, relative)
+            .is_some_and(|permission| permission.allows_read())
+    };
+    if !can_read(Path::new("")) {
+        return Ok(HttpResponse::Found()
+            .append_header((
+                header::LOCATION,
+                login_location(config, username.as_deref(), &req),
+            ))
+            .finish());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let codec = storage_codec(&state, &mount_name).into_owned();
+    let hash_cache = state.hash_cache.clone();
+    let versions = mount.releases.versions;
+    let mut releases = run_blocking(&state, move || {
+        releases::collect(&base_path, codec.as_codec(), &hash_cache, versions)
+    })
+    .await?;
+
+    let expires = clock::now_unix() + mount.releases.link_hours * 3600;
+    for release in &mut releases {
+        release.files.retain_mut(|file| {
+            let Some(relative) = normalize_relative_path(&file.path) else {
+                return false;
+            };
+            if !can_read(&relative) {
+                return false;
+            }
+            let signature = state
+                .signer
+                .sign(&mount_name, &pathbuf_to_string(&relative), expires);
+            file.url = format!(
+                "{}?expires={}&sig={}",
+                urls::entry("dl", &mount_name, &relative),
+                expires,
+                signature
+            );
+            true
+        });
+    }
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("mount_description", &mount.description);
+    context.insert("releases", &releases);
+    context.insert("link_hours", &mount.releases.link_hours);
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+    context.insert(
+        "size_units",
+        size_units_for(config, username.as_deref()).as_str(),
+    );
+
+    let html = render_template(&state, "releases.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn trash_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .filter(|mount| mount.trash)
+        .ok_or_else(|| LunaError::not_found("Trash not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    let admin = is_admin(config, username.as_deref());
+    if !admin && trash_permission(&state, username.as_deref(), &mount_name, "").is_none() {
+        return Err(LunaError::forbidden("Delete permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let entries: Vec<trash::TrashedEntry> = run_blocking(&state, move || trash::list(&base_path))
+        .await?
+        .into_iter()
+        .filter(|entry| {
+            admin
+                || trash_permission(&state, username.as_deref(), &mount_name, &entry.path).is_some()
+        })
+        .collect();
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("mount_description", &mount.description);
+    context.insert("entries", &entries);
+    context.insert("can_empty", &admin);
+    context.insert("writable", &!state.maintenance.load(Ordering::Relaxed));
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+    context.insert(
+        "size_units",
+        size_units_for(config, username.as_deref()).as_str(),
+    );
+
+    let html = render_template(&state, "trash.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn trash_action(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<TrashForm>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, action) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .filter(|mount| mount.trash)
+        .ok_or_else(|| LunaError::not_found("Trash not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+
+    if action == "empty" {
+        if !is_admin(config, username.as_deref()) {
+            return Err(LunaError::forbidden("Admin permission required").into());
+        }
+        let trash_base = base_path.clone();
+        run_blocking(&state, move || trash::empty(&trash_base)).await?;
+        schedule_blob_gc(&state, mount, &base_path);
+        return Ok(HttpResponse::Found()
+            .append_header((header::LOCATION, urls::mount("trash", &mount_name)))
+            .finish());
+    }
+
+    let entry = trash::get(&base_path, &form.id).map_err(LunaError::not_found)?;
+    let relative = normalize_relative_path(&entry.path)
+        .ok_or_else(|| LunaError::bad_request("Invalid target path"))?;
+    if !is_admin(config, username.as_deref())
+        && trash_permission(&state, username.as_deref(), &mount_name, &entry.path).is_none()
+    {
+        return Err(LunaError::forbidden("Delete permission required").into());
+    }
+
+    match action.as_str() {
+        "restore" => {
+            let destination =
+                resolve_path(&base_path, &relative).map_err(LunaError::bad_request)?;
+            if fs::symlink_metadata(&destination).is_ok() {
+                return Err(LunaError::conflict("The original location is taken").into());
+            }
+            let trash_base = base_path.clone();
+            run_blocking(&state, move || {
+                trash::restore(&trash_base, &entry, &destination)
+            })
+            .await?;
+            publish_file_event(
+                &state,
+                &mount_name,
+                "upload",
+                &relative,
+                username.as_deref(),
+            );
+        }
+        "delete" => {
+            let trash_base = base_path.clone();
+            run_blocking(&state, move || trash::purge(&trash_base, &entry)).await?;
+            schedule_blob_gc(&state, mount, &base_path);
+        }
+        _ => return Err(LunaError::not_found("Unknown trash action").into()),
+    }
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, urls::mount("trash", &mount_name)))
+        .finish())
+}
+
+async fn duplicates_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    if !is_admin(config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let report = run_blocking(&state, move || duplicates::find(&base_path)).await?;
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("mount_description", &mount.description);
+    context.insert("report", &report);
+    context.insert("writable", &!state.maintenance.load(Ordering::Relaxed));
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+
+    context.insert(
+        "size_units",
+        size_units_for(config, username.as_deref()).as_str(),
+    );
+
+    let html = render_template(&state, "duplicates.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn hardlink_duplicates(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<Vec<(String, String)>>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    if !is_admin(config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let mut keep = None;
+    let mut paths = Vec::new();
+    for (key, value) in form.into_inner() {
+        let path = normalize_relative_path(value.trim())
+            .filter(|path| !path.as_os_str().is_empty())
+            .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+        match key.as_str() {
+            "keep" => keep = Some(path),
+            "path" => paths.push(path),
+            _ => {}
+        }
+    }
+    let keep = keep.ok_or_else(|| LunaError::bad_request("Missing file to keep"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let linked = run_blocking(&state, move || {
+        duplicates::hardlink(&base_path, &keep, &paths)
+    })
+    .await?;
+    log::info!(
+        "Replaced {} duplicate(s) in mount '{}' with hardlinks",
+        linked,
+        mount_name
+    );
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, urls::mount("duplicates", &mount_name)))
+        .finish())
+}
+
+async fn health_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    if !is_admin(config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let issues = run_blocking(&state, move || health::scan(&base_path)).await?;
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("mount_description", &mount.description);
+    context.insert("issues", &issues);
+    context.insert("writable", &!state.maintenance.load(Ordering::Relaxed));
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+
+    let html = render_template(&state, "health.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn health_cleanup(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<Vec<(String, String)>>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    if !is_admin(config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let paths = form
+        .into_inner()
+        .into_iter()
+        .filter(|(key, _)| key == "path")
+        .map(|(_, value)| {
+            normalize_relative_path(value.trim())
+                .filter(|path| !path.as_os_str().is_empty())
+                .ok_or_else(|| LunaError::bad_request("Invalid path"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let removed = run_blocking(&state, move || health::cleanup(&base_path, &paths)).await?;
+    log::info!(
+        "Removed {} leftover entries from mount '{}'",
+        removed,
+        mount_name
+    );
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, urls::mount("health", &mount_name)))
+        .finish())
+}
+
+async fn checksums_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    if !is_admin(config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let codec = storage_codec(&state, &mount_name).into_owned();
+    let report = run_blocking(&state, move || {
+        checksums::verify(&base_path, codec.as_codec())
+    })
+    .await?;
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("mount_description", &mount.description);
+    context.insert("report", &report);
+    context.insert("auto_generate", &mount.checksums);
+    context.insert("writable", &!state.maintenance.load(Ordering::Relaxed));
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+
+    let html = render_template(&state, "checksums.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn checksums_refresh(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let mount_name = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    if !is_admin(config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let codec = storage_codec(&state, &mount_name).into_owned();
+    let updated = run_blocking(&state, move || {
+        checksums::refresh(&base_path, codec.as_codec())
+    })
+    .await?;
+    log::info!(
+        "Refreshed {} checksum files on mount '{}'",
+        updated,
+        mount_name
+    );
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, urls::mount("checksums", &mount_name)))
+        .finish())
+}
+
+async fn quarantine_action(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<ModerationForm>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, action) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = get_username_from_cookie(&req);
+    if !is_admin(config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let target_relative = normalize_relative_path(&form.target_path)
+        .ok_or_else(|| LunaError::bad_request("Invalid target path"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    match action.as_str() {
+        "release" => quarantine::release(&base_path, &target_relative),
+        "delete" => quarantine::delete(&base_path, &target_relative),
+        _ => return Err(LunaError::not_found("Unknown quarantine action").into()),
+    }
+    .map_err(LunaError::bad_request)?;
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, urls::mount("quarantine", &mount_name)))
+        .finish())
+}
+
+async fn edit_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    let username = get_username_from_cookie(&req);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        None,
+        &mount_name,
+        &relative_path,
+    )
+    .ok_or_else(|| LunaError::forbidden("Permission required"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    let creating = check_edit_target(mount, &permission, &relative_path, &target_path)?;
+
+    let mut content = String::new();
+    if creating {
+        let title = relative_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        content = format!("# {}\n\n", title);
+    } else {
+        storage::open_reader(storage_codec(&state, &mount_name), &target_path)
+            .and_then(|(mut reader, _)| Ok(reader.read_to_string(&mut content)?))
+            .map_err(LunaError::internal)?;
+    }
+
+    let parent_path = relative_path
+        .parent()
+        .map(osname::encode_path)
+        .unwrap_or_else(|| ".".to_string());
+
+    let filename = target_path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("target_path", &pathbuf_to_string(&relative_path));
+    context.insert("target_link", &osname::encode_path(&relative_path));
+    context.insert("parent_path", &parent_path);
+    context.insert("filename", &filename);
+    context.insert("content", &content);
+    context.insert(
+        "base_version",
+        &content_version(if creating { "" } else { &content }),
+    );
+    context.insert("creating", &creating);
+    if !creating && content.len() as u64 <= config.display.max_highlight_size {
+        let highlighter = state.highlighter.clone();
+        let (file_path, source) = (relative_path.clone(), content.clone());
+        if let Some(highlighted) = run_blocking(&state, move || {
+            Ok(highlighter.highlight(&file_path, &source))
+        })
+        .await?
+        {
+            context.insert("highlighted", &highlighted);
+        }
+    }
+
+    let html = render_template(&state, "edit.html", &mut context)?;
+
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+async fn preview_page(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    let username = get_username_from_cookie(&req);
+    let guest = guest_token(&state, &req, &mount_name);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        guest.as_ref(),
+        &mount_name,
+        &relative_path,
+    )
+    .filter(Permission::allows_read);
+    let Some(permission) = permission else {
+        return Ok(HttpResponse::Found()
+            .append_header((
+                header::LOCATION,
+                login_location(config, username.as_deref(), &req),
+            ))
+            .finish());
+    };
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !target_path.is_file() {
+        return Err(LunaError::not_found("File not found").into());
+    }
+    if fs::metadata(&target_path)
+        .map_err(LunaError::internal)?
+        .len()
+        > MAX_PREVIEW_SIZE
+    {
+        return Err(LunaError::payload_too_large("File is too large to preview").into());
+    }
+
+    let max_highlight_size = config.display.max_highlight_size;
+    let codec = storage_codec(&state, &mount_name).into_owned();
+    let highlighter = state.highlighter.clone();
+    let (source, file_path) = (target_path.clone(), relative_path.clone());
+    let (content, highlighted) = run_blocking(&state, move || {
+        let mut bytes = Vec::new();
+        storage::open_reader(codec.as_codec(), &source)
+            .and_then(|(mut reader, _)| Ok(reader.read_to_end(&mut bytes)?))?;
+        let content = String::from_utf8(bytes).ok();
+        let highlighted = content
+            .as_deref()
+            .filter(|content| content.len() as u64 <= max_highlight_size)
+            .and_then(|content| highlighter.highlight(&file_path, content));
+        Ok((content, highlighted))
+    })
+    .await?;
+    let content = content
+        .ok_or_else(|| LunaError::unsupported_media_type("Only text files can be previewed"))?;
+
+    let parent_path = relative_path
+        .parent()
+        .map(osname::encode_path)
+        .unwrap_or_else(|| ".".to_string());
+    let filename = target_path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut context = TeraContext::new();
+    context.insert("mount_name", &mount_name);
+    context.insert("target_path", &pathbuf_to_string(&relative_path));
+    context.insert("target_link", &osname::encode_path(&relative_path));
+    context.insert("parent_path", &parent_path);
+    context.insert("filename", &filename);
+    context.insert("file_size", &content.len());
+    context.insert("line_count", &content.lines().count());
+    match highlighted {
+        Some(highlighted) => context.insert("highlighted", &highlighted),
+        None => context.insert("content", &content),
+    }
+    context.insert(
+        "too_large_to_highlight",
+        &(content.len() as u64 > max_highlight_size),
+    );
+    context.insert(
+        "can_edit",
+        &(guest.is_none()
+            && !state.maintenance.load(Ordering::Relaxed)
+            && permission.allows_modify()),
+    );
+    context.insert("token_query", &token_query(guest.as_ref()));
+    if let Some(ref username) = username {
+        context.insert("username", username);
+    }
+    context.insert(
+        "size_units",
+        size_units_for(config, username.as_deref()).as_str(),
+    );
+
+    let html = render_template(&state, "preview.html", &mut context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+fn content_version(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+fn diff_lines(current: &str, submitted: &str) -> Vec<DiffLine> {
+    similar::TextDiff::from_lines(current, submitted)
+        .unified_diff()
+        .context_radius(3)
+        .header("현재 파일", "내 변경 사항")
+        .to_string()
+        .lines()
+        .map(|line| DiffLine {
+            kind: match line.chars().next() {
+                Some('+') if !line.starts_with("+++") => "insert",
+                Some('-') if !line.starts_with("---") => "delete",
+                Some('@') | Some('+') | Some('-') => "hunk",
+                _ => "equal",
+            },
+            text: line.to_string(),
+        })
+        .collect()
+}
+
+async fn edit_save(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    form: web::Form<EditForm>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+
+    let (mount_name, tail) = path.into_inner();
+    let config = &state.config;
+    let mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let relative_path =
+        normalize_relative_path(&tail).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+    let username = get_username_from_cookie(&req);
+    let permission = effective_permission(
+        &state,
+        username.as_deref(),
+        None,
+        &mount_name,
+        &relative_path,
+    )
+    .ok_or_else(|| LunaError::forbidden("Write permission required"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    let creating = check_edit_target(mount, &permission, &relative_path, &target_path)?;
+
+    let form = form.into_inner();
+    if let Some(base_version) = form
+        .base_version
+        .as_deref()
+        .filter(|v| !creating && !v.is_empty())
+    {
+        let mut current = String::new();
+        storage::open_reader(storage_codec(&state, &mount_name), &target_path)
+            .and_then(|(mut reader, _)| Ok(reader.read_to_string(&mut current)?))
+            .map_err(LunaError::internal)?;
+        let current_version = content_version(&current);
+        if current_version != base_version {
+            log::info!(
+                "Rejected a stale edit of {}/{}",
+                mount_name,
+                pathbuf_to_string(&relative_path)
+            );
+            let filename = target_path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut context = TeraContext::new();
+            context.insert("mount_name", &mount_name);
+            context.insert("target_path", &pathbuf_to_string(&relative_path));
+            context.insert("target_link", &osname::encode_path(&relative_path));
+            context.insert(
+                "parent_path",
+                &relative_path
+                    .parent()
+                    .map(osname::encode_path)
+                    .unwrap_or_else(|| ".".to_string()),
+            );
+            context.insert("filename", &filename);
+            context.insert("content", &form.content);
+            context.insert("base_version", &current_version);
+            context.insert("diff", &diff_lines(&current, &form.content));
+
+            let html = render_template(&state, "edit.html", &mut context)?;
+            return Ok(HttpResponse::Conflict()
+                .content_type("text/html")
+                .body(html));
+        }
+    }
+
+    let mut file = storage::FileWriter::create(storage_codec(&state, &mount_name), &target_path)
+        .map_err(LunaError::internal)?;
+    file.write_all(form.content.as_bytes())
+        .and_then(|_| file.finish())
+        .map_err(LunaError::internal)?;
+    store_deduplicated(&state, mount, &base_path, &target_path).await;
+    write_checksum(&state, &mount_name, mount, &base_path, &target_path).await;
+    schedule_blob_gc(&state, mount, &base_path);
+    publish_file_event(
+        &state,
+        &mount_name,
+        if creating { "upload" } else { "modify" },
+        &relative_path,
+        username.as_deref(),
+    );
+
+    let location = if mount.wiki && wiki::is_page(&relative_path) {
+        relative_path.as_path()
+    } else {
+        relative_path.parent().unwrap_or(Path::new(""))
+    };
+
+    Ok(HttpResponse::Found()
+        .append_header((
+            header::LOCATION,
+            urls::entry("browse", &mount_name, location),
+        ))
+        .finish())
+}
+
+fn check_edit_target(
+    mount: &MountConfig,
+    permission: &Permission,
+    relative_path: &Path,
+    target_path: &Path,
+) -> Result<bool, LunaError> {
+    let creating =
+        mount.wiki && wiki::is_page(relative_path) && fs::symlink_metadata(target_path).is_err();
+    if !creating {
+        if !permission.allows_modify() {
+            return Err(LunaError::forbidden("Modify permission required"));
+        }
+        if !target_path.is_file() {
+            return Err(LunaError::bad_request("Target is not a file"));
+        }
+        return Ok(false);
+    }
+    if !permission.allows_upload() {
+        return Err(LunaError::forbidden("Write permission required"));
+    }
+    if mount.moderation && !permission.allows_moderate() {
+        return Err(LunaError::forbidden(
+            "New pages on a moderated mount must be uploaded for review",
+        ));
+    }
+    if !target_path.parent().is_some_and(Path::is_dir) {
+        return Err(LunaError::conflict("Parent folder does not exist"));
+    }
+    Ok(true)
+}
+
+async fn update_pins(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    form: web::Form<PinForm>,
+) -> ActixResult<HttpResponse> {
+    let username =
+        get_username_from_cookie(&req).ok_or_else(|| LunaError::unauthorized("Login required"))?;
+    if !state.config.mounts.contains_key(&form.mount) {
+        return Err(LunaError::not_found("Mount not found").into());
+    }
+    let change = match form.action.as_str() {
+        "pin" => pins::Change::Pin,
+        "unpin" => pins::Change::Unpin,
+        "up" => pins::Change::Up,
+        "down" => pins::Change::Down,
+        _ => return Err(LunaError::bad_request("Unknown action").into()),
+    };
+    state
+        .pins
+        .apply(&username, &form.mount, change)
+        .map_err(LunaError::internal)?;
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, "/"))
+        .finish())
+}
+
+async fn set_maintenance(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    form: web::Form<MaintenanceForm>,
+) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    if !is_admin(&state.config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    state.maintenance.store(form.enabled, Ordering::Relaxed);
+    log::info!(
+        "Maintenance mode {} by {}",
+        if form.enabled { "enabled" } else { "disabled" },
+        username.as_deref().unwrap_or("unknown")
+    );
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, "/"))
+        .finish())
+}
+
+async fn set_account_disabled(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    form: web::Form<AccountForm>,
+) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    if !is_admin(&state.config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+    if !state.config.users.contains_key(&form.username) {
+        return Err(LunaError::not_found("User not found").into());
+    }
+    if form.disabled && username.as_deref() == Some(form.username.as_str()) {
+        return Err(LunaError::bad_request("You cannot disable your own account").into());
+    }
+
+    state
+        .accounts
+        .set_disabled(&form.username, form.disabled)
+        .map_err(LunaError::internal)?;
+    log::info!(
+        "Account {} {} by {}",
+        form.username,
+        if form.disabled { "disabled" } else { "enabled" },
+        username.as_deref().unwrap_or("unknown")
+    );
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, "/"))
+        .finish())
+}
+
+async fn start_view_as(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    form: web::Form<ViewAsForm>,
+) -> ActixResult<HttpResponse> {
+    let admin = session_username(&req)
+        .filter(|admin| is_admin(&state.config, Some(admin)))
+        .ok_or_else(|| LunaError::forbidden("Admin permission required"))?;
+    if !state.config.users.contains_key(&form.username) {
+        return Err(LunaError::not_found("User not found").into());
+    }
+
+    let expires = clock::now_unix() + VIEW_AS_TTL_SECS;
+    let signature = state
+        .signer
+        .sign("view-as", &view_as_message(&admin, &form.username), expires);
+    let cookie = Cookie::build(
+        VIEW_AS_COOKIE,
+        format!(
+            "{}.{}.{}",
+            urlencoding::encode(&form.username),
+            expires,
+            signature
+        ),
+    )
+    .http_only(true)
+    .path("/")
+    .max_age(Duration::seconds(VIEW_AS_TTL_SECS as i64))
+    .finish();
+    log::info!(
+        target: "security",
+        "{} started viewing as {}",
+        admin,
+        form.username
+    );
+
+    let mut response = HttpResponse::Found()
+        .append_header((header::LOCATION, "/"))
+        .finish();
+    response.add_cookie(&cookie).map_err(LunaError::internal)?;
+    Ok(response)
+}
+
+async fn stop_view_as(req: HttpRequest) -> ActixResult<HttpResponse> {
+    if let Some((admin, target)) = view_as(&req) {
+        log::info!(target: "security", "{} stopped viewing as {}", admin, target);
+    }
+    let mut response = HttpResponse::Found()
+        .append_header((header::LOCATION, "/"))
+        .finish();
+    let cookie = Cookie::build(VIEW_AS_COOKIE, "")
+        .path("/")
+        .max_age(Duration::seconds(0))
+        .finish();
+    response.add_cookie(&cookie).map_err(LunaError::internal)?;
+    Ok(response)
+}
+
+async fn set_announcement(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    form: web::Form<AnnouncementForm>,
+) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    if !is_admin(&state.config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let form = form.into_inner();
+    let message = form.message.trim().to_string();
+    let announcement = if message.is_empty() {
+        None
+    } else {
+        Some(ActiveAnnouncement {
+            message,
+            severity: form.severity,
+            expires_at: form
+                .expires_in_hours
+                .and_then(|hours| hours.trim().parse::<u64>().ok())
+                .map(expires_after)
+                .transpose()?,
+        })
+    };
+
+    *state
+        .announcement
+        .write()
+        .map_err(|_| LunaError::internal("Announcement lock poisoned"))? = announcement;
+
+    Ok(HttpResponse::Found()
+        .append_header((header::LOCATION, "/"))
+        .finish())
+}
+
+async fn export_stats(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<StatsQuery>,
+) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    if !is_admin(&state.config, username.as_deref()) {
+        return Err(LunaError::forbidden("Admin permission required").into());
+    }
+
+    let query = query.into_inner();
+    let parse_bound = |value: Option<String>| -> Result<Option<u64>, LunaError> {
+        match value
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+        {
+            Some(value) => clock::parse_date(value)
+                .map(Some)
+                .ok_or_else(|| LunaError::bad_request(format!("Invalid date: {}", value))),
+            None => Ok(None),
+        }
+    };
+    let from = parse_bound(query.from)?;
+    let until = parse_bound(query.to)?.map(|to| to + 86_400);
+
+    let stats_log = state.stats.clone();
+    let summary = run_blocking(&state, move || stats_log.summarize(from, until)).await?;
+
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => Ok(HttpResponse::Ok().json(summary)),
+        "csv" => Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .append_header((
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"lunafinder-stats.csv\"",
+            ))
+            .body(stats::to_csv(&summary))),
+        other => Err(LunaError::bad_request(format!("Unsupported format: {}", other)).into()),
+    }
+}
+
+async fn sync_manifest(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let mount = readable_mount(&state, &req, &mount_name)?;
+
+    let cursor = state.journal.cursor();
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let hash_cache = state.hash_cache.clone();
+    let entries = run_blocking(&state, move || sync::manifest(&base_path, &hash_cache)).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "mount": mount_name,
+        "cursor": cursor,
+        "entries": entries,
+    })))
+}
+
+async fn sync_changes(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ChangesQuery>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    readable_mount(&state, &req, &mount_name)?;
+
+    let body = match state.journal.since(&mount_name, &query.cursor) {
+        Some((changes, cursor)) => serde_json::json!({
+            "reset": false,
+            "cursor": cursor,
+            "changes": changes,
+        }),
+        None => serde_json::json!({
+            "reset": true,
+            "cursor": state.journal.cursor(),
+            "changes": [],
+        }),
+    };
+    Ok(HttpResponse::Ok().json(body))
+}
+
+async fn long_poll_changes(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<LongPollQuery>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    readable_mount(&state, &req, &mount_name)?;
+
+    let Some(since) = query.since.as_deref().filter(|since| !since.is_empty()) else {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "reset": false,
+            "cursor": state.journal.cursor(),
+            "changes": [],
+        })));
+    };
+    let timeout = query
+        .timeout
+        .unwrap_or(DEFAULT_LONG_POLL_SECS)
+        .min(MAX_LONG_POLL_SECS);
+    let body = match state
+        .journal
+        .wait_since(&mount_name, since, StdDuration::from_secs(timeout))
+        .await
+    {
+        Some((changes, cursor)) => serde_json::json!({
+            "reset": false,
+            "cursor": cursor,
+            "changes": changes,
+        }),
+        None => serde_json::json!({
+            "reset": true,
+            "cursor": state.journal.cursor(),
+            "changes": [],
+        }),
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(body))
+}
+
+async fn api_list(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ListQuery>,
+) -> ActixResult<HttpResponse> {
+    let mount_name = path.into_inner();
+    let mount = state
+        .config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    let relative_path = normalize_relative_path(query.path.as_deref().unwrap_or_default())
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let username = get_username_from_cookie(&req);
+    effective_permission(
+        &state,
+        username.as_deref(),
+        None,
+        &mount_name,
+        &relative_path,
+    )
+    .filter(|permission| permission.allows_read())
+    .ok_or_else(|| LunaError::forbidden("Read permission required"))?;
+
+    let base_path = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let target_path = resolve_path(&base_path, &relative_path).map_err(LunaError::bad_request)?;
+    if !target_path.is_dir() {
+        return Err(LunaError::not_found("Directory not found").into());
+    }
+
+    let limit = query
+        .limit
+        .unwrap_or(listing::DEFAULT_PAGE_LIMIT)
+        .clamp(1, listing::MAX_PAGE_LIMIT);
+    let cursor = query.cursor.as_deref().filter(|cursor| !cursor.is_empty());
+    let listing_base = mount.gitignore.then(|| base_path.clone());
+    let entries = run_blocking(&state, move || {
+        let rules = listing_base.map(|base| gitignore::Rules::load(&base, &relative_path));
+        listing::read(&target_path, rules.as_ref())
+    })
+    .await?;
+    let etag = listing::etag(&entries, cursor, limit);
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .finish());
+    }
+
+    let page = listing::page(entries, cursor, limit)
+        .ok_or_else(|| LunaError::bad_request("Invalid cursor"))?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, "private, no-cache"))
+        .json(page))
+}
+
+fn api_target<'a>(
+    state: &'a AppState,
+    req: &HttpRequest,
+    mount_name: String,
+    path: &str,
+) -> Result<DavTarget<'a>, LunaError> {
+    let mount = state
+        .config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    let relative =
+        normalize_relative_path(path).ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let username = get_username_from_cookie(req);
+    let permission = effective_permission(state, username.as_deref(), None, &mount_name, &relative)
+        .filter(Permission::allows_read)
+        .ok_or_else(|| match username {
+            Some(_) => LunaError::forbidden("Read permission required"),
+            None => LunaError::unauthorized("Login required"),
+        })?;
+    let base = canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let path = resolve_path(&base, &relative).map_err(LunaError::bad_request)?;
+    Ok(DavTarget {
+        mount_name,
+        mount,
+        base,
+        relative,
+        path,
+        permission,
+        username,
+    })
+}
+
+async fn api_stat(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ApiPathQuery>,
+) -> ActixResult<HttpResponse> {
+    let target = api_target(
+        &state,
+        &req,
+        path.into_inner(),
+        query.path.as_deref().unwrap_or_default(),
+    )?;
+    let metadata =
+        fs::metadata(&target.path).map_err(|_| LunaError::not_found("Path not found"))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs());
+    Ok(HttpResponse::Ok()
+        .append_header((header::CACHE_CONTROL, "private, no-cache"))
+        .json(serde_json::json!({
+            "mount": target.mount_name,
+            "path": osname::encode_path(&target.relative),
+            "name": target
+                .relative
+                .file_name()
+                .map(|name| osname::encode_path(Path::new(name))),
+            "dir": metadata.is_dir(),
+            "size": if metadata.is_dir() { 0 } else { metadata.len() },
+            "mtime": modified,
+            "permissions": target.permission.actions(),
+            "tags": state
+                .tags
+                .get(&target.mount_name, &osname::encode_path(&target.relative)),
+        })))
+}
+
+async fn api_download(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ApiPathQuery>,
+) -> ActixResult<HttpResponse> {
+    let target = api_target(
+        &state,
+        &req,
+        path.into_inner(),
+        query.path.as_deref().unwrap_or_default(),
+    )?;
+    if target.path.is_dir() {
+        return Err(LunaError::bad_request("Path is a directory").into());
+    }
+    webdav::get(&state, &req, target)
+}
+
+async fn api_upload_multipart(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    mut payload: Multipart,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+    let mount_name = path.into_inner();
+    let limits = &state.config.server.limits;
+    let mut metadata = UploadMetadata::default();
+    let mut stored = Vec::new();
+    let mut total_bytes: u64 = 0;
+
+    while let Some(mut field) = payload.try_next().await.map_err(LunaError::internal)? {
+        let disposition = field.content_disposition();
+        let field_name = disposition
+            .and_then(|cd| cd.get_name())
+            .unwrap_or_default()
+            .to_string();
+        let Some(filename) = disposition
+            .and_then(|cd| cd.get_filename())
+            .map(str::to_string)
+        else {
+            if field_name == "metadata" {
+                if !stored.is_empty() {
+                    return Err(
+                        LunaError::bad_request("The metadata part must precede the files").into(),
+                    );
+                }
+                let mut value = Vec::new();
+                while let Some(chunk) = field.try_next().await.map_err(LunaError::internal)? {
+                    value.extend_from_slice(&chunk);
+                    if value.len() > MAX_UPLOAD_METADATA_LEN {
+                        return Err(LunaError::bad_request("Upload metadata is too large").into());
+                    }
+                }
+                metadata = serde_json::from_slice(&value).map_err(|e| {
+                    LunaError::bad_request(format!("Invalid upload metadata: {}", e))
+                })?;
+            }
+            continue;
+        };
+
+        let options = metadata.files.remove(&field_name).unwrap_or_default();
+        let name = sanitize_file_name(options.name.as_deref().unwrap_or(&filename))
+            .ok_or_else(|| LunaError::bad_request("Invalid file name"))?;
+        let file_tags = tags::normalize(&[metadata.tags.clone(), options.tags].concat())
+            .ok_or_else(|| LunaError::bad_request("Invalid tags"))?;
+        let expected = options
+            .sha256
+            .map(|hash| hash.trim().to_ascii_lowercase())
+            .filter(|hash| !hash.is_empty());
+        if expected
+            .as_ref()
+            .is_some_and(|hash| hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            return Err(LunaError::bad_request("Invalid sha256").into());
+        }
+        let directory = normalize_relative_path(&metadata.path)
+            .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+
+        let mut target = api_target(
+            &state,
+            &req,
+            mount_name.clone(),
+            &osname::encode_path(&directory.join(&name)),
+        )?;
+        if metadata.overwrite == OverwritePolicy::Rename && target.path.exists() {
+            let parent = target
+                .path
+                .parent()
+                .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+            let renamed = unclaimed_path(parent, &name);
+            let renamed = renamed
+                .file_name()
+                .ok_or_else(|| LunaError::bad_request("Invalid file name"))?;
+            target = api_target(
+                &state,
+                &req,
+                mount_name.clone(),
+                &osname::encode_path(&directory.join(renamed)),
+            )?;
+        }
+        let moderated =
+            check_upload_destination(&target, metadata.overwrite == OverwritePolicy::Replace)?;
+        let replaced = target.path.is_file();
+        let destination = if moderated {
+            moderation::queue_root(&target.base).join(&target.relative)
+        } else {
+            target.path.clone()
+        };
+        let parent = destination
+            .parent()
+            .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+        if moderated {
+            fs::create_dir_all(parent).map_err(LunaError::internal)?;
+        }
+
+        let temp_path = staging_path(&state, parent, "upload");
+        let max_upload_size =
+            max_upload_size(&state.config, target.mount, target.username.as_deref());
+        let mut file = storage::QueuedWriter::spawn(
+            storage::FileWriter::create(
+                staged_codec(&state, &target.mount_name, &destination),
+                &temp_path,
+            )
+            .map_err(LunaError::internal)?,
+            state.config.server.uploads.queue_chunks,
+        );
+        let mut hasher = Sha256::new();
+        let mut head = Vec::new();
+        let mut written: u64 = 0;
+        let result: ActixResult<()> = async {
+            while let Some(chunk) = field.try_next().await.map_err(LunaError::internal)? {
+                written += chunk.len() as u64;
+                total_bytes += chunk.len() as u64;
+                check_upload_limits(limits, written, total_bytes)?;
+                check_upload_size(
+                    &state.config,
+                    max_upload_size,
+                    total_bytes,
+                    target.username.as_deref(),
+                )?;
+                if head.len() < content::SNIFF_LEN {
+                    let take = (content::SNIFF_LEN - head.len()).min(chunk.len());
+                    head.extend_from_slice(&chunk[..take]);
+                }
+                hasher.update(&chunk);
+                file.write(chunk).await.map_err(LunaError::internal)?;
+            }
+            if let Err(category) = content::check(
+                &target.mount.allow_content,
+                &target.mount.deny_content,
+                &head,
+            ) {
+                return Err(LunaError::unsupported_media_type(format!(
+                    "Uploading {} content to this mount is not allowed",
+                    category
+                ))
+                .into());
+            }
+            Ok(())
+        }
+        .await;
+        let result = match result {
+            Ok(()) => file
+                .finish()
+                .await
+                .map_err(|e| LunaError::internal(e).into()),
+            Err(e) => Err(e),
+        };
+        let actual = format!("{:x}", hasher.finalize());
+        let result = result.and_then(|_| match &expected {
+            Some(expected) if *expected != actual => Err(LunaError::bad_request(format!(
+                "SHA-256 mismatch for {}: expected {}, received {}",
+                name, expected, actual
+            ))
+            .into()),
+            _ => storage::persist(&temp_path, &destination)
+                .map_err(|e| LunaError::internal(e).into()),
+        });
+        if let Err(e) = result {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+
+        finish_stored_upload(&state, &target, &destination, written).await?;
+        let stored_path = osname::encode_path(&target.relative);
+        if let Err(e) = state
+            .tags
+            .set(&target.mount_name, &stored_path, file_tags.clone())
+        {
+            log::warn!("Failed to save tags for {}: {}", stored_path, e);
+        }
+        stored.push(serde_json::json!({
+            "field": field_name,
+            "path": stored_path,
+            "size": written,
+            "sha256": actual,
+            "tags": file_tags,
+            "replaced": replaced,
+            "moderated": moderated,
+        }));
+    }
+
+    if stored.is_empty() {
+        return Err(LunaError::bad_request("No files were uploaded").into());
+    }
+    Ok(HttpResponse::Created().json(serde_json::json!({ "files": stored })))
+}
+
+async fn api_upload(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ApiPathQuery>,
+    payload: web::Payload,
+) -> ActixResult<HttpResponse> {
+    let target = api_target(
+        &state,
+        &req,
+        path.into_inner(),
+        query.path.as_deref().unwrap_or_default(),
+    )?;
+    if !target.permission.allows_upload() {
+        return Err(LunaError::forbidden("Write permission required").into());
+    }
+    if target.path.is_file() {
+        if !query.overwrite {
+            return Err(LunaError::conflict("File already exists").into());
+        }
+        if !target.permission.allows_modify() {
+            return Err(LunaError::forbidden("Modify permission required").into());
+        }
+    }
+    webdav::put(&state, target, payload).await
+}
+
+async fn api_paste(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ApiPathQuery>,
+    mut payload: web::Payload,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+    let extension = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(pasted_image_extension)
+        .ok_or_else(|| {
+            LunaError::unsupported_media_type(
+                "Pasted content must be a PNG, JPEG, GIF or WebP image",
+            )
+        })?;
+    let mount_name = path.into_inner();
+    let directory = api_target(
+        &state,
+        &req,
+        mount_name.clone(),
+        query.path.as_deref().unwrap_or_default(),
+    )?;
+    if !directory.path.is_dir() {
+        return Err(LunaError::not_found("Folder not found").into());
+    }
+    let name = format!(
+        "screenshot-{}.{}",
+        clock::format_file_stamp(clock::now_unix()),
+        extension
+    );
+    let file_name = unclaimed_path(&directory.path, &name)
+        .file_name()
+        .map(OsString::from)
+        .ok_or_else(|| LunaError::bad_request("Invalid file name"))?;
+    let relative = directory.relative.join(file_name);
+    let target = api_target(
+        &state,
+        &req,
+        mount_name.clone(),
+        &osname::encode_path(&relative),
+    )?;
+    let moderated = check_upload_destination(&target, false)?;
+
+    let mut head = Vec::new();
+    while head.len() < content::SNIFF_LEN {
+        match payload.try_next().await.map_err(LunaError::internal)? {
+            Some(chunk) => head.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    if content::sniff(&head) != content::ContentCategory::Image {
+        return Err(LunaError::unsupported_media_type("Pasted content is not an image").into());
+    }
+    let body = futures_util::stream::iter([Ok(web::Bytes::from(head))]).chain(payload);
+    webdav::put(&state, target, body).await?;
+
+    let link = (!moderated).then(|| {
+        let expires = clock::now_unix() + DEFAULT_PRESIGN_HOURS * 3600;
+        let signature = state
+            .signer
+            .sign(&mount_name, &pathbuf_to_string(&relative), expires);
+        let connection = req.connection_info();
+        format!(
+            "{}://{}{}?expires={}&sig={}",
+            connection.scheme(),
+            connection.host(),
+            urls::entry("dl", &mount_name, &relative),
+            expires,
+            signature
+        )
+    });
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "mount": mount_name,
+        "path": osname::encode_path(&relative),
+        "url": link,
+        "expires_in_hours": DEFAULT_PRESIGN_HOURS,
+        "moderated": moderated,
+    })))
+}
+
+fn pasted_image_extension(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+    match mime.as_str() {
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        _ => None,
+    }
+}
+
+fn check_upload_destination(target: &DavTarget<'_>, overwrite: bool) -> Result<bool, LunaError> {
+    if !target.permission.allows_upload() {
+        return Err(LunaError::forbidden("Write permission required"));
+    }
+    if target.relative.as_os_str().is_empty() || target.path.is_dir() {
+        return Err(LunaError::conflict("A folder already exists at this path"));
+    }
+    let moderated = target.mount.moderation && !target.permission.allows_moderate();
+    if !moderated && !target.path.parent().is_some_and(Path::is_dir) {
+        return Err(LunaError::conflict("Parent folder does not exist"));
+    }
+    if target.path.is_file() {
+        if !overwrite {
+            return Err(LunaError::conflict("File already exists"));
+        }
+        if !target.permission.allows_modify() {
+            return Err(LunaError::forbidden("Modify permission required"));
+        }
+    }
+    Ok(moderated)
+}
+
+fn resumable_upload_session(
+    state: &AppState,
+    req: &HttpRequest,
+    id: &str,
+) -> Result<upload::UploadSession, LunaError> {
+    let username = get_username_from_cookie(req);
+    state
+        .uploads
+        .get(id)
+        .filter(|session| session.username == username)
+        .ok_or_else(|| LunaError::not_found("Upload not found"))
+}
+
+fn upload_location(id: &str) -> String {
+    format!("/api/v1/uploads/{}", id)
+}
+
+async fn resumable_upload_create(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ResumableUploadRequest>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+    let body = body.into_inner();
+    let target = api_target(&state, &req, path.into_inner(), &body.path)?;
+    check_upload_destination(&target, body.overwrite)?;
+    check_upload_limits(&state.config.server.limits, body.size, body.size)?;
+    check_upload_size(
+        &state.config,
+        max_upload_size(&state.config, target.mount, target.username.as_deref()),
+        body.size,
+        target.username.as_deref(),
+    )?;
+
+    state.uploads.expire();
+    let session = state
+        .uploads
+        .create(
+            &target.mount_name,
+            &osname::encode_path(&target.relative),
+            body.size,
+            target.username.as_deref(),
+            body.overwrite,
+        )
+        .map_err(LunaError::internal)?;
+    let location = upload_location(&session.id);
+    Ok(HttpResponse::Created()
+        .insert_header((header::LOCATION, location.clone()))
+        .insert_header(("Upload-Offset", "0"))
+        .insert_header(("Upload-Length", session.size.to_string()))
+        .json(serde_json::json!({
+            "id": session.id,
+            "location": location,
+            "offset": 0,
+            "size": session.size,
+        })))
+}
+
+async fn resumable_upload_status(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let session = resumable_upload_session(&state, &req, &path)?;
+    let offset = state.uploads.offset(&session.id);
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .insert_header(("Upload-Offset", offset.to_string()))
+        .insert_header(("Upload-Length", session.size.to_string()))
+        .json(serde_json::json!({
+            "id": session.id,
+            "mount": session.mount,
+            "path": session.path,
+            "offset": offset,
+            "size": session.size,
+            "expires_at": session.created_at + upload::SESSION_TTL_SECS,
+        })))
+}
+
+async fn resumable_upload_append(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    mut payload: web::Payload,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+    let session = resumable_upload_session(&state, &req, &path)?;
+    let _active = state
+        .uploads
+        .begin(&session.id)
+        .map_err(LunaError::conflict)?;
+    let requested: u64 = req
+        .headers()
+        .get("Upload-Offset")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or_else(|| LunaError::bad_request("Upload-Offset header is required"))?;
+    let mut offset = state.uploads.offset(&session.id);
+    if requested != offset {
+        return Ok(HttpResponse::Conflict()
+            .insert_header(("Upload-Offset", offset.to_string()))
+            .json(serde_json::json!({
+                "code": "offset_mismatch",
+                "offset": offset,
+            })));
+    }
+
+    let mut part = tokio::fs::OpenOptions::new()
+        .append(true)
+        .open(state.uploads.part_path(&session.id))
+        .await
+        .map_err(LunaError::internal)?;
+    while let Some(chunk) = payload.try_next().await.map_err(LunaError::internal)? {
+        if offset + chunk.len() as u64 > session.size {
+            return Err(
+                LunaError::payload_too_large("Chunk extends past the declared size").into(),
+            );
+        }
+        part.write_all(&chunk).await.map_err(LunaError::internal)?;
+        offset += chunk.len() as u64;
+    }
+    part.flush().await.map_err(LunaError::internal)?;
+    drop(part);
+
+    if offset < session.size {
+        return Ok(HttpResponse::NoContent()
+            .insert_header(("Upload-Offset", offset.to_string()))
+            .finish());
+    }
+    let target = api_target(&state, &req, session.mount.clone(), &session.path)?;
+    let result = complete_resumable_upload(&state, &session, &target).await;
+    state.uploads.remove(&session.id);
+    result?;
+    Ok(HttpResponse::Created()
+        .insert_header(("Upload-Offset", offset.to_string()))
+        .json(serde_json::json!({
+            "mount": session.mount,
+            "path": session.path,
+            "size": session.size,
+        })))
+}
+
+async fn complete_resumable_upload(
+    state: &AppState,
+    session: &upload::UploadSession,
+    target: &DavTarget<'_>,
+) -> ActixResult<()> {
+    let moderated = check_upload_destination(target, session.overwrite)?;
+    let part_path = state.uploads.part_path(&session.id);
+
+    let mut head = Vec::with_capacity(content::SNIFF_LEN);
+    fs::File::open(&part_path)
+        .and_then(|file| file.take(content::SNIFF_LEN as u64).read_to_end(&mut head))
+        .map_err(LunaError::internal)?;
+    if let Err(category) = content::check(
+        &target.mount.allow_content,
+        &target.mount.deny_content,
+        &head,
+    ) {
+        return Err(LunaError::unsupported_media_type(format!(
+            "Uploading {} content to this mount is not allowed",
+            category
+        ))
+        .into());
+    }
+
+    let destination = if moderated {
+        moderation::queue_root(&target.base).join(&target.relative)
+    } else {
+        target.path.clone()
+    };
+    let parent = destination
+        .parent()
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    fs::create_dir_all(parent).map_err(LunaError::internal)?;
+
+    let staged_path = staging_path(state, parent, "upload");
+    let mut file = storage::FileWriter::create(
+        staged_codec(state, &target.mount_name, &destination),
+        &staged_path,
+    )
+    .map_err(LunaError::internal)?;
+    let (source, staged, final_path) = (part_path, staged_path.clone(), destination.clone());
+    let stored = run_blocking(state, move || {
+        let mut reader = fs::File::open(&source)?;
+        std::io::copy(&mut reader, &mut file)?;
+        file.finish()?;
+        storage::persist(&staged, &final_path)?;
+        Ok(())
+    })
+    .await;
+    if let Err(e) = stored {
+        let _ = fs::remove_file(&staged_path);
+        return Err(e);
+    }
+
+    finish_stored_upload(state, target, &destination, session.size).await
+}
+
+async fn resumable_upload_cancel(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> ActixResult<HttpResponse> {
+    let session = resumable_upload_session(&state, &req, &path)?;
+    let _active = state
+        .uploads
+        .begin(&session.id)
+        .map_err(LunaError::conflict)?;
+    state.uploads.remove(&session.id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn api_delete(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ApiPathRequest>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+    let target = api_target(&state, &req, path.into_inner(), &body.path)?;
+    if !target.permission.allows_delete() {
+        return Err(LunaError::forbidden("Delete permission required").into());
+    }
+    let subject = confirm_subject("delete", target.username.as_deref(), &target.relative);
+    if let Some((summary, token)) = confirmation_required(
+        &state,
+        &target.mount_name,
+        &subject,
+        &target.path,
+        body.confirm.as_deref(),
+    )
+    .await?
+    {
+        return Ok(
+            HttpResponse::build(actix_web::http::StatusCode::PRECONDITION_REQUIRED).json(
+                serde_json::json!({
+                    "code": "confirmation_required",
+                    "summary": summary,
+                    "confirm": token,
+                }),
+            ),
+        );
+    }
+    let submitted = webdav::submitted_tokens(&req);
+    Ok(match remove_entry(&state, target, &submitted).await? {
+        Some(job_id) => job_accepted(job_id),
+        None => HttpResponse::NoContent().finish(),
+    })
+}
+
+async fn api_mkdir(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ApiPathRequest>,
+) -> ActixResult<HttpResponse> {
+    let target = api_target(&state, &req, path.into_inner(), &body.path)?;
+    if !target.permission.allows_create_folder() {
+        return Err(LunaError::forbidden("Create folder permission required").into());
+    }
+    if target.relative.as_os_str().is_empty() || fs::symlink_metadata(&target.path).is_ok() {
+        return Err(LunaError::conflict("An entry already exists at this path").into());
+    }
+    webdav::mkcol(&state, target)
+}
+
+async fn api_rename(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ApiRenameRequest>,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(&state)?;
+    let body = body.into_inner();
+    let target = api_target(&state, &req, path.into_inner(), &body.path)?;
+    if !target.permission.allows_rename() {
+        return Err(LunaError::forbidden("Rename permission required").into());
+    }
+    let new_name =
+        sanitize_file_name(&body.name).ok_or_else(|| LunaError::bad_request("Invalid new name"))?;
+    let parent_relative = target
+        .relative
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| LunaError::bad_request("The mount root cannot be renamed"))?;
+    if fs::symlink_metadata(&target.path).is_err() {
+        return Err(LunaError::not_found("Path not found").into());
+    }
+    let renamed = parent_relative.join(&new_name);
+    let destination = resolve_path(&target.base, &renamed).map_err(LunaError::bad_request)?;
+    if fs::symlink_metadata(&destination).is_ok() {
+        return Err(LunaError::conflict("An entry with that name already exists").into());
+    }
+    fs::rename(&target.path, &destination).map_err(LunaError::internal)?;
+    if let Some(username) = &target.username {
+        state.history.record(
+            username,
+            state.history.next_id(),
+            format!(
+                "이름 변경: {}/{} → {}",
+                target.mount_name,
+                pathbuf_to_string(&target.relative),
+                new_name
+            ),
+            history::Operation::Rename {
+                mount: target.mount_name.clone(),
+                from: target.relative.clone(),
+                to: renamed.clone(),
+            },
+        );
+    }
+    publish_file_event(
+        &state,
+        &target.mount_name,
+        "rename",
+        &renamed,
+        target.username.as_deref(),
+    );
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "mount": target.mount_name,
+        "path": osname::encode_path(&renamed),
+    })))
+}
+
+async fn api_copy(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ApiTransferRequest>,
+) -> ActixResult<HttpResponse> {
     let username = get_username_from_cookie(&req);
-    let permission = effective_permission(config, username.as_deref(), mount)
-        .ok_or_else(|| error::ErrorForbidden("Write permission required"))?;
-    if !permission.allows_delete() {
-        return Err(error::ErrorForbidden("Write permission required"));
+    api_transfer(
+        &state,
+        &req,
+        username,
+        path.into_inner(),
+        body.into_inner(),
+        false,
+    )
+    .await
+}
+
+async fn api_move(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<ApiTransferRequest>,
+) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    api_transfer(
+        &state,
+        &req,
+        username,
+        path.into_inner(),
+        body.into_inner(),
+        true,
+    )
+    .await
+}
+
+async fn api_transfer(
+    state: &AppState,
+    req: &HttpRequest,
+    username: Option<String>,
+    mount_name: String,
+    body: ApiTransferRequest,
+    is_move: bool,
+) -> ActixResult<HttpResponse> {
+    ensure_writable(state)?;
+
+    let config = &state.config;
+    let header_value = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+    };
+    let (destination_mount_name, destination_relative) = match &body.destination {
+        Some(destination) => parse_destination(destination),
+        None => header_value("Destination")
+            .or_else(|| header_value("X-Destination"))
+            .and_then(|value| {
+                let value = match value.split_once("://") {
+                    Some((_, rest)) => &rest[rest.find('/')?..],
+                    None => value,
+                };
+                parse_destination(&urlencoding::decode(value).ok()?)
+            }),
     }
+    .ok_or_else(|| LunaError::bad_request("A valid destination is required"))?;
+    let overwrite = match body.overwrite {
+        Some(overwrite) => overwrite,
+        None => match header_value("Overwrite") {
+            None | Some("F") | Some("f") => false,
+            Some("T") | Some("t") => true,
+            Some(_) => return Err(LunaError::bad_request("Overwrite must be T or F").into()),
+        },
+    };
+    let recursive = match body.depth.as_deref().or_else(|| header_value("Depth")) {
+        None => true,
+        Some(depth) if depth.eq_ignore_ascii_case("infinity") => true,
+        Some("0") if !is_move => false,
+        Some(_) => return Err(LunaError::bad_request("Unsupported depth").into()),
+    };
 
-    let current_relative =
-        normalize_relative_path(&tail).ok_or_else(|| error::ErrorBadRequest("Invalid path"))?;
-    let target_relative = normalize_relative_path(&form.target_path)
-        .ok_or_else(|| error::ErrorBadRequest("Invalid target path"))?;
+    let source_mount = config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    let destination_mount = config
+        .mounts
+        .get(&destination_mount_name)
+        .ok_or_else(|| LunaError::not_found("Destination mount not found"))?;
+    if destination_mount_name != mount_name
+        && (state.mount_keys.contains_key(&mount_name)
+            || state.mount_keys.contains_key(&destination_mount_name))
+    {
+        return Err(LunaError::bad_request(
+            "Entries cannot be transferred to or from an encrypted mount",
+        )
+        .into());
+    }
+
+    let source_relative = normalize_relative_path(&body.path)
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    if destination_relative.as_os_str().is_empty() {
+        return Err(LunaError::bad_request("Invalid destination path").into());
+    }
+
+    let source_permission = effective_permission(
+        state,
+        username.as_deref(),
+        None,
+        &mount_name,
+        &source_relative,
+    )
+    .ok_or_else(|| LunaError::forbidden("Read permission required"))?;
+    if !source_permission.allows_read() || (is_move && !source_permission.allows_delete()) {
+        return Err(LunaError::forbidden("Permission required on source").into());
+    }
+    let destination_permission = effective_permission(
+        state,
+        username.as_deref(),
+        None,
+        &destination_mount_name,
+        &destination_relative,
+    )
+    .ok_or_else(|| LunaError::forbidden("Write permission required"))?;
+    if !destination_permission.allows_upload()
+        || (destination_mount.moderation && !destination_permission.allows_moderate())
+    {
+        return Err(LunaError::forbidden("Write permission required on destination").into());
+    }
+
+    let source_base = canonicalize_mount(&source_mount.path).map_err(LunaError::internal)?;
+    let destination_base =
+        canonicalize_mount(&destination_mount.path).map_err(LunaError::internal)?;
+    let source_path =
+        resolve_path(&source_base, &source_relative).map_err(LunaError::bad_request)?;
+    let destination_path =
+        resolve_path(&destination_base, &destination_relative).map_err(LunaError::bad_request)?;
+
+    if fs::symlink_metadata(&source_path).is_err() {
+        return Err(LunaError::not_found("Source not found").into());
+    }
+    if !destination_path.parent().is_some_and(Path::is_dir) {
+        return Err(LunaError::conflict("Destination directory does not exist").into());
+    }
+    if destination_path.starts_with(&source_path) || source_path.starts_with(&destination_path) {
+        return Err(LunaError::bad_request("Source and destination overlap").into());
+    }
+    let replaced = fs::symlink_metadata(&destination_path).is_ok();
+    if replaced {
+        if !overwrite {
+            return Err(LunaError::conflict("Destination already exists").into());
+        }
+        if !destination_permission.allows_delete() {
+            return Err(LunaError::forbidden("Delete permission required on destination").into());
+        }
+    }
+
+    if is_move {
+        let subject = confirm_subject("move", username.as_deref(), &source_relative);
+        if let Some((summary, token)) = confirmation_required(
+            state,
+            &mount_name,
+            &subject,
+            &source_path,
+            body.confirm.as_deref(),
+        )
+        .await?
+        {
+            return Ok(
+                HttpResponse::build(actix_web::http::StatusCode::PRECONDITION_REQUIRED).json(
+                    serde_json::json!({
+                        "code": "confirmation_required",
+                        "summary": summary,
+                        "confirm": token,
+                    }),
+                ),
+            );
+        }
+    }
+
+    let job_id = state.events.next_job_id();
+    let publish_job = |status| {
+        state.events.publish(events::Event::Job {
+            id: job_id,
+            username: username.clone(),
+            label: format!(
+                "{} {}/{} → {}/{}",
+                if is_move { "move" } else { "copy" },
+                mount_name,
+                pathbuf_to_string(&source_relative),
+                destination_mount_name,
+                pathbuf_to_string(&destination_relative)
+            ),
+            status,
+        })
+    };
+    publish_job(events::JobStatus::Started);
+    let result = run_blocking(state, move || {
+        if replaced {
+            if destination_path.is_dir() && !destination_path.is_symlink() {
+                fs::remove_dir_all(&destination_path)
+            } else {
+                fs::remove_file(&destination_path)
+            }
+            .with_context(|| format!("Failed to replace {:?}", destination_path))?;
+        }
+        if is_move {
+            move_entry(&source_path, &destination_path)
+        } else if recursive || !source_path.is_dir() {
+            copy_entry(&source_path, &destination_path)
+        } else {
+            fs::create_dir(&destination_path)
+                .with_context(|| format!("Failed to create directory: {:?}", destination_path))
+        }
+    })
+    .await;
+    if let Err(e) = result {
+        publish_job(events::JobStatus::Failed);
+        return Err(e);
+    }
+    publish_job(events::JobStatus::Finished);
+
+    if is_move {
+        schedule_blob_gc(state, source_mount, &source_base);
+        publish_file_event(
+            state,
+            &mount_name,
+            "delete",
+            &source_relative,
+            username.as_deref(),
+        );
+        if !replaced {
+            record_move(
+                state,
+                username.as_deref(),
+                &mount_name,
+                &source_relative,
+                &destination_mount_name,
+                &destination_relative,
+            );
+        }
+    }
+    if replaced {
+        schedule_blob_gc(state, destination_mount, &destination_base);
+    }
+    publish_file_event(
+        state,
+        &destination_mount_name,
+        "upload",
+        &destination_relative,
+        username.as_deref(),
+    );
+
+    let location = urls::entry("browse", &destination_mount_name, &destination_relative);
+    if replaced {
+        Ok(HttpResponse::NoContent()
+            .insert_header((header::LOCATION, location))
+            .finish())
+    } else {
+        Ok(HttpResponse::Created()
+            .insert_header((header::LOCATION, location))
+            .finish())
+    }
+}
+
+fn parse_destination(destination: &str) -> Option<(String, PathBuf)> {
+    let trimmed = destination.trim_start_matches('/');
+    let trimmed = trimmed
+        .strip_prefix("api/v1/")
+        .or_else(|| trimmed.strip_prefix("browse/"))
+        .unwrap_or(trimmed);
+    let (mount, rest) = trimmed.split_once('/').unwrap_or((trimmed, ""));
+    Some((mount.to_string(), normalize_relative_path(rest)?))
+}
+
+fn s3_target<'a>(
+    state: &'a AppState,
+    req: &HttpRequest,
+    bucket: &str,
+    key: &str,
+) -> Result<S3Target<'a>, s3::S3Error> {
+    let s3_config = state
+        .config
+        .s3
+        .as_ref()
+        .ok_or_else(|| s3::S3Error::access_denied("The S3 API is disabled"))?;
+    let authorized = s3::authenticate(s3_config, req)?;
+    let mount = state.config.mounts.get(bucket).ok_or_else(|| {
+        s3::S3Error::new(
+            actix_web::http::StatusCode::NOT_FOUND,
+            "NoSuchBucket",
+            "The specified bucket does not exist.",
+        )
+    })?;
+    let relative_path = normalize_raw_path(key.trim_end_matches('/'))
+        .ok_or_else(|| s3::S3Error::invalid_argument("Invalid object key"))?;
+    let username = authorized.key.user.clone();
+    if state.accounts.is_disabled(&state.config, &username) {
+        return Err(s3::S3Error::access_denied("The account is disabled"));
+    }
+    if needs_terms_acceptance(state, &username) {
+        return Err(s3::S3Error::access_denied(
+            "The terms of service must be accepted first",
+        ));
+    }
+    let permission = effective_permission(state, Some(&username), None, bucket, &relative_path)
+        .unwrap_or_default();
+
+    Ok(S3Target {
+        mount,
+        username,
+        payload_hash: authorized.payload_hash,
+        relative_path,
+        permission,
+    })
+}
+
+async fn s3_list_objects(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<S3ListQuery>,
+) -> Result<HttpResponse, s3::S3Error> {
+    let bucket = path.into_inner();
+    let target = s3_target(&state, &req, &bucket, "")?;
+    if !target.permission.allows_read() {
+        return Err(s3::S3Error::access_denied("Read permission required"));
+    }
+
+    let base_path = canonicalize_mount(&target.mount.path).map_err(s3::S3Error::internal)?;
+    let prefix = query.prefix.clone();
+    let objects = run_blocking(&state, move || s3::list_objects(&base_path, &prefix))
+        .await
+        .map_err(s3::S3Error::internal)?
+        .into_iter()
+        .filter(|object| {
+            normalize_raw_path(&object.key).is_some_and(|relative| {
+                effective_permission(&state, Some(&target.username), None, &bucket, &relative)
+                    .is_some_and(|permission| permission.allows_read())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let body = s3::list_objects_v2(
+        &s3::ListRequest {
+            bucket: &bucket,
+            prefix: &query.prefix,
+            delimiter: query.delimiter.as_deref(),
+            max_keys: query
+                .max_keys
+                .unwrap_or(s3::DEFAULT_MAX_KEYS)
+                .min(s3::DEFAULT_MAX_KEYS),
+            continuation_token: query.continuation_token.as_deref(),
+            start_after: query.start_after.as_deref(),
+        },
+        &objects,
+    );
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml")
+        .body(body))
+}
+
+async fn s3_get_object(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, s3::S3Error> {
+    let (bucket, key) = path.into_inner();
+    let target = s3_target(&state, &req, &bucket, &key)?;
+    if !target.permission.allows_read() {
+        return Err(s3::S3Error::access_denied("Read permission required"));
+    }
+
+    let base_path = canonicalize_mount(&target.mount.path).map_err(s3::S3Error::internal)?;
+    let file_path = resolve_path(&base_path, &target.relative_path)
+        .map_err(|e| s3::S3Error::invalid_argument(e.to_string()))?;
+    if key.ends_with('/') || !file_path.is_file() {
+        return Err(s3::S3Error::no_such_key());
+    }
+
+    let metadata = fs::metadata(&file_path).map_err(s3::S3Error::internal)?;
+    let (mut response, size) =
+        file_response(&state, &req, &bucket, &file_path).map_err(s3::S3Error::internal)?;
+    let etag = s3::etag(size, s3::modified_unix(&metadata));
+    if req.method() != actix_web::http::Method::HEAD {
+        record_access(
+            &state,
+            &bucket,
+            stats::EventKind::Download,
+            Some(target.username),
+            size,
+        );
+    }
+    if let Ok(value) = header::HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    Ok(response)
+}
+
+async fn s3_put_object(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, s3::S3Error> {
+    let (bucket, key) = path.into_inner();
+    let target = s3_target(&state, &req, &bucket, &key)?;
+    if state.maintenance.load(Ordering::Relaxed) {
+        return Err(s3::S3Error::new(
+            actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+            "ServiceUnavailable",
+            maintenance_message(&state.config),
+        ));
+    }
+    if target.relative_path.as_os_str().is_empty() {
+        return Err(s3::S3Error::invalid_argument("Object key is empty"));
+    }
+
+    let base_path = canonicalize_mount(&target.mount.path).map_err(s3::S3Error::internal)?;
+    let destination = resolve_path(&base_path, &target.relative_path)
+        .map_err(|e| s3::S3Error::invalid_argument(e.to_string()))?;
+
+    if key.ends_with('/') {
+        if !target.permission.allows_create_folder() {
+            return Err(s3::S3Error::access_denied(
+                "Create folder permission required",
+            ));
+        }
+        fs::create_dir_all(&destination).map_err(s3::S3Error::internal)?;
+        publish_file_event(
+            &state,
+            &bucket,
+            "create_folder",
+            &target.relative_path,
+            Some(&target.username),
+        );
+        return Ok(HttpResponse::Ok().finish());
+    }
+
+    if !target.permission.allows_upload() {
+        return Err(s3::S3Error::access_denied("Write permission required"));
+    }
+    if destination.is_dir() {
+        return Err(s3::S3Error::invalid_argument(
+            "A folder already exists at this key",
+        ));
+    }
+
+    let moderated = target.mount.moderation && !target.permission.allows_moderate();
+    let destination = if moderated {
+        moderation::queue_root(&base_path).join(&target.relative_path)
+    } else {
+        destination
+    };
+    let parent = destination
+        .parent()
+        .ok_or_else(|| s3::S3Error::invalid_argument("Invalid object key"))?;
+    if !parent.is_dir() {
+        if !moderated && !target.permission.allows_create_folder() {
+            return Err(s3::S3Error::access_denied(
+                "Create folder permission required",
+            ));
+        }
+        fs::create_dir_all(parent).map_err(s3::S3Error::internal)?;
+    }
+
+    let limits = &state.config.server.limits;
+    let temp_path = staging_path(&state, parent, "s3");
+    let max_upload_size = max_upload_size(&state.config, target.mount, Some(&target.username));
+    let mut file = storage::QueuedWriter::spawn(
+        storage::FileWriter::create(staged_codec(&state, &bucket, &destination), &temp_path)
+            .map_err(s3::S3Error::internal)?,
+        state.config.server.uploads.queue_chunks,
+    );
+    let mut hasher = Sha256::new();
+    let mut head = Vec::new();
+    let mut written: u64 = 0;
+    let result: Result<(), s3::S3Error> = async {
+        while let Some(chunk) = payload.try_next().await.map_err(s3::S3Error::internal)? {
+            written += chunk.len() as u64;
+            check_upload_limits(limits, written, written)
+                .and_then(|_| {
+                    check_upload_size(
+                        &state.config,
+                        max_upload_size,
+                        written,
+                        Some(&target.username),
+                    )
+                })
+                .map_err(|e| {
+                    s3::S3Error::new(
+                        actix_web::http::StatusCode::PAYLOAD_TOO_LARGE,
+                        "EntityTooLarge",
+                        e.to_string(),
+                    )
+                })?;
+            if head.len() < content::SNIFF_LEN {
+                let take = (content::SNIFF_LEN - head.len()).min(chunk.len());
+                head.extend_from_slice(&chunk[..take]);
+            }
+            hasher.update(&chunk);
+            file.write(chunk).await.map_err(s3::S3Error::internal)?;
+        }
+        if let Err(category) = content::check(
+            &target.mount.allow_content,
+            &target.mount.deny_content,
+            &head,
+        ) {
+            return Err(s3::S3Error::new(
+                actix_web::http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "InvalidRequest",
+                format!(
+                    "Uploading {} content to this mount is not allowed",
+                    category
+                ),
+            ));
+        }
+        let computed = format!("{:x}", hasher.finalize());
+        if target.payload_hash != s3::UNSIGNED_PAYLOAD && target.payload_hash != computed {
+            return Err(s3::S3Error::new(
+                actix_web::http::StatusCode::BAD_REQUEST,
+                "XAmzContentSHA256Mismatch",
+                "The provided x-amz-content-sha256 header does not match the payload.",
+            ));
+        }
+        Ok(())
+    }
+    .await;
+    let result = match result {
+        Ok(()) => file.finish().await.map_err(s3::S3Error::internal),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result
+        .and_then(|_| storage::persist(&temp_path, &destination).map_err(s3::S3Error::internal))
+    {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    record_access(
+        &state,
+        &bucket,
+        stats::EventKind::Upload,
+        Some(target.username.clone()),
+        written,
+    );
+    publish_file_event(
+        &state,
+        &bucket,
+        "upload",
+        &target.relative_path,
+        Some(&target.username),
+    );
+
+    if let Some(scan) = &state.config.scan {
+        let command = scan.command.clone();
+        let scan_path = destination.clone();
+        let codec = storage_codec(&state, &bucket).into_owned();
+        let outcome = run_blocking(&state, move || {
+            scan_stored_file(&command, codec.as_codec(), &scan_path)
+        })
+        .await
+        .map_err(s3::S3Error::internal)?;
+        if let ScanOutcome::Flagged(verdict) = outcome {
+            log::warn!(
+                "Upload {:?} to mount '{}' flagged by scanner: {}",
+                destination,
+                bucket,
+                verdict
+            );
+            quarantine::isolate(&base_path, &target.relative_path, &destination, &verdict)
+                .map_err(s3::S3Error::internal)?;
+        }
+    }
+    store_deduplicated(&state, target.mount, &base_path, &destination).await;
+    write_checksum(&state, &bucket, target.mount, &base_path, &destination).await;
+
+    let metadata = fs::metadata(&destination).ok();
+    let etag = s3::etag(
+        written,
+        metadata.as_ref().map(s3::modified_unix).unwrap_or(0),
+    );
+    Ok(HttpResponse::Ok()
+        .append_header((header::ETAG, etag))
+        .finish())
+}
+
+async fn s3_delete_object(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, s3::S3Error> {
+    let (bucket, key) = path.into_inner();
+    let target = s3_target(&state, &req, &bucket, &key)?;
+    if state.maintenance.load(Ordering::Relaxed) {
+        return Err(s3::S3Error::new(
+            actix_web::http::StatusCode::SERVICE_UNAVAILABLE,
+            "ServiceUnavailable",
+            maintenance_message(&state.config),
+        ));
+    }
+    if !target.permission.allows_delete() {
+        return Err(s3::S3Error::access_denied("Delete permission required"));
+    }
+    if target.relative_path.as_os_str().is_empty() {
+        return Err(s3::S3Error::invalid_argument("Object key is empty"));
+    }
+
+    let base_path = canonicalize_mount(&target.mount.path).map_err(s3::S3Error::internal)?;
+    let target_path = resolve_path(&base_path, &target.relative_path)
+        .map_err(|e| s3::S3Error::invalid_argument(e.to_string()))?;
+
+    let removed = if key.ends_with('/') {
+        target_path.is_dir() && fs::remove_dir(&target_path).is_ok()
+    } else if target_path.is_file() {
+        if target.mount.trash {
+            trash::discard(
+                &base_path,
+                &target.relative_path,
+                &target_path,
+                Some(&target.username),
+            )
+            .map_err(s3::S3Error::internal)?;
+        } else {
+            fs::remove_file(&target_path).map_err(s3::S3Error::internal)?;
+        }
+        true
+    } else {
+        false
+    };
+    if removed {
+        if !target.mount.trash {
+            schedule_blob_gc(&state, target.mount, &base_path);
+        }
+        publish_file_event(
+            &state,
+            &bucket,
+            "delete",
+            &target.relative_path,
+            Some(&target.username),
+        );
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn finish_stored_upload(
+    state: &AppState,
+    target: &DavTarget<'_>,
+    destination: &Path,
+    bytes: u64,
+) -> ActixResult<()> {
+    record_access(
+        state,
+        &target.mount_name,
+        stats::EventKind::Upload,
+        target.username.clone(),
+        bytes,
+    );
+    publish_file_event(
+        state,
+        &target.mount_name,
+        "upload",
+        &target.relative,
+        target.username.as_deref(),
+    );
+
+    if let Some(scan) = &state.config.scan {
+        let command = scan.command.clone();
+        let scan_path = destination.to_path_buf();
+        let codec = storage_codec(state, &target.mount_name).into_owned();
+        let outcome = run_blocking(state, move || {
+            scan_stored_file(&command, codec.as_codec(), &scan_path)
+        })
+        .await?;
+        if let ScanOutcome::Flagged(verdict) = outcome {
+            log::warn!(
+                "Upload {:?} to mount '{}' flagged by scanner: {}",
+                destination,
+                target.mount_name,
+                verdict
+            );
+            quarantine::isolate(&target.base, &target.relative, destination, &verdict)
+                .map_err(LunaError::internal)?;
+        }
+    }
+    store_deduplicated(state, target.mount, &target.base, destination).await;
+    write_checksum(
+        state,
+        &target.mount_name,
+        target.mount,
+        &target.base,
+        destination,
+    )
+    .await;
+    Ok(())
+}
+
+fn readable_mount<'a>(
+    state: &'a AppState,
+    req: &HttpRequest,
+    mount_name: &str,
+) -> ActixResult<&'a MountConfig> {
+    let mount = state
+        .config
+        .mounts
+        .get(mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+    let username = get_username_from_cookie(req);
+    let readable =
+        effective_permission(state, username.as_deref(), None, mount_name, Path::new(""))
+            .is_some_and(|permission| permission.allows_read());
+    if !readable {
+        return Err(LunaError::forbidden("Read permission required").into());
+    }
+    Ok(mount)
+}
+
+async fn event_stream(state: web::Data<AppState>, req: HttpRequest) -> ActixResult<HttpResponse> {
+    let username = get_username_from_cookie(&req);
+    let state = state.get_ref().clone();
+    let receiver = state.events.subscribe();
+    let heartbeat = tokio::time::interval(StdDuration::from_secs(SSE_HEARTBEAT_SECS));
+
+    let stream = futures_util::stream::unfold(
+        (receiver, heartbeat),
+        move |(mut receiver, mut heartbeat)| {
+            let state = state.clone();
+            let username = username.clone();
+            async move {
+                loop {
+                    tokio::select! {
+                        event = receiver.recv() => match event {
+                            Ok(event) => {
+                                if !event_visible(&state, username.as_deref(), &event) {
+                                    continue;
+                                }
+                                let data = serde_json::to_string(&event).unwrap_or_default();
+                                let chunk = format!("event: {}\ndata: {}\n\n", event.name(), data);
+                                return Some((
+                                    Ok::<_, std::io::Error>(web::Bytes::from(chunk)),
+                                    (receiver, heartbeat),
+                                ));
+                            }
+                            Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                        },
+                        _ = heartbeat.tick() => {
+                            return Some((
+                                Ok(web::Bytes::from_static(b": keep-alive\n\n")),
+                                (receiver, heartbeat),
+                            ));
+                        }
+                    }
+                }
+            }
+        },
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .append_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(stream))
+}
+
+fn event_visible(state: &AppState, username: Option<&str>, event: &events::Event) -> bool {
+    match event {
+        events::Event::File { mount, .. } => {
+            effective_permission(state, username, None, mount, Path::new(""))
+                .is_some_and(|permission| permission.allows_read())
+        }
+        events::Event::Job {
+            username: owner, ..
+        }
+        | events::Event::Progress {
+            username: owner, ..
+        } => username.is_some() && owner.as_deref() == username,
+    }
+}
 
-    let base_path = canonicalize_mount(&mount.path).map_err(error::ErrorInternalServerError)?;
-    let current_directory = resolve_path(&base_path, &current_relative)
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
-    let target_path = resolve_path(&base_path, &target_relative)
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+fn publish_file_event(
+    state: &AppState,
+    mount: &str,
+    action: &str,
+    path: &Path,
+    username: Option<&str>,
+) {
+    let path = pathbuf_to_string(path);
+    state.journal.record(mount, action, &path);
+    state.events.publish(events::Event::File {
+        mount: mount.to_string(),
+        action: action.to_string(),
+        path,
+        username: username.map(str::to_string),
+    });
+}
 
-    if !target_path.starts_with(&current_directory)
-        && target_path.parent() != Some(&current_directory)
-    {
-        return Err(error::ErrorBadRequest("Target outside directory"));
+fn notify_creator(state: &AppState, guest: &tokens::GuestToken, message: String) {
+    if let Some(notifier) = &state.notifier {
+        notifier.notify(&guest.created_by, message);
     }
+}
 
-    if target_path.is_dir() {
-        fs::remove_dir_all(&target_path).map_err(error::ErrorInternalServerError)?;
-    } else {
-        fs::remove_file(&target_path).map_err(error::ErrorInternalServerError)?;
+fn record_access(
+    state: &AppState,
+    mount: &str,
+    kind: stats::EventKind,
+    username: Option<String>,
+    bytes: u64,
+) {
+    let event = stats::AccessEvent {
+        timestamp: clock::now_unix(),
+        mount: mount.to_string(),
+        kind,
+        username,
+        bytes,
+    };
+    if let Err(e) = state.stats.record(&event) {
+        log::warn!("Failed to record access statistics: {}", e);
     }
-
-    Ok(HttpResponse::Found()
-        .append_header((header::LOCATION, format!("/browse/{}/{}", mount_name, tail)))
-        .finish())
 }
 
-async fn rename_entry(
-    state: web::Data<AppState>,
-    req: HttpRequest,
-    path: web::Path<(String, String)>,
-    form: web::Form<RenameForm>,
-) -> ActixResult<HttpResponse> {
-    let (mount_name, tail) = path.into_inner();
-    let config = &state.config;
-    let mount = config
-        .mounts
-        .get(&mount_name)
-        .ok_or_else(|| error::ErrorNotFound("Mount not found"))?;
-
-    let username = get_username_from_cookie(&req);
-    let permission = effective_permission(config, username.as_deref(), mount)
-        .ok_or_else(|| error::ErrorForbidden("Write permission required"))?;
-    if !permission.allows_rename() {
-        return Err(error::ErrorForbidden("Write permission required"));
+fn storage_codec<'a>(state: &'a AppState, mount_name: &str) -> storage::Codec<'a> {
+    storage::Codec {
+        key: state.mount_keys.get(mount_name),
+        compress: state
+            .config
+            .mounts
+            .get(mount_name)
+            .is_some_and(|mount| mount.compression),
     }
+}
 
-    let current_relative =
-        normalize_relative_path(&tail).ok_or_else(|| error::ErrorBadRequest("Invalid path"))?;
-    let target_relative = normalize_relative_path(&form.target_path)
-        .ok_or_else(|| error::ErrorBadRequest("Invalid target path"))?;
+fn staged_codec<'a>(
+    state: &'a AppState,
+    mount_name: &str,
+    destination: &Path,
+) -> storage::Codec<'a> {
+    let codec = storage_codec(state, mount_name);
+    storage::Codec {
+        compress: codec.compress && storage::should_compress(destination),
+        ..codec
+    }
+}
 
-    let new_name = sanitize_file_name(&form.new_name)
-        .ok_or_else(|| error::ErrorBadRequest("Invalid new name"))?;
+fn file_response(
+    state: &AppState,
+    req: &HttpRequest,
+    mount_name: &str,
+    path: &Path,
+) -> anyhow::Result<(HttpResponse, u64)> {
+    let codec = storage_codec(state, mount_name);
+    let (etag, modified) = file_validators(&fs::metadata(path)?);
+    let last_modified = header::HttpDate::from(modified).to_string();
+    if is_not_modified(req, &etag, modified) {
+        let response = HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::LAST_MODIFIED, last_modified))
+            .finish();
+        return Ok((response, 0));
+    }
 
-    let base_path = canonicalize_mount(&mount.path).map_err(error::ErrorInternalServerError)?;
-    let current_directory = resolve_path(&base_path, &current_relative)
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
-    let source_path = resolve_path(&base_path, &target_relative)
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+    if !storage::is_encoded(codec, path) {
+        let file = NamedFile::open(path)?
+            .use_etag(false)
+            .use_last_modified(false);
+        let size = file.metadata().len();
+        let mut response = file.into_response(req);
+        let headers = response.headers_mut();
+        headers.insert(header::ETAG, header::HeaderValue::from_str(&etag)?);
+        headers.insert(
+            header::LAST_MODIFIED,
+            header::HeaderValue::from_str(&last_modified)?,
+        );
+        return Ok((response, size));
+    }
 
-    if source_path.parent() != Some(&current_directory) {
-        return Err(error::ErrorBadRequest("Target outside directory"));
+    let (reader, size) = storage::open_reader(codec, path)?;
+    let range = match size {
+        Some(size) => match requested_range(req, size) {
+            Some(Some(range)) => Some(range),
+            Some(None) => {
+                let response = HttpResponse::RangeNotSatisfiable()
+                    .insert_header((header::CONTENT_RANGE, format!("bytes */{}", size)))
+                    .finish();
+                return Ok((response, 0));
+            }
+            None => None,
+        },
+        None => None,
+    };
+    let (skip, length) = match (range, size) {
+        (Some((start, end)), _) => (start, Some(end - start + 1)),
+        (None, size) => (0, size),
+    };
+    let stream = storage::queued_reader(reader, skip, length.unwrap_or(u64::MAX));
+    let mut response = match (range, size) {
+        (Some((start, end)), Some(size)) => {
+            let mut response = HttpResponse::PartialContent();
+            response.insert_header((
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, size),
+            ));
+            response
+        }
+        _ => HttpResponse::Ok(),
+    };
+    response.content_type(mime_guess::from_path(path).first_or_octet_stream());
+    response.insert_header((header::ETAG, etag));
+    response.insert_header((header::LAST_MODIFIED, last_modified));
+    if size.is_some() {
+        response.insert_header((header::ACCEPT_RANGES, "bytes"));
     }
+    if let Some(length) = length {
+        response.no_chunking(length);
+    }
+    let stored_size = fs::metadata(path)?.len();
+    Ok((response.streaming(stream), length.unwrap_or(stored_size)))
+}
 
-    let destination = current_directory.join(new_name);
-    fs::rename(&source_path, &destination).map_err(error::ErrorInternalServerError)?;
+fn file_validators(metadata: &fs::Metadata) -> (String, std::time::SystemTime) {
+    let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+    let nanos = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+    let etag = format!(
+        "\"{:x}-{:x}-{:x}\"",
+        file_inode(metadata),
+        metadata.len(),
+        nanos
+    );
+    (etag, modified)
+}
 
-    Ok(HttpResponse::Found()
-        .append_header((header::LOCATION, format!("/browse/{}/{}", mount_name, tail)))
-        .finish())
+#[cfg(unix)]
+fn file_inode(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
 }
 
-async fn edit_page(
-    state: web::Data<AppState>,
-    req: HttpRequest,
-    path: web::Path<(String, String)>,
-) -> ActixResult<HttpResponse> {
-    let (mount_name, tail) = path.into_inner();
-    let config = &state.config;
-    let mount = config
-        .mounts
-        .get(&mount_name)
-        .ok_or_else(|| error::ErrorNotFound("Mount not found"))?;
+#[cfg(not(unix))]
+fn file_inode(_metadata: &fs::Metadata) -> u64 {
+    0
+}
 
-    let username = get_username_from_cookie(&req);
-    let permission = effective_permission(config, username.as_deref(), mount)
-        .ok_or_else(|| error::ErrorForbidden("Permission required"))?;
-    if !permission.allows_modify() {
-        return Err(error::ErrorForbidden("Modify permission required"));
+fn is_not_modified(req: &HttpRequest, etag: &str, modified: std::time::SystemTime) -> bool {
+    if !matches!(
+        *req.method(),
+        actix_web::http::Method::GET | actix_web::http::Method::HEAD
+    ) {
+        return false;
+    }
+    if let Some(value) = req.headers().get(header::IF_NONE_MATCH) {
+        return value.to_str().is_ok_and(|value| {
+            value.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == etag
+            })
+        });
     }
+    let seconds = |time: std::time::SystemTime| {
+        time.duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    };
+    req.headers()
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<header::HttpDate>().ok())
+        .is_some_and(|since| seconds(modified) <= seconds(since.into()))
+}
 
-    let relative_path =
-        normalize_relative_path(&tail).ok_or_else(|| error::ErrorBadRequest("Invalid path"))?;
+fn requested_range(req: &HttpRequest, size: u64) -> Option<Option<(u64, u64)>> {
+    let value = req.headers().get(header::RANGE)?.to_str().ok()?;
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix = suffix.parse::<u64>().ok()?;
+            (size.saturating_sub(suffix), size.checked_sub(1)?)
+        }
+        (start, "") => (start.parse::<u64>().ok()?, size.saturating_sub(1)),
+        (start, end) => (
+            start.parse::<u64>().ok()?,
+            end.parse::<u64>().ok()?.min(size.saturating_sub(1)),
+        ),
+    };
+    Some((start <= end && start < size).then_some((start, end)))
+}
 
-    let base_path = canonicalize_mount(&mount.path).map_err(error::ErrorInternalServerError)?;
-    let target_path = resolve_path(&base_path, &relative_path)
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+fn scan_stored_file(
+    command: &[String],
+    codec: storage::Codec,
+    path: &Path,
+) -> anyhow::Result<ScanOutcome> {
+    if !storage::is_encoded(codec, path) {
+        return quarantine::scan(command, path);
+    }
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("File has no parent directory: {:?}", path))?;
+    let plaintext = parent.join(format!(".lunafinder_scan_{:016x}", rand::random::<u64>()));
+    storage::decode_file(codec, path, &plaintext)?;
+    let outcome = quarantine::scan(command, &plaintext);
+    let _ = fs::remove_file(&plaintext);
+    outcome
+}
 
-    if !target_path.is_file() {
-        return Err(error::ErrorBadRequest("Target is not a file"));
+async fn store_deduplicated(state: &AppState, mount: &MountConfig, base_path: &Path, path: &Path) {
+    if !mount.dedup || !path.is_file() {
+        return;
+    }
+    let base = base_path.to_path_buf();
+    let file = path.to_path_buf();
+    if let Err(e) = run_blocking(state, move || cas::store(&base, &file)).await {
+        log::warn!("Failed to deduplicate {:?}: {}", path, e);
     }
+}
 
-    let content = fs::read_to_string(&target_path).map_err(error::ErrorInternalServerError)?;
+async fn write_checksum(
+    state: &AppState,
+    mount_name: &str,
+    mount: &MountConfig,
+    base_path: &Path,
+    path: &Path,
+) {
+    if !mount.checksums
+        || !path.is_file()
+        || checksums::is_sidecar(path)
+        || path.starts_with(moderation::queue_root(base_path))
+    {
+        return;
+    }
+    let codec = storage_codec(state, mount_name).into_owned();
+    let file = path.to_path_buf();
+    if let Err(e) = run_blocking(state, move || checksums::generate(codec.as_codec(), &file)).await
+    {
+        log::warn!("Failed to write checksum for {:?}: {}", path, e);
+    }
+}
 
-    let parent_path = relative_path
-        .parent()
-        .map(|p| {
-            if p.as_os_str().is_empty() {
-                ".".to_string()
-            } else {
-                pathbuf_to_string(p)
+fn start_token_flusher(state: AppState) {
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::time::sleep(StdDuration::from_secs(TOKEN_FLUSH_SECS)).await;
+            let tokens = state.tokens.clone();
+            match tokio::task::spawn_blocking(move || tokens.flush()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::warn!("Failed to save share analytics: {}", e),
+                Err(e) => log::warn!("Share analytics flush panicked: {}", e),
             }
-        })
-        .unwrap_or_else(|| ".".to_string());
+        }
+    });
+}
 
-    let filename = target_path
-        .file_name()
-        .and_then(|f| f.to_str())
-        .unwrap_or("")
-        .to_string();
+fn start_fulltext_indexer(state: AppState) {
+    let mounts = state
+        .config
+        .mounts
+        .iter()
+        .filter(|(_, mount)| mount.fulltext)
+        .map(|(name, mount)| (name.clone(), mount.path.clone(), mount.gitignore))
+        .collect::<Vec<_>>();
+    if mounts.is_empty() {
+        return;
+    }
+    if !fulltext::available() {
+        log::warn!("Some mounts set fulltext = true but this build lacks the `fulltext` feature; content search is disabled");
+        return;
+    }
 
-    let mut context = TeraContext::new();
-    context.insert("mount_name", &mount_name);
-    context.insert("target_path", &pathbuf_to_string(&relative_path));
-    context.insert("parent_path", &parent_path);
-    context.insert("filename", &filename);
-    context.insert("content", &content);
+    let interval = StdDuration::from_secs(state.config.search.index_interval_secs);
+    actix_web::rt::spawn(async move {
+        loop {
+            for (mount_name, mount_path, gitignore) in &mounts {
+                let index = state.fulltext.clone();
+                let codec = storage_codec(&state, mount_name).into_owned();
+                let name = mount_name.clone();
+                let path = mount_path.clone();
+                let gitignore = *gitignore;
+                let started = Instant::now();
+                let result = tokio::task::spawn_blocking(move || {
+                    let base = canonicalize_mount(&path)?;
+                    index.rebuild(&name, &base, codec.as_codec(), gitignore)
+                })
+                .await;
+                match result {
+                    Ok(Ok(indexed)) => log::info!(
+                        "Indexed {} files on mount '{}' in {:?}",
+                        indexed,
+                        mount_name,
+                        started.elapsed()
+                    ),
+                    Ok(Err(e)) => {
+                        log::warn!(
+                            "Full-text indexing failed for mount '{}': {}",
+                            mount_name,
+                            e
+                        )
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Full-text indexer for mount '{}' panicked: {}",
+                            mount_name,
+                            e
+                        )
+                    }
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
 
-    let html = state
-        .tera
-        .render("edit.html", &context)
-        .map_err(error::ErrorInternalServerError)?;
+fn schedule_blob_gc(state: &AppState, mount: &MountConfig, base_path: &Path) {
+    if !mount.dedup {
+        return;
+    }
+    let state = state.clone();
+    let base = base_path.to_path_buf();
+    actix_web::rt::spawn(async move {
+        let blobs = base.clone();
+        match run_blocking(&state, move || cas::collect_garbage(&blobs)).await {
+            Ok(0) => {}
+            Ok(removed) => log::info!("Removed {} unreferenced blobs from {:?}", removed, base),
+            Err(e) => log::warn!("Blob garbage collection failed for {:?}: {}", base, e),
+        }
+    });
+}
 
-    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+fn ensure_writable(state: &AppState) -> ActixResult<()> {
+    if state.maintenance.load(Ordering::Relaxed) {
+        return Err(LunaError::Maintenance(maintenance_message(&state.config)).into());
+    }
+    Ok(())
 }
 
-async fn edit_save(
-    state: web::Data<AppState>,
-    req: HttpRequest,
-    path: web::Path<(String, String)>,
-    form: web::Form<EditForm>,
-) -> ActixResult<HttpResponse> {
-    let (mount_name, tail) = path.into_inner();
-    let config = &state.config;
-    let mount = config
-        .mounts
-        .get(&mount_name)
-        .ok_or_else(|| error::ErrorNotFound("Mount not found"))?;
+fn maintenance_message(config: &Config) -> String {
+    config
+        .server
+        .maintenance_message
+        .clone()
+        .unwrap_or_else(|| DEFAULT_MAINTENANCE_MESSAGE.to_string())
+}
 
-    let username = get_username_from_cookie(&req);
-    let permission = effective_permission(config, username.as_deref(), mount)
-        .ok_or_else(|| error::ErrorForbidden("Write permission required"))?;
-    if !permission.allows_modify() {
-        return Err(error::ErrorForbidden("Modify permission required"));
+fn render_template(
+    state: &AppState,
+    template: &str,
+    context: &mut TeraContext,
+) -> ActixResult<String> {
+    if state.maintenance.load(Ordering::Relaxed) {
+        context.insert("maintenance", &maintenance_message(&state.config));
     }
+    if let Some(announcement) = current_announcement(state) {
+        context.insert("announcement", &announcement);
+    }
+    context.insert("footer_links", &footer_links(&state.config));
+    if let Some(footer_text) = &state.config.pages.footer_text {
+        context.insert("footer_text", footer_text);
+    }
+    if state.config.server.dev {
+        let mut tera = state
+            .tera
+            .write()
+            .map_err(|_| LunaError::internal("Template lock poisoned"))?;
+        tera.full_reload().map_err(LunaError::internal)?;
+        state.assets.reload().map_err(LunaError::internal)?;
+    }
+    Ok(state
+        .tera
+        .read()
+        .map_err(|_| LunaError::internal("Template lock poisoned"))?
+        .render(template, context)
+        .map_err(LunaError::internal)?)
+}
 
-    let relative_path =
-        normalize_relative_path(&tail).ok_or_else(|| error::ErrorBadRequest("Invalid path"))?;
-
-    let base_path = canonicalize_mount(&mount.path).map_err(error::ErrorInternalServerError)?;
-    let target_path = resolve_path(&base_path, &relative_path)
-        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+fn footer_links(config: &Config) -> Vec<FooterLink> {
+    LEGAL_PAGES
+        .iter()
+        .filter(|(name, _)| config.pages.get(name).is_some())
+        .map(|(name, label)| FooterLink {
+            path: format!("/{}", name),
+            label: label.to_string(),
+        })
+        .collect()
+}
 
-    if !target_path.is_file() {
-        return Err(error::ErrorBadRequest("Target is not a file"));
+fn current_announcement(state: &AppState) -> Option<ActiveAnnouncement> {
+    let announcement = state.announcement.read().ok()?.clone()?;
+    match announcement.expires_at {
+        Some(expires_at) if expires_at <= clock::now_unix() => None,
+        _ => Some(announcement),
     }
+}
 
-    fs::write(&target_path, form.content.as_bytes()).map_err(error::ErrorInternalServerError)?;
-
-    let parent = relative_path
-        .parent()
-        .map(|p| {
-            if p.as_os_str().is_empty() {
-                ".".to_string()
-            } else {
-                pathbuf_to_string(p)
-            }
-        })
-        .unwrap_or_else(|| ".".to_string());
+async fn run_blocking<F, T>(state: &AppState, task: F) -> ActixResult<T>
+where
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let _permit = state
+        .blocking_jobs
+        .acquire()
+        .await
+        .map_err(LunaError::internal)?;
+    let timeout = StdDuration::from_secs(state.config.server.blocking.timeout_secs);
 
-    Ok(HttpResponse::Found()
-        .append_header((
-            header::LOCATION,
-            format!("/browse/{}/{}", mount_name, parent),
-        ))
-        .finish())
+    match tokio::time::timeout(timeout, web::block(task)).await {
+        Ok(result) => Ok(result?.map_err(LunaError::internal)?),
+        Err(_) => Err(LunaError::Timeout.into()),
+    }
 }
 
 fn ensure_mount_directories(config: &Config) -> anyhow::Result<()> {
@@ -638,9 +8719,94 @@ fn ensure_mount_directories(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn guest_token(
+    state: &AppState,
+    req: &HttpRequest,
+    mount_name: &str,
+) -> Option<tokens::GuestToken> {
+    let query = web::Query::<TokenQuery>::from_query(req.query_string()).ok()?;
+    state
+        .tokens
+        .get(query.token.as_deref()?)
+        .filter(|guest| guest.mount == mount_name && guest.drop.is_none())
+}
+
+fn share_referrer(req: &HttpRequest) -> Option<String> {
+    let referer = req.headers().get(header::REFERER)?.to_str().ok()?;
+    let (_, rest) = referer.split_once("://")?;
+    let host = rest
+        .split(['/', '?', '#'])
+        .next()?
+        .rsplit('@')
+        .next()?
+        .to_ascii_lowercase();
+    let own_host = req.connection_info().host().to_ascii_lowercase();
+    (!host.is_empty() && host != own_host).then_some(host)
+}
+
+fn token_query(guest: Option<&tokens::GuestToken>) -> String {
+    guest
+        .map(|guest| format!("?token={}", guest.token))
+        .unwrap_or_default()
+}
+
+fn login_location(config: &Config, username: Option<&str>, req: &HttpRequest) -> String {
+    let negotiate = config
+        .negotiate
+        .as_ref()
+        .is_some_and(|negotiate| negotiate.enabled);
+    if negotiate && username.is_none() {
+        format!("/login/negotiate?next={}", urls::segment(req.path()))
+    } else {
+        "/login".to_string()
+    }
+}
+
 fn get_username_from_cookie(req: &HttpRequest) -> Option<String> {
-    req.cookie(SESSION_COOKIE)
-        .map(|cookie| cookie.value().to_string())
+    let username = session_username(req)?;
+    Some(view_as(req).map(|(_, target)| target).unwrap_or(username))
+}
+
+fn session_username(req: &HttpRequest) -> Option<String> {
+    let state = req.app_data::<web::Data<AppState>>()?;
+    let username = state.sessions.verify(req.cookie(SESSION_COOKIE)?.value())?;
+    (!state.accounts.is_disabled(&state.config, &username)).then_some(username)
+}
+
+fn view_as(req: &HttpRequest) -> Option<(String, String)> {
+    let state = req.app_data::<web::Data<AppState>>()?;
+    let admin = session_username(req).filter(|admin| is_admin(&state.config, Some(admin)))?;
+    let cookie = req.cookie(VIEW_AS_COOKIE)?;
+    let mut parts = cookie.value().rsplitn(3, '.');
+    let signature = parts.next()?;
+    let expires = parts.next()?.parse().ok()?;
+    let target = urlencoding::decode(parts.next()?).ok()?.into_owned();
+    (state.config.users.contains_key(&target)
+        && state.signer.verify(
+            "view-as",
+            &view_as_message(&admin, &target),
+            expires,
+            signature,
+        ))
+    .then_some((admin, target))
+}
+
+fn view_as_message(admin: &str, target: &str) -> String {
+    format!("{}\0{}", admin, target)
+}
+
+fn is_admin(config: &Config, username: Option<&str>) -> bool {
+    username
+        .and_then(|username| config.users.get(username))
+        .map(|user| user.group.iter().any(|group| group == "admin"))
+        .unwrap_or(false)
+}
+
+fn size_units_for(config: &Config, username: Option<&str>) -> SizeUnits {
+    username
+        .and_then(|username| config.users.get(username))
+        .and_then(|user| user.size_units)
+        .unwrap_or(config.display.size_units)
 }
 
 fn render_markdown(content: &str) -> String {
@@ -655,6 +8821,14 @@ fn render_markdown(content: &str) -> String {
 }
 
 fn normalize_relative_path(path: &str) -> Option<PathBuf> {
+    normalize_path_with(path, osname::decode)
+}
+
+fn normalize_raw_path(path: &str) -> Option<PathBuf> {
+    normalize_path_with(path, |part| Some(OsString::from(part)))
+}
+
+fn normalize_path_with(path: &str, decode: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
     if path == "." || path.is_empty() {
         return Some(PathBuf::new());
     }
@@ -663,7 +8837,13 @@ fn normalize_relative_path(path: &str) -> Option<PathBuf> {
     for component in Path::new(path).components() {
         match component {
             Component::CurDir => {}
-            Component::Normal(part) => normalized.push(part),
+            Component::Normal(part) => {
+                let part = decode(part.to_str()?)?;
+                if is_reserved_name(&part.to_string_lossy()) {
+                    return None;
+                }
+                normalized.push(part)
+            }
             Component::ParentDir => {
                 if !normalized.pop() {
                     return None;
@@ -682,12 +8862,20 @@ fn sanitize_file_name(filename: &str) -> Option<String> {
         .and_then(|f| f.to_str())?
         .trim();
 
-    if candidate.is_empty() || candidate.contains('/') || candidate.contains('\\') {
+    if candidate.is_empty()
+        || candidate.contains('/')
+        || candidate.contains('\\')
+        || is_reserved_name(candidate)
+    {
         return None;
     }
     Some(candidate.to_string())
 }
 
+fn is_reserved_name(name: &str) -> bool {
+    name.starts_with(".lunafinder_")
+}
+
 fn canonicalize_mount(path: &Path) -> anyhow::Result<PathBuf> {
     if !path.exists() {
         fs::create_dir_all(path)
@@ -707,29 +8895,179 @@ fn resolve_path(base: &Path, relative: &Path) -> anyhow::Result<PathBuf> {
         return Err(anyhow!("Access outside of mount detected"));
     }
 
+    let mut existing = target.as_path();
+    while fs::symlink_metadata(existing).is_err() {
+        existing = existing
+            .parent()
+            .ok_or_else(|| anyhow!("Access outside of mount detected"))?;
+    }
+    let canonical = fs::canonicalize(existing)
+        .with_context(|| format!("Failed to resolve path: {:?}", existing))?;
+    if !canonical.starts_with(base) {
+        return Err(anyhow!("Access outside of mount detected"));
+    }
+
     Ok(target)
 }
 
-fn collect_entries(path: &Path) -> anyhow::Result<Vec<FileEntry>> {
+fn contained_metadata(base: &Path, entry: &fs::DirEntry) -> Option<fs::Metadata> {
+    if !entry.file_type().ok()?.is_symlink() {
+        return entry.metadata().ok();
+    }
+    let resolved = fs::canonicalize(entry.path()).ok()?;
+    let metadata = fs::metadata(&resolved).ok()?;
+    (resolved.starts_with(base) && metadata.is_file()).then_some(metadata)
+}
+
+fn copy_entry(source: &Path, destination: &Path) -> anyhow::Result<()> {
+    if source.is_dir() {
+        fs::create_dir(destination)
+            .with_context(|| format!("Failed to create directory: {:?}", destination))?;
+        for entry in fs::read_dir(source)
+            .with_context(|| format!("Failed to read directory: {:?}", source))?
+        {
+            let entry = entry?;
+            if is_reserved_name(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            copy_entry(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, destination)
+            .with_context(|| format!("Failed to copy {:?} to {:?}", source, destination))?;
+    }
+    Ok(())
+}
+
+fn count_entries(path: &Path, limit: u64) -> anyhow::Result<u64> {
+    if !fs::symlink_metadata(path)?.is_dir() {
+        return Ok(1);
+    }
+    let mut count = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(&directory)
+            .with_context(|| format!("Failed to read directory: {:?}", directory))?
+        {
+            let entry = entry?;
+            count += 1;
+            if count > limit {
+                return Ok(count);
+            }
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            }
+        }
+    }
+    Ok(count + 1)
+}
+
+fn remove_tree(root: &Path, job: &jobs::Job, progress: impl Fn(u64)) -> anyhow::Result<bool> {
+    let mut pending = vec![(root.to_path_buf(), false)];
+    while let Some((path, visited)) = pending.pop() {
+        if job.is_cancelled() {
+            return Ok(false);
+        }
+        if visited {
+            fs::remove_dir(&path).with_context(|| format!("Failed to delete {:?}", path))?;
+            progress(job.advance());
+            continue;
+        }
+        if fs::symlink_metadata(&path)?.is_dir() {
+            pending.push((path.clone(), true));
+            for entry in fs::read_dir(&path)
+                .with_context(|| format!("Failed to read directory: {:?}", path))?
+            {
+                pending.push((entry?.path(), false));
+            }
+        } else {
+            fs::remove_file(&path).with_context(|| format!("Failed to delete {:?}", path))?;
+            progress(job.advance());
+        }
+    }
+    Ok(true)
+}
+
+fn move_entry(source: &Path, destination: &Path) -> anyhow::Result<()> {
+    if fs::rename(source, destination).is_ok() {
+        return Ok(());
+    }
+    copy_entry(source, destination)?;
+    if source.is_dir() {
+        fs::remove_dir_all(source)
+    } else {
+        fs::remove_file(source)
+    }
+    .with_context(|| format!("Failed to remove {:?} after copying", source))
+}
+
+fn collect_entries(
+    path: &Path,
+    relative: &Path,
+    sort: ListingSort,
+    descending: bool,
+    rules: Option<&gitignore::Rules>,
+) -> anyhow::Result<Vec<FileEntry>> {
     let mut entries = Vec::new();
+    let now = clock::now_unix();
 
     if path.is_dir() {
         for entry in
             fs::read_dir(path).with_context(|| format!("Failed to read directory: {:?}", path))?
         {
             let entry = entry?;
+            let file_name = entry.file_name();
+            let name = file_name.to_string_lossy().to_string();
+            if is_reserved_name(&name) {
+                continue;
+            }
             let metadata = entry.metadata()?;
-            let name = entry.file_name().to_string_lossy().to_string();
             let is_dir = metadata.is_dir();
+            if rules.is_some_and(|rules| rules.is_ignored(&entry.path(), is_dir)) {
+                continue;
+            }
             let size = if is_dir { None } else { Some(metadata.len()) };
-
-            entries.push(FileEntry { name, is_dir, size });
+            let mtime = s3::modified_unix(&metadata);
+            let created = metadata
+                .created()
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs());
+            let viewer =
+                (!is_dir && model_content_type(Path::new(&file_name)).is_some()).then_some("model");
+            let thumbnail = !is_dir && thumbs::source_format(Path::new(&file_name)).is_some();
+
+            entries.push(FileEntry {
+                name,
+                link: osname::encode_path(&relative.join(&file_name)),
+                file_name,
+                is_dir,
+                size,
+                mtime,
+                created,
+                modified_relative: format::format_relative_time(mtime, now),
+                viewer,
+                thumbnail,
+                can_rename: false,
+                can_delete: false,
+                can_modify: false,
+            });
         }
 
-        entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        entries.sort_by(|a, b| {
+            let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+            let ordering = match sort {
+                ListingSort::Name => by_name(),
+                ListingSort::Size => a.size.cmp(&b.size).then_with(by_name),
+                ListingSort::Mtime => a.mtime.cmp(&b.mtime).then_with(by_name),
+                ListingSort::Created => a.created.cmp(&b.created).then_with(by_name),
+            };
+            match (a.is_dir, b.is_dir) {
+                (true, false) => std::cmp::Ordering::Less,
+                (false, true) => std::cmp::Ordering::Greater,
+                _ if descending => ordering.reverse(),
+                _ => ordering,
+            }
         });
     }
 
@@ -751,11 +9089,8 @@ fn build_directory_tree(
     base: &Path,
     relative: &Path,
     depth: usize,
+    limits: &TreeConfig,
 ) -> anyhow::Result<DirectoryNode> {
-    if depth > TREE_MAX_DEPTH {
-        return Err(anyhow!("Directory tree depth exceeded"));
-    }
-
     let current_path = if relative.as_os_str().is_empty() {
         base.to_path_buf()
     } else {
@@ -767,16 +9102,17 @@ fn build_directory_tree(
     } else {
         relative
             .file_name()
-            .and_then(|f| f.to_str())
-            .unwrap_or("")
-            .to_string()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default()
     };
 
-    let path_string = pathbuf_to_string(relative);
+    let path_string = osname::encode_path(relative);
     let mut node = DirectoryNode {
         name,
         path: path_string,
         children: Vec::new(),
+        truncated: false,
+        omitted: 0,
     };
 
     let mut directories = Vec::new();
@@ -784,22 +9120,44 @@ fn build_directory_tree(
         .with_context(|| format!("Failed to read directory: {:?}", current_path))?
     {
         let entry = entry?;
+        let entry_name = entry.file_name().to_string_lossy().to_string();
+        if is_reserved_name(&entry_name) || pattern::matches_any(&limits.exclude, &entry_name) {
+            continue;
+        }
         let metadata = entry.metadata()?;
         if metadata.is_dir() {
             directories.push(entry.file_name());
         }
     }
 
+    if depth >= limits.max_depth {
+        node.truncated = !directories.is_empty();
+        node.omitted = directories.len();
+        return Ok(node);
+    }
+
     directories.sort_by(|a, b| {
         a.to_string_lossy()
             .to_lowercase()
             .cmp(&b.to_string_lossy().to_lowercase())
     });
 
+    if directories.len() > limits.max_children {
+        node.truncated = true;
+        node.omitted = directories.len() - limits.max_children;
+        directories.truncate(limits.max_children);
+    }
+
     for dir_name in directories {
         let child_relative = relative.join(&dir_name);
-        node.children
-            .push(build_directory_tree(base, &child_relative, depth + 1)?);
+        match build_directory_tree(base, &child_relative, depth + 1, limits) {
+            Ok(child) => node.children.push(child),
+            Err(e) => {
+                log::warn!("Skipping {:?} in directory tree: {}", child_relative, e);
+                node.truncated = true;
+                node.omitted += 1;
+            }
+        }
     }
 
     Ok(node)
@@ -826,30 +9184,27 @@ fn build_open_paths(current_path: &str) -> Vec<String> {
 }
 
 fn effective_permission(
-    config: &Config,
+    state: &AppState,
     username: Option<&str>,
-    mount: &MountConfig,
+    guest: Option<&tokens::GuestToken>,
+    mount: &str,
+    target: &Path,
 ) -> Option<Permission> {
-    let mut aggregated = if mount.public {
-        Some(Permission::from_actions(["read"]))
-    } else {
-        None
-    };
+    let mut aggregated = state.permissions.lookup(mount, username, target);
 
-    if let Some(username) = username {
-        if let Some(spec) = mount.user.get(username) {
-            let resolved = config.resolve_permission_spec(spec);
-            aggregated = merge_permission(aggregated, resolved);
-        }
+    if let Some(guest) = guest.filter(|guest| target.starts_with(&guest.path)) {
+        aggregated =
+            permissions::merge_permission(aggregated, Permission::from_actions(&guest.actions));
+    }
 
-        if let Some(user_config) = config.users.get(username) {
-            for group in &user_config.group {
-                if let Some(spec) = mount.group.get(group) {
-                    let resolved = config.resolve_permission_spec(spec);
-                    aggregated = merge_permission(aggregated, resolved);
-                }
+    if state.config.server.read_only {
+        aggregated = aggregated.map(|permission| {
+            if permission.allows_read() {
+                Permission::from_actions(["read"])
+            } else {
+                Permission::default()
             }
-        }
+        });
     }
 
     match aggregated {
@@ -857,17 +9212,3 @@ fn effective_permission(
         other => other,
     }
 }
-
-fn merge_permission(current: Option<Permission>, addition: Permission) -> Option<Permission> {
-    if addition.is_empty() {
-        return current;
-    }
-
-    match current {
-        Some(mut existing) => {
-            existing.merge(&addition);
-            Some(existing)
-        }
-        None => Some(addition),
-    }
-}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MAX_FILES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Plain,
+    Aria2,
+}
+
+impl Format {
+    pub fn parse(value: Option<&str>) -> Option<Self> {
+        match value.unwrap_or("plain") {
+            "plain" | "wget" => Some(Format::Plain),
+            "aria2" => Some(Format::Aria2),
+            _ => None,
+        }
+    }
+}
+
+pub fn collect(
+    base: &Path,
+    selection: &[PathBuf],
+    readable: impl Fn(&Path) -> bool,
+) -> Result<Vec<PathBuf>> {
+    let mut files = BTreeSet::new();
+    let mut pending = selection.to_vec();
+    while let Some(relative) = pending.pop() {
+        if !readable(&relative) {
+            continue;
+        }
+        let path = base.join(&relative);
+        let metadata =
+            fs::metadata(&path).with_context(|| format!("Failed to read {:?}", relative))?;
+        if metadata.is_file() {
+            files.insert(relative);
+            if files.len() > MAX_FILES {
+                break;
+            }
+            continue;
+        }
+        if !metadata.is_dir() {
+            continue;
+        }
+        for entry in
+            fs::read_dir(&path).with_context(|| format!("Failed to read directory: {:?}", path))?
+        {
+            let name = entry?.file_name();
+            if !crate::is_reserved_name(&name.to_string_lossy()) {
+                pending.push(relative.join(name));
+            }
+        }
+    }
+    Ok(files.into_iter().collect())
+}
+
+pub fn render(format: Format, entries: &[(String, String)]) -> String {
+    let mut out = String::new();
+    for (url, relative) in entries {
+        out.push_str(url);
+        out.push('\n');
+        if format == Format::Aria2 {
+            out.push_str("  out=");
+            out.push_str(relative);
+            out.push('\n');
+        }
+    }
+    out
+}
@@ -0,0 +1,125 @@
+use actix_web::http::Method;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Default, Clone, Copy)]
+struct Series {
+    requests: u64,
+    errors: u64,
+    bytes: u64,
+}
+
+type SeriesMap = BTreeMap<(String, &'static str), Series>;
+type Family = (&'static str, &'static str, fn(&Series) -> u64);
+
+#[derive(Default)]
+pub struct Metrics {
+    series: Mutex<SeriesMap>,
+}
+
+impl Metrics {
+    pub fn record(&self, mount: &str, operation: &'static str, bytes: u64, failed: bool) {
+        let Ok(mut series) = self.series.lock() else {
+            return;
+        };
+        let entry = series.entry((mount.to_string(), operation)).or_default();
+        entry.requests += 1;
+        entry.bytes += bytes;
+        if failed {
+            entry.errors += 1;
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let series = self
+            .series
+            .lock()
+            .map(|series| series.clone())
+            .unwrap_or_default();
+        let families: [Family; 3] = [
+            (
+                "lunafinder_requests_total",
+                "Requests handled per mount and operation.",
+                |series| series.requests,
+            ),
+            (
+                "lunafinder_request_errors_total",
+                "Requests that ended with a 4xx or 5xx status per mount and operation.",
+                |series| series.errors,
+            ),
+            (
+                "lunafinder_response_bytes_total",
+                "Response body bytes with a known length per mount and operation.",
+                |series| series.bytes,
+            ),
+        ];
+
+        let mut output = String::new();
+        for (name, help, value) in families {
+            let _ = writeln!(output, "# HELP {} {}", name, help);
+            let _ = writeln!(output, "# TYPE {} counter", name);
+            for ((mount, operation), entry) in &series {
+                let _ = writeln!(
+                    output,
+                    "{}{{mount=\"{}\",operation=\"{}\"}} {}",
+                    name,
+                    escape_label(mount),
+                    operation,
+                    value(entry)
+                );
+            }
+        }
+        output
+    }
+}
+
+pub fn operation(pattern: &str, method: &Method) -> Option<&'static str> {
+    let operation = if pattern.starts_with("/s3/") {
+        if method == Method::PUT {
+            "s3_put"
+        } else if method == Method::DELETE {
+            "s3_delete"
+        } else if pattern == "/s3/{bucket}" {
+            "s3_list"
+        } else {
+            "s3_get"
+        }
+    } else if pattern.starts_with("/browse/") {
+        match pattern.rsplit('/').next() {
+            Some("upload") => "upload",
+            Some("delete") => "delete",
+            Some("rename") => "rename",
+            Some("transfer") => "transfer",
+            Some("token") | Some("presign") => "share",
+            _ => "browse",
+        }
+    } else if pattern.starts_with("/dl/") {
+        "download"
+    } else if pattern.starts_with("/edit/") {
+        "edit"
+    } else if pattern.starts_with("/api/v1/") {
+        match pattern.rsplit('/').next() {
+            Some("copy") | Some("move") => "transfer",
+            _ => "browse",
+        }
+    } else if pattern.starts_with("/api/sync/") {
+        "sync"
+    } else if pattern.starts_with("/moderate/") {
+        "moderate"
+    } else if pattern.starts_with("/quarantine/") {
+        "quarantine"
+    } else {
+        return None;
+    };
+    Some(operation)
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
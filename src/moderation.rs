@@ -0,0 +1,93 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const MODERATION_DIR: &str = ".lunafinder_moderation";
+
+#[derive(Debug, Serialize)]
+pub struct PendingUpload {
+    pub path: String,
+    pub name: String,
+    pub size: u64,
+}
+
+pub fn queue_root(base: &Path) -> PathBuf {
+    base.join(MODERATION_DIR)
+}
+
+pub fn list_pending(base: &Path) -> Result<Vec<PendingUpload>> {
+    let root = queue_root(base);
+    let mut pending = Vec::new();
+    if root.is_dir() {
+        collect_pending(&root, Path::new(""), &mut pending)?;
+    }
+    pending.sort_by_key(|upload| upload.path.to_lowercase());
+    Ok(pending)
+}
+
+fn collect_pending(root: &Path, relative: &Path, pending: &mut Vec<PendingUpload>) -> Result<()> {
+    let current = root.join(relative);
+    for entry in fs::read_dir(&current)
+        .with_context(|| format!("Failed to read directory: {:?}", current))?
+    {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let child_relative = relative.join(entry.file_name());
+        if metadata.is_dir() {
+            collect_pending(root, &child_relative, pending)?;
+        } else {
+            pending.push(PendingUpload {
+                path: crate::osname::encode_path(&child_relative),
+                name: entry.file_name().to_string_lossy().to_string(),
+                size: metadata.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn approve(base: &Path, relative: &Path) -> Result<()> {
+    let source = pending_file(base, relative)?;
+    let destination = base.join(relative);
+    if destination.exists() {
+        return Err(anyhow!("A file already exists at {:?}", relative));
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+    fs::rename(&source, &destination)
+        .with_context(|| format!("Failed to publish pending upload: {:?}", relative))?;
+    prune_empty_parents(&queue_root(base), &source);
+    Ok(())
+}
+
+pub fn reject(base: &Path, relative: &Path) -> Result<()> {
+    let source = pending_file(base, relative)?;
+    fs::remove_file(&source)
+        .with_context(|| format!("Failed to remove pending upload: {:?}", relative))?;
+    prune_empty_parents(&queue_root(base), &source);
+    Ok(())
+}
+
+fn pending_file(base: &Path, relative: &Path) -> Result<PathBuf> {
+    if relative.as_os_str().is_empty() {
+        return Err(anyhow!("Pending upload path is empty"));
+    }
+    let path = queue_root(base).join(relative);
+    if !path.is_file() {
+        return Err(anyhow!("Pending upload not found: {:?}", relative));
+    }
+    Ok(path)
+}
+
+fn prune_empty_parents(root: &Path, file: &Path) {
+    let mut current = file.parent();
+    while let Some(dir) = current {
+        if dir == root || !dir.starts_with(root) || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
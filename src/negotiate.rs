@@ -0,0 +1,37 @@
+use anyhow::{anyhow, Result};
+
+pub struct Accepted {
+    pub principal: String,
+    pub response_token: Option<String>,
+}
+
+#[cfg(feature = "spnego")]
+pub fn accept(service_principal: Option<&str>, token: &str) -> Result<Accepted> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use cross_krb5::{AcceptFlags, K5ServerCtx, ServerCtx, Step};
+
+    let token = STANDARD.decode(token.trim())?;
+    let pending = ServerCtx::new(AcceptFlags::empty(), service_principal)?;
+    match pending.step(&token)? {
+        Step::Finished((mut context, response_token)) => Ok(Accepted {
+            principal: context.client()?,
+            response_token: response_token.map(|token| STANDARD.encode(&*token)),
+        }),
+        Step::Continue(_) => Err(anyhow!(
+            "Negotiate handshake did not complete in a single round trip"
+        )),
+    }
+}
+
+#[cfg(not(feature = "spnego"))]
+pub fn accept(_service_principal: Option<&str>, _token: &str) -> Result<Accepted> {
+    Err(anyhow!("LunaFinder was built without the `spnego` feature"))
+}
+
+pub fn username_from_principal(principal: &str) -> &str {
+    principal.split('@').next().unwrap_or(principal)
+}
+
+pub fn available() -> bool {
+    cfg!(feature = "spnego")
+}
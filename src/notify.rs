@@ -0,0 +1,159 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::config::{Config, NotificationsConfig};
+
+const MAIL_SUBJECT: &str = "LunaFinder 게스트 링크 알림";
+const ALERT_SUBJECT: &str = "LunaFinder 보안 알림";
+
+struct Notice {
+    recipient: String,
+    subject: &'static str,
+    message: String,
+}
+
+#[derive(Clone)]
+pub struct Notifier {
+    sender: UnboundedSender<Notice>,
+}
+
+impl Notifier {
+    pub fn start(config: Arc<Config>) -> Option<Self> {
+        let settings = config.notifications.clone()?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        actix_web::rt::spawn(run(config, settings, receiver));
+        Some(Self { sender })
+    }
+
+    pub fn notify(&self, recipient: &str, message: String) {
+        self.send(recipient, MAIL_SUBJECT, message);
+    }
+
+    pub fn alert(&self, recipient: &str, message: String) {
+        self.send(recipient, ALERT_SUBJECT, message);
+    }
+
+    fn send(&self, recipient: &str, subject: &'static str, message: String) {
+        let notice = Notice {
+            recipient: recipient.to_string(),
+            subject,
+            message,
+        };
+        if self.sender.send(notice).is_err() {
+            log::warn!("Notification worker has stopped");
+        }
+    }
+}
+
+async fn run(
+    config: Arc<Config>,
+    settings: NotificationsConfig,
+    mut receiver: UnboundedReceiver<Notice>,
+) {
+    let client = awc::Client::default();
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.batch_secs.max(1)));
+    let mut pending: BTreeMap<(String, &'static str), Vec<String>> = BTreeMap::new();
+
+    loop {
+        tokio::select! {
+            notice = receiver.recv() => match notice {
+                Some(notice) => pending
+                    .entry((notice.recipient, notice.subject))
+                    .or_default()
+                    .push(notice.message),
+                None => break,
+            },
+            _ = interval.tick() => {
+                for ((recipient, subject), messages) in std::mem::take(&mut pending) {
+                    deliver(&client, &config, &settings, &recipient, subject, messages).await;
+                }
+            }
+        }
+    }
+}
+
+async fn deliver(
+    client: &awc::Client,
+    config: &Config,
+    settings: &NotificationsConfig,
+    recipient: &str,
+    subject: &str,
+    messages: Vec<String>,
+) {
+    let Some(user) = config.users.get(recipient) else {
+        return;
+    };
+
+    if let Some(webhook) = &user.webhook {
+        let payload = serde_json::json!({
+            "user": recipient,
+            "subject": subject,
+            "events": &messages,
+        });
+        match client.post(webhook).send_json(&payload).await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => log::warn!(
+                "Webhook for {} responded with {}",
+                recipient,
+                response.status()
+            ),
+            Err(e) => log::warn!("Failed to call webhook for {}: {}", recipient, e),
+        }
+    }
+
+    if let (Some(email), Some(sendmail)) = (&user.email, &settings.sendmail) {
+        let email = email.clone();
+        let sendmail = sendmail.clone();
+        let from = settings.from.clone();
+        let subject = subject.to_string();
+        let body = messages.join("\n");
+        let sent = tokio::task::spawn_blocking(move || {
+            send_mail(&sendmail, from.as_deref(), &email, &subject, &body)
+        })
+        .await;
+        match sent {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::warn!("Failed to email {}: {}", recipient, e),
+            Err(e) => log::warn!("Failed to email {}: {}", recipient, e),
+        }
+    }
+}
+
+fn send_mail(
+    sendmail: &Path,
+    from: Option<&str>,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<()> {
+    let mut message = String::new();
+    if let Some(from) = from {
+        message.push_str(&format!("From: {}\n", from));
+    }
+    message.push_str(&format!(
+        "To: {}\nSubject: {}\nContent-Type: text/plain; charset=utf-8\n\n{}\n",
+        to, subject, body
+    ));
+
+    let mut child = Command::new(sendmail)
+        .arg("-t")
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {:?}", sendmail))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("sendmail stdin unavailable"))?
+        .write_all(message.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow!("sendmail exited with {}", status));
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+pub fn encode(name: &OsStr) -> String {
+    let mut encoded = String::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            if ch == '%' {
+                encoded.push_str("%25");
+            } else {
+                encoded.push(ch);
+            }
+        }
+        for byte in chunk.invalid() {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+pub fn decode(name: &str) -> Option<OsString> {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    if decoded.is_empty()
+        || decoded == b"."
+        || decoded == b".."
+        || decoded.iter().any(|byte| *byte == b'/' || *byte == 0)
+    {
+        return None;
+    }
+    Some(OsString::from_vec(decoded))
+}
+
+pub fn encode_path(path: &Path) -> String {
+    if path.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        path.components()
+            .map(|c| encode(c.as_os_str()))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
@@ -0,0 +1,321 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::config::{
+    Config, MountConfig, NestedMountPolicy, Permission, PermissionRule, PermissionSpec, WILDCARD,
+};
+
+struct CompiledRule {
+    rule: PermissionRule,
+    grants: HashMap<String, Permission>,
+}
+
+struct CompiledMount {
+    public: Option<Permission>,
+    grants: HashMap<String, Permission>,
+    rules: Vec<CompiledRule>,
+    enclosing: Vec<(String, PathBuf)>,
+}
+
+#[derive(Serialize)]
+pub struct Contribution {
+    pub source: String,
+    pub permission: Permission,
+}
+
+#[derive(Default)]
+pub struct PermissionTable {
+    mounts: HashMap<String, CompiledMount>,
+}
+
+impl PermissionTable {
+    pub fn compile(config: &Config) -> Self {
+        let mut mounts: HashMap<String, CompiledMount> = config
+            .mounts
+            .iter()
+            .map(|(name, mount)| (name.clone(), compile_mount(config, mount)))
+            .collect();
+        if config.server.nested_mounts == NestedMountPolicy::Inherit {
+            for nested in config.nested_mounts() {
+                if let Some(mount) = mounts.get_mut(&nested.inner) {
+                    mount.enclosing.push((nested.outer, nested.prefix));
+                }
+            }
+        }
+        Self { mounts }
+    }
+
+    pub fn lookup(&self, mount: &str, username: Option<&str>, target: &Path) -> Option<Permission> {
+        let compiled = self.mounts.get(mount)?;
+        let mut permission = self.lookup_direct(compiled, username, target);
+        for (outer, prefix) in &compiled.enclosing {
+            let limit = self
+                .mounts
+                .get(outer)
+                .and_then(|outer| self.lookup_direct(outer, username, &prefix.join(target)))
+                .unwrap_or_default();
+            permission = permission.map(|permission| permission.restrict_to(&limit));
+        }
+        permission
+    }
+
+    pub fn explain(
+        &self,
+        config: &Config,
+        mount_name: &str,
+        username: Option<&str>,
+        target: &Path,
+    ) -> Vec<Contribution> {
+        let (Some(mount), Some(compiled)) =
+            (config.mounts.get(mount_name), self.mounts.get(mount_name))
+        else {
+            return Vec::new();
+        };
+        let section = format!("mounts.{}", mount_name);
+        let mut contributions = Vec::new();
+        if let Some(public) = &compiled.public {
+            contributions.push(Contribution {
+                source: format!("[{}] public", section),
+                permission: public.clone(),
+            });
+        }
+        if let Some(username) = username {
+            explain_grants(
+                config,
+                &format!("[{}]", section),
+                username,
+                &mount.user,
+                &mount.group,
+                &mut contributions,
+            );
+            let relative = crate::pathbuf_to_string(target);
+            if !target.as_os_str().is_empty() {
+                for (index, rule) in mount
+                    .rules
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rule)| rule.applies_to(&relative))
+                {
+                    explain_grants(
+                        config,
+                        &format!("[[{}.rules]] #{} \"{}\"", section, index + 1, rule.pattern),
+                        username,
+                        &rule.user,
+                        &rule.group,
+                        &mut contributions,
+                    );
+                }
+            }
+        }
+        for (outer, prefix) in &compiled.enclosing {
+            let limit = self
+                .mounts
+                .get(outer)
+                .and_then(|outer| self.lookup_direct(outer, username, &prefix.join(target)))
+                .unwrap_or_default();
+            contributions.push(Contribution {
+                source: format!("limited by enclosing [mounts.{}]", outer),
+                permission: limit,
+            });
+        }
+        contributions
+    }
+
+    fn lookup_direct(
+        &self,
+        mount: &CompiledMount,
+        username: Option<&str>,
+        target: &Path,
+    ) -> Option<Permission> {
+        let mut aggregated = mount.public.clone();
+
+        if let Some(username) = username {
+            if let Some(grant) = mount.grants.get(username) {
+                aggregated = merge_permission(aggregated, grant.clone());
+            }
+
+            if !target.as_os_str().is_empty() && !mount.rules.is_empty() {
+                let target = crate::pathbuf_to_string(target);
+                for rule in mount
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.rule.applies_to(&target))
+                {
+                    if let Some(grant) = rule.grants.get(username) {
+                        aggregated = merge_permission(aggregated, grant.clone());
+                    }
+                }
+            }
+        }
+
+        aggregated
+    }
+}
+
+fn compile_mount(config: &Config, mount: &MountConfig) -> CompiledMount {
+    let public = if mount.public && mount.public_upload {
+        Some(Permission::from_actions(["read", "upload"]))
+    } else if mount.public {
+        Some(Permission::from_actions(["read"]))
+    } else {
+        None
+    };
+
+    CompiledMount {
+        public,
+        grants: compile_grants(config, &mount.user, &mount.group),
+        rules: mount
+            .rules
+            .iter()
+            .map(|rule| CompiledRule {
+                rule: rule.clone(),
+                grants: compile_grants(config, &rule.user, &rule.group),
+            })
+            .collect(),
+        enclosing: Vec::new(),
+    }
+}
+
+fn compile_grants(
+    config: &Config,
+    users: &HashMap<String, PermissionSpec>,
+    groups: &HashMap<String, PermissionSpec>,
+) -> HashMap<String, Permission> {
+    let usernames: BTreeSet<&String> = config.users.keys().chain(users.keys()).collect();
+    usernames
+        .into_iter()
+        .filter(|username| username.as_str() != WILDCARD)
+        .filter_map(|username| {
+            merge_grants(config, username, None, users, groups)
+                .map(|permission| (username.clone(), permission))
+        })
+        .collect()
+}
+
+fn merge_grants(
+    config: &Config,
+    username: &str,
+    mut aggregated: Option<Permission>,
+    users: &HashMap<String, PermissionSpec>,
+    groups: &HashMap<String, PermissionSpec>,
+) -> Option<Permission> {
+    if let Some(spec) = users.get(username) {
+        let resolved = config.resolve_permission_spec(spec);
+        aggregated = merge_permission(aggregated, resolved);
+    }
+
+    if let Some(user_config) = config.users.get(username) {
+        if let Some(spec) = users.get(WILDCARD) {
+            aggregated = merge_permission(aggregated, config.resolve_permission_spec(spec));
+        }
+        if let Some(spec) = groups
+            .get(WILDCARD)
+            .filter(|_| !user_config.group.is_empty())
+        {
+            aggregated = merge_permission(aggregated, config.resolve_permission_spec(spec));
+        }
+        for group in &user_config.group {
+            if let Some(spec) = groups.get(group) {
+                let resolved = config.resolve_permission_spec(spec);
+                aggregated = merge_permission(aggregated, resolved);
+            }
+        }
+    }
+
+    aggregated
+}
+
+fn explain_grants(
+    config: &Config,
+    section: &str,
+    username: &str,
+    users: &HashMap<String, PermissionSpec>,
+    groups: &HashMap<String, PermissionSpec>,
+    contributions: &mut Vec<Contribution>,
+) {
+    let mut grants: Vec<(String, &PermissionSpec)> = Vec::new();
+    if let Some(spec) = users.get(username) {
+        grants.push((format!("user.{}", username), spec));
+    }
+    if let Some(user_config) = config.users.get(username) {
+        if let Some(spec) = users.get(WILDCARD) {
+            grants.push((format!("user.{}", WILDCARD), spec));
+        }
+        if let Some(spec) = groups
+            .get(WILDCARD)
+            .filter(|_| !user_config.group.is_empty())
+        {
+            grants.push((format!("group.{}", WILDCARD), spec));
+        }
+        for group in &user_config.group {
+            if let Some(spec) = groups.get(group) {
+                grants.push((format!("group.{}", group), spec));
+            }
+        }
+    }
+    for (key, spec) in grants {
+        contributions.push(Contribution {
+            source: format!("{} {}", section, key),
+            permission: config.resolve_permission_spec(spec),
+        });
+    }
+}
+
+pub fn merge_permission(current: Option<Permission>, addition: Permission) -> Option<Permission> {
+    if addition.is_empty() {
+        return current;
+    }
+
+    match current {
+        Some(mut existing) => {
+            existing.merge(&addition);
+            Some(existing)
+        }
+        None => Some(addition),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[server]
+host = "127.0.0.1"
+port = 8080
+
+[main_page]
+title = "LunaFinder"
+description = ""
+markdown_file = ""
+
+[user.writer]
+password = ""
+group = []
+hash_algorithm = "plain"
+
+[mounts.wiki]
+path = "/srv/wiki"
+description = ""
+user.writer = "read"
+
+[[mounts.wiki.rules]]
+pattern = "*.md"
+user.writer = ["upload", "modify"]
+"#;
+
+    #[test]
+    fn upload_rules_match_the_uploaded_file_name() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let table = PermissionTable::compile(&config);
+        let allows_upload = |path: &str| {
+            table
+                .lookup("wiki", Some("writer"), Path::new(path))
+                .is_some_and(|permission| permission.allows_upload())
+        };
+        assert!(allows_upload("docs/notes.md"));
+        assert!(!allows_upload("docs/photo.png"));
+        assert!(!allows_upload("docs"));
+    }
+}
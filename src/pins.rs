@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const PINS_FILE: &str = "pinned_mounts.json";
+
+pub enum Change {
+    Pin,
+    Unpin,
+    Up,
+    Down,
+}
+
+pub struct PinStore {
+    path: PathBuf,
+    pins: RwLock<HashMap<String, Vec<String>>>,
+}
+
+impl PinStore {
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(PINS_FILE);
+        let pins = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read pinned mounts: {:?}", path))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse pinned mounts: {:?}", path))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path,
+            pins: RwLock::new(pins),
+        })
+    }
+
+    pub fn get(&self, username: &str) -> Vec<String> {
+        self.pins
+            .read()
+            .ok()
+            .and_then(|pins| pins.get(username).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn apply(&self, username: &str, mount: &str, change: Change) -> Result<()> {
+        let mut pins = self
+            .pins
+            .write()
+            .map_err(|_| anyhow::anyhow!("Pinned mounts lock poisoned"))?;
+        let list = pins.entry(username.to_string()).or_default();
+        let position = list.iter().position(|pinned| pinned == mount);
+        match (change, position) {
+            (Change::Pin, None) => list.push(mount.to_string()),
+            (Change::Unpin, Some(index)) => {
+                list.remove(index);
+            }
+            (Change::Up, Some(index)) if index > 0 => list.swap(index, index - 1),
+            (Change::Down, Some(index)) if index + 1 < list.len() => list.swap(index, index + 1),
+            _ => return Ok(()),
+        }
+        if list.is_empty() {
+            pins.remove(username);
+        }
+        let content = serde_json::to_string_pretty(&*pins)?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to save pinned mounts: {:?}", self.path))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const QUARANTINE_DIR: &str = ".lunafinder_quarantine";
+
+const FILES_DIR: &str = "files";
+const VERDICTS_DIR: &str = "verdicts";
+const VERDICT_EXTENSION: &str = "verdict";
+
+#[derive(Debug, Serialize)]
+pub struct QuarantinedFile {
+    pub path: String,
+    pub size: u64,
+    pub verdict: String,
+}
+
+pub enum ScanOutcome {
+    Clean,
+    Flagged(String),
+}
+
+pub fn scan(command: &[String], file: &Path) -> Result<ScanOutcome> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("Scan command is empty"))?;
+
+    let output = Command::new(program)
+        .args(args)
+        .arg(file)
+        .output()
+        .with_context(|| format!("Failed to run scan command: {}", program))?;
+
+    if output.status.success() {
+        return Ok(ScanOutcome::Clean);
+    }
+
+    let mut verdict = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !stderr.is_empty() {
+        if !verdict.is_empty() {
+            verdict.push('\n');
+        }
+        verdict.push_str(&stderr);
+    }
+    if verdict.is_empty() {
+        verdict = match output.status.code() {
+            Some(code) => format!("Scanner exited with status {}", code),
+            None => "Scanner terminated by signal".to_string(),
+        };
+    }
+
+    Ok(ScanOutcome::Flagged(verdict))
+}
+
+pub fn isolate(base: &Path, relative: &Path, source: &Path, verdict: &str) -> Result<()> {
+    let destination = files_root(base).join(relative);
+    let verdict_path = verdict_file(base, relative);
+    for path in [&destination, &verdict_path] {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+    }
+    fs::rename(source, &destination)
+        .with_context(|| format!("Failed to quarantine file: {:?}", relative))?;
+    fs::write(&verdict_path, verdict)
+        .with_context(|| format!("Failed to record scan verdict: {:?}", relative))?;
+    Ok(())
+}
+
+pub fn list_quarantined(base: &Path) -> Result<Vec<QuarantinedFile>> {
+    let root = files_root(base);
+    let mut files = Vec::new();
+    if root.is_dir() {
+        collect_quarantined(base, &root, Path::new(""), &mut files)?;
+    }
+    files.sort_by_key(|file| file.path.to_lowercase());
+    Ok(files)
+}
+
+fn collect_quarantined(
+    base: &Path,
+    root: &Path,
+    relative: &Path,
+    files: &mut Vec<QuarantinedFile>,
+) -> Result<()> {
+    let current = root.join(relative);
+    for entry in fs::read_dir(&current)
+        .with_context(|| format!("Failed to read directory: {:?}", current))?
+    {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let child_relative = relative.join(entry.file_name());
+        if metadata.is_dir() {
+            collect_quarantined(base, root, &child_relative, files)?;
+        } else {
+            let verdict =
+                fs::read_to_string(verdict_file(base, &child_relative)).unwrap_or_default();
+            files.push(QuarantinedFile {
+                path: crate::osname::encode_path(&child_relative),
+                size: metadata.len(),
+                verdict,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn release(base: &Path, relative: &Path) -> Result<()> {
+    let source = quarantined_file(base, relative)?;
+    let destination = base.join(relative);
+    if destination.exists() {
+        return Err(anyhow!("A file already exists at {:?}", relative));
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+    fs::rename(&source, &destination)
+        .with_context(|| format!("Failed to release quarantined file: {:?}", relative))?;
+    forget_verdict(base, relative);
+    prune_empty_parents(&files_root(base), &source);
+    Ok(())
+}
+
+pub fn delete(base: &Path, relative: &Path) -> Result<()> {
+    let source = quarantined_file(base, relative)?;
+    fs::remove_file(&source)
+        .with_context(|| format!("Failed to delete quarantined file: {:?}", relative))?;
+    forget_verdict(base, relative);
+    prune_empty_parents(&files_root(base), &source);
+    Ok(())
+}
+
+fn files_root(base: &Path) -> PathBuf {
+    base.join(QUARANTINE_DIR).join(FILES_DIR)
+}
+
+fn verdict_file(base: &Path, relative: &Path) -> PathBuf {
+    let mut path = base.join(QUARANTINE_DIR).join(VERDICTS_DIR).join(relative);
+    let file_name = format!(
+        "{}.{}",
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        VERDICT_EXTENSION
+    );
+    path.set_file_name(file_name);
+    path
+}
+
+fn quarantined_file(base: &Path, relative: &Path) -> Result<PathBuf> {
+    if relative.as_os_str().is_empty() {
+        return Err(anyhow!("Quarantined file path is empty"));
+    }
+    let path = files_root(base).join(relative);
+    if !path.is_file() {
+        return Err(anyhow!("Quarantined file not found: {:?}", relative));
+    }
+    Ok(path)
+}
+
+fn forget_verdict(base: &Path, relative: &Path) {
+    let verdict_path = verdict_file(base, relative);
+    if fs::remove_file(&verdict_path).is_ok() {
+        prune_empty_parents(&base.join(QUARANTINE_DIR).join(VERDICTS_DIR), &verdict_path);
+    }
+}
+
+fn prune_empty_parents(root: &Path, file: &Path) {
+    let mut current = file.parent();
+    while let Some(dir) = current {
+        if dir == root || !dir.starts_with(root) || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
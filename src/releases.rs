@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::checksums;
+use crate::storage::Codec;
+use crate::sync::HashCache;
+
+#[derive(Debug, Serialize)]
+pub struct Release {
+    pub version: String,
+    pub modified: u64,
+    pub files: Vec<ReleaseFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReleaseFile {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub url: String,
+}
+
+pub fn collect(
+    base: &Path,
+    codec: Codec,
+    cache: &HashCache,
+    versions: usize,
+) -> Result<Vec<Release>> {
+    let mut folders = Vec::new();
+    for entry in
+        fs::read_dir(base).with_context(|| format!("Failed to read directory: {:?}", base))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if crate::is_reserved_name(&name) || !entry.file_type()?.is_dir() {
+            continue;
+        }
+        folders.push((name, modified(&entry.metadata()?)));
+    }
+    folders.sort_by(|(a, _), (b, _)| compare_versions(b, a));
+    folders.truncate(versions);
+
+    let mut releases = Vec::new();
+    for (version, modified_at) in folders {
+        let directory = base.join(&version);
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&directory)
+            .with_context(|| format!("Failed to read directory: {:?}", directory))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let metadata = entry.metadata()?;
+            if crate::is_reserved_name(&name)
+                || !metadata.is_file()
+                || checksums::is_sidecar(&entry.path())
+            {
+                continue;
+            }
+            let (sha256, size) =
+                cache.content_hash(codec, &entry.path(), metadata.len(), modified(&metadata))?;
+            files.push(ReleaseFile {
+                path: crate::osname::encode_path(&Path::new(&version).join(entry.file_name())),
+                name,
+                size,
+                sha256,
+                url: String::new(),
+            });
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        releases.push(Release {
+            version,
+            modified: modified_at,
+            files,
+        });
+    }
+    Ok(releases)
+}
+
+fn modified(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (version_parts(a).into_iter(), version_parts(b).into_iter());
+    loop {
+        match (a.next(), b.next()) {
+            (Some(x), Some(y)) => {
+                let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    _ => x.cmp(y),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(_), None) => return Ordering::Greater,
+            (None, Some(_)) => return Ordering::Less,
+            (None, None) => return Ordering::Equal,
+        }
+    }
+}
+
+fn version_parts(version: &str) -> Vec<&str> {
+    version
+        .trim_start_matches(['v', 'V'])
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect()
+}
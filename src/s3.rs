@@ -0,0 +1,444 @@
+use actix_web::{
+    http::{header, StatusCode},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::clock;
+use crate::config::{S3Config, S3Key};
+use crate::signing::{constant_time_eq, hex, hmac_sha256};
+
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+pub const DEFAULT_MAX_KEYS: usize = 1000;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const MAX_CLOCK_SKEW_SECS: u64 = 15 * 60;
+const XML_NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+#[derive(Debug)]
+pub struct S3Error {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl S3Error {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn access_denied(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "AccessDenied", message)
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "InvalidArgument", message)
+    }
+
+    pub fn no_such_key() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "NoSuchKey",
+            "The specified key does not exist.",
+        )
+    }
+
+    pub fn internal(error: impl fmt::Display) -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "InternalError",
+            error.to_string(),
+        )
+    }
+}
+
+impl fmt::Display for S3Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl ResponseError for S3Error {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status)
+            .content_type("application/xml")
+            .body(format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>{}</Message></Error>",
+                self.code,
+                xml_escape(&self.message)
+            ))
+    }
+}
+
+pub struct Authorized<'a> {
+    pub key: &'a S3Key,
+    pub payload_hash: String,
+}
+
+pub fn authenticate<'a>(
+    config: &'a S3Config,
+    req: &HttpRequest,
+) -> Result<Authorized<'a>, S3Error> {
+    let authorization = header_value(req, header::AUTHORIZATION.as_str())
+        .ok_or_else(|| S3Error::access_denied("Missing Authorization header"))?;
+    let fields = authorization
+        .strip_prefix(ALGORITHM)
+        .ok_or_else(|| S3Error::access_denied("Only AWS4-HMAC-SHA256 signatures are supported"))?;
+
+    let mut credential = None;
+    let mut signed_headers = None;
+    let mut signature = None;
+    for field in fields.split(',') {
+        match field.trim().split_once('=') {
+            Some(("Credential", value)) => credential = Some(value),
+            Some(("SignedHeaders", value)) => signed_headers = Some(value),
+            Some(("Signature", value)) => signature = Some(value),
+            _ => {}
+        }
+    }
+    let (Some(credential), Some(signed_headers), Some(signature)) =
+        (credential, signed_headers, signature)
+    else {
+        return Err(S3Error::access_denied("Malformed Authorization header"));
+    };
+
+    let (access_key, scope) = credential
+        .split_once('/')
+        .ok_or_else(|| S3Error::access_denied("Malformed credential"))?;
+    let scope_parts: Vec<&str> = scope.split('/').collect();
+    let [date, region, service, "aws4_request"] = scope_parts[..] else {
+        return Err(S3Error::access_denied("Malformed credential scope"));
+    };
+    if service != "s3" {
+        return Err(S3Error::access_denied("Credential scope must target s3"));
+    }
+    let key = config.key(access_key).ok_or_else(|| {
+        S3Error::new(
+            StatusCode::FORBIDDEN,
+            "InvalidAccessKeyId",
+            "The access key does not exist.",
+        )
+    })?;
+
+    let amz_date = header_value(req, "x-amz-date")
+        .ok_or_else(|| S3Error::access_denied("Missing x-amz-date header"))?;
+    let signed_at = clock::parse_amz_date(&amz_date)
+        .ok_or_else(|| S3Error::access_denied("Malformed x-amz-date header"))?;
+    if !amz_date.starts_with(date) || clock::now_unix().abs_diff(signed_at) > MAX_CLOCK_SKEW_SECS {
+        return Err(S3Error::new(
+            StatusCode::FORBIDDEN,
+            "RequestTimeTooSkewed",
+            "The difference between the request time and the server time is too large.",
+        ));
+    }
+
+    let payload_hash = header_value(req, "x-amz-content-sha256")
+        .ok_or_else(|| S3Error::invalid_argument("Missing x-amz-content-sha256 header"))?;
+    if payload_hash.starts_with("STREAMING-") {
+        return Err(S3Error::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "NotImplemented",
+            "Chunked payload signing is not supported.",
+        ));
+    }
+
+    let header_names: Vec<&str> = signed_headers.split(';').collect();
+    if !header_names.contains(&"host") {
+        return Err(S3Error::access_denied("The host header must be signed"));
+    }
+    let mut canonical_headers = String::new();
+    for name in &header_names {
+        let values: Vec<String> = req
+            .headers()
+            .get_all(*name)
+            .filter_map(|value| value.to_str().ok())
+            .map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        canonical_headers.push_str(&format!("{}:{}\n", name, values.join(",")));
+    }
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        req.method().as_str(),
+        canonical_uri(req.uri().path()),
+        canonical_query(req.query_string()),
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{:x}",
+        ALGORITHM,
+        amz_date,
+        scope,
+        Sha256::digest(canonical_request.as_bytes())
+    );
+
+    let mut signing_key = hmac_sha256(format!("AWS4{}", key.secret_key).as_bytes(), date);
+    for part in [region, service, "aws4_request"] {
+        signing_key = hmac_sha256(&signing_key, part);
+    }
+    let expected = hex(&hmac_sha256(&signing_key, &string_to_sign));
+    if !constant_time_eq(expected.as_bytes(), signature.as_bytes()) {
+        return Err(S3Error::new(
+            StatusCode::FORBIDDEN,
+            "SignatureDoesNotMatch",
+            "The request signature we calculated does not match the signature you provided.",
+        ));
+    }
+
+    Ok(Authorized { key, payload_hash })
+}
+
+fn header_value(req: &HttpRequest, name: &str) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+}
+
+fn canonical_uri(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            let decoded = urlencoding::decode(segment)
+                .map(|decoded| decoded.into_owned())
+                .unwrap_or_else(|_| segment.to_string());
+            aws_encode(&decoded)
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn canonical_query(query: &str) -> String {
+    let mut pairs: Vec<(String, String)> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |part: &str| {
+                urlencoding::decode(part)
+                    .map(|decoded| decoded.into_owned())
+                    .unwrap_or_else(|_| part.to_string())
+            };
+            (aws_encode(&decode(name)), aws_encode(&decode(value)))
+        })
+        .collect();
+    pairs.sort();
+    pairs
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn aws_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[derive(Debug)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub modified: u64,
+}
+
+pub fn list_objects(base: &Path, prefix: &str) -> Result<Vec<ObjectInfo>> {
+    let mut objects = Vec::new();
+    collect_objects(base, base, "", prefix, &mut objects)?;
+    objects.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(objects)
+}
+
+fn collect_objects(
+    base: &Path,
+    directory: &Path,
+    key_prefix: &str,
+    prefix: &str,
+    objects: &mut Vec<ObjectInfo>,
+) -> Result<()> {
+    for entry in fs::read_dir(directory)
+        .with_context(|| format!("Failed to read directory: {:?}", directory))?
+    {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if name.starts_with(".lunafinder_") {
+            continue;
+        }
+        let key = format!("{}{}", key_prefix, name);
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            let contained = fs::canonicalize(entry.path())
+                .is_ok_and(|resolved| resolved.starts_with(base) && resolved.is_file());
+            if contained && key.starts_with(prefix) {
+                let metadata = fs::metadata(entry.path())?;
+                objects.push(ObjectInfo {
+                    key,
+                    size: metadata.len(),
+                    modified: modified_unix(&metadata),
+                });
+            }
+        } else if file_type.is_dir() {
+            let dir_key = format!("{}/", key);
+            if prefix.starts_with(&dir_key) || dir_key.starts_with(prefix) {
+                collect_objects(base, &entry.path(), &dir_key, prefix, objects)?;
+            }
+        } else if file_type.is_file() && key.starts_with(prefix) {
+            let metadata = entry.metadata()?;
+            objects.push(ObjectInfo {
+                key,
+                size: metadata.len(),
+                modified: modified_unix(&metadata),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn modified_unix(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn etag(size: u64, modified: u64) -> String {
+    format!("\"{:x}-{:x}\"", modified, size)
+}
+
+pub struct ListRequest<'a> {
+    pub bucket: &'a str,
+    pub prefix: &'a str,
+    pub delimiter: Option<&'a str>,
+    pub max_keys: usize,
+    pub continuation_token: Option<&'a str>,
+    pub start_after: Option<&'a str>,
+}
+
+pub fn decode_continuation_token(token: &str) -> Option<String> {
+    let bytes = URL_SAFE_NO_PAD.decode(token).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+pub fn list_objects_v2(request: &ListRequest, objects: &[ObjectInfo]) -> String {
+    let marker = request
+        .continuation_token
+        .and_then(decode_continuation_token)
+        .or_else(|| request.start_after.map(str::to_string));
+
+    let mut contents = Vec::new();
+    let mut common_prefixes: Vec<String> = Vec::new();
+    let mut truncated = false;
+    let mut last_entry = None;
+    for object in objects
+        .iter()
+        .filter(|object| object.key.starts_with(request.prefix))
+    {
+        let rollup = request.delimiter.filter(|d| !d.is_empty()).and_then(|d| {
+            object.key[request.prefix.len()..]
+                .find(d)
+                .map(|index| object.key[..request.prefix.len() + index + d.len()].to_string())
+        });
+        let entry = rollup.as_deref().unwrap_or(&object.key);
+        if marker.as_deref().is_some_and(|marker| entry <= marker) {
+            continue;
+        }
+        if rollup.is_some() && common_prefixes.last().map(String::as_str) == Some(entry) {
+            continue;
+        }
+        if contents.len() + common_prefixes.len() >= request.max_keys {
+            truncated = true;
+            break;
+        }
+        last_entry = Some(entry.to_string());
+        match rollup {
+            Some(rollup) => common_prefixes.push(rollup),
+            None => contents.push(object),
+        }
+    }
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListBucketResult xmlns=\"{}\">",
+        XML_NAMESPACE
+    );
+    xml.push_str(&element("Name", request.bucket));
+    xml.push_str(&element("Prefix", request.prefix));
+    if let Some(delimiter) = request.delimiter {
+        xml.push_str(&element("Delimiter", delimiter));
+    }
+    xml.push_str(&element("MaxKeys", &request.max_keys.to_string()));
+    xml.push_str(&element(
+        "KeyCount",
+        &(contents.len() + common_prefixes.len()).to_string(),
+    ));
+    xml.push_str(&element("IsTruncated", &truncated.to_string()));
+    if let Some(token) = request.continuation_token {
+        xml.push_str(&element("ContinuationToken", token));
+    }
+    if let Some(start_after) = request.start_after {
+        xml.push_str(&element("StartAfter", start_after));
+    }
+    if let Some(last_entry) = last_entry.filter(|_| truncated) {
+        xml.push_str(&element(
+            "NextContinuationToken",
+            &URL_SAFE_NO_PAD.encode(last_entry),
+        ));
+    }
+    for object in contents {
+        xml.push_str("<Contents>");
+        xml.push_str(&element("Key", &object.key));
+        xml.push_str(&element(
+            "LastModified",
+            &clock::format_iso8601(object.modified),
+        ));
+        xml.push_str(&element("ETag", &etag(object.size, object.modified)));
+        xml.push_str(&element("Size", &object.size.to_string()));
+        xml.push_str(&element("StorageClass", "STANDARD"));
+        xml.push_str("</Contents>");
+    }
+    for prefix in common_prefixes {
+        xml.push_str("<CommonPrefixes>");
+        xml.push_str(&element("Prefix", &prefix));
+        xml.push_str("</CommonPrefixes>");
+    }
+    xml.push_str("</ListBucketResult>");
+    xml
+}
+
+fn element(name: &str, value: &str) -> String {
+    format!("<{}>{}</{}>", name, xml_escape(value), name)
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::gitignore::Rules;
+use crate::{osname, pattern, s3};
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub name: String,
+    pub path: String,
+    pub parent: String,
+    pub is_dir: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    pub mtime: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    pub truncated: bool,
+}
+
+pub struct Matcher {
+    needle: String,
+    glob: bool,
+}
+
+impl Matcher {
+    pub fn new(query: &str) -> Self {
+        let needle = query.trim().to_lowercase();
+        Self {
+            glob: needle.contains(['*', '?']),
+            needle,
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        if self.glob {
+            pattern::matches(&self.needle, &name)
+        } else {
+            name.contains(&self.needle)
+        }
+    }
+}
+
+pub fn find(
+    base: &Path,
+    start: &Path,
+    matcher: &Matcher,
+    max_depth: usize,
+    max_results: usize,
+    gitignore: bool,
+    readable: impl Fn(&Path) -> bool,
+) -> Result<SearchResults> {
+    let mut results = SearchResults::default();
+    let rules = gitignore.then(|| Rules::load(base, start));
+    let mut pending = vec![(start.to_path_buf(), 0, rules)];
+    'walk: while let Some((relative, depth, rules)) = pending.pop() {
+        let directory = base.join(&relative);
+        let listing = match fs::read_dir(&directory) {
+            Ok(listing) => listing,
+            Err(e) if relative == start => {
+                return Err(e).with_context(|| format!("Failed to read directory: {:?}", directory))
+            }
+            Err(_) => continue,
+        };
+        for entry in listing {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let file_name = entry.file_name();
+            if crate::is_reserved_name(&file_name.to_string_lossy()) {
+                continue;
+            }
+            let child = relative.join(&file_name);
+            if !readable(&child) {
+                continue;
+            }
+            let Some(metadata) = crate::contained_metadata(base, &entry) else {
+                continue;
+            };
+            if rules
+                .as_ref()
+                .is_some_and(|rules| rules.is_ignored(&entry.path(), metadata.is_dir()))
+            {
+                continue;
+            }
+            let name = osname::encode(&file_name);
+            if matcher.matches(&name) {
+                if results.hits.len() >= max_results {
+                    results.truncated = true;
+                    break 'walk;
+                }
+                results.hits.push(SearchHit {
+                    path: osname::encode_path(&child),
+                    parent: osname::encode_path(&relative),
+                    name,
+                    is_dir: metadata.is_dir(),
+                    size: metadata.is_file().then_some(metadata.len()),
+                    mtime: s3::modified_unix(&metadata),
+                });
+            }
+            if metadata.is_dir() {
+                if depth + 1 < max_depth {
+                    let child_rules = rules.as_ref().map(|rules| rules.child(&entry.path()));
+                    pending.push((child, depth + 1, child_rules));
+                } else {
+                    results.truncated = true;
+                }
+            }
+        }
+    }
+    results.hits.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(results)
+}
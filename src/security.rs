@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::clock;
+use crate::config::SecurityConfig;
+
+const MAX_TRACKED_SOURCES: usize = 4096;
+
+struct Window {
+    started: u64,
+    count: u32,
+}
+
+#[derive(Default)]
+struct Counter {
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl Counter {
+    fn bump(&self, key: &str, threshold: u32, window_secs: u64) -> Option<u32> {
+        let now = clock::now_unix();
+        let mut windows = self.windows.lock().ok()?;
+        if windows.len() >= MAX_TRACKED_SOURCES {
+            windows.retain(|_, window| window.started + window_secs > now);
+        }
+        let window = windows.entry(key.to_string()).or_insert(Window {
+            started: now,
+            count: 0,
+        });
+        if window.started + window_secs <= now {
+            window.started = now;
+            window.count = 0;
+        }
+        window.count += 1;
+        (window.count == threshold.max(1)).then_some(window.count)
+    }
+}
+
+#[derive(Default)]
+pub struct SecurityMonitor {
+    failed_logins: Counter,
+    denied: Counter,
+}
+
+impl SecurityMonitor {
+    pub fn failed_login(
+        &self,
+        settings: &SecurityConfig,
+        channel: &str,
+        username: &str,
+        source: &str,
+    ) -> Option<String> {
+        let count = self.failed_logins.bump(
+            &format!("{}\0{}", username, source),
+            settings.failed_logins,
+            settings.window_secs,
+        )?;
+        Some(format!(
+            "{} failed {} logins for '{}' from {} within {}s",
+            count, channel, username, source, settings.window_secs
+        ))
+    }
+
+    pub fn denied(&self, settings: &SecurityConfig, subject: &str) -> Option<String> {
+        let count = self
+            .denied
+            .bump(subject, settings.denied, settings.window_secs)?;
+        Some(format!(
+            "{} permission-denied responses for {} within {}s",
+            count, subject, settings.window_secs
+        ))
+    }
+}
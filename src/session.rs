@@ -0,0 +1,51 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::clock;
+use crate::signing::{constant_time_eq, hex, hmac_sha256, load_key};
+
+pub const SESSION_TTL_SECS: u64 = 24 * 60 * 60;
+
+const KEY_FILE: &str = "session.key";
+
+pub struct SessionSigner {
+    key: Vec<u8>,
+}
+
+impl SessionSigner {
+    pub fn load(data_dir: &Path, secret: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            key: load_key(data_dir, KEY_FILE, secret)?,
+        })
+    }
+
+    pub fn issue(&self, username: &str) -> String {
+        let expires = clock::now_unix() + SESSION_TTL_SECS;
+        format!(
+            "{}.{}.{}",
+            urlencoding::encode(username),
+            expires,
+            self.sign(username, expires)
+        )
+    }
+
+    pub fn verify(&self, value: &str) -> Option<String> {
+        let mut parts = value.rsplitn(3, '.');
+        let signature = parts.next()?;
+        let expires = parts.next()?.parse::<u64>().ok()?;
+        let username = urlencoding::decode(parts.next()?).ok()?.into_owned();
+        (expires > clock::now_unix()
+            && constant_time_eq(
+                self.sign(&username, expires).as_bytes(),
+                signature.as_bytes(),
+            ))
+        .then_some(username)
+    }
+
+    fn sign(&self, username: &str, expires: u64) -> String {
+        hex(&hmac_sha256(
+            &self.key,
+            &format!("session\n{}\n{}", username, expires),
+        ))
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use crate::clock;
+
+const KEY_FILE: &str = "url_signing.key";
+const KEY_LEN: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct UrlSigner {
+    key: Vec<u8>,
+}
+
+impl UrlSigner {
+    pub fn load(data_dir: &Path, secret: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            key: load_key(data_dir, KEY_FILE, secret)?,
+        })
+    }
+
+    pub fn derive_key(&self, purpose: &str) -> Vec<u8> {
+        hmac_sha256(&self.key, purpose)
+    }
+
+    pub fn sign(&self, mount: &str, path: &str, expires: u64) -> String {
+        hex(&hmac_sha256(&self.key, &message(mount, path, expires)))
+    }
+
+    pub fn verify(&self, mount: &str, path: &str, expires: u64, signature: &str) -> bool {
+        expires > clock::now_unix()
+            && constant_time_eq(
+                self.sign(mount, path, expires).as_bytes(),
+                signature.as_bytes(),
+            )
+    }
+}
+
+pub fn load_key(data_dir: &Path, file: &str, secret: Option<&str>) -> Result<Vec<u8>> {
+    if let Some(secret) = secret {
+        return Ok(secret.as_bytes().to_vec());
+    }
+
+    let path = data_dir.join(file);
+    if path.exists() {
+        return fs::read(&path).with_context(|| format!("Failed to read key: {:?}", path));
+    }
+
+    let mut key = vec![0u8; KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut key);
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| file.write_all(&key))
+        .with_context(|| format!("Failed to save key: {:?}", path))?;
+    Ok(key)
+}
+
+fn message(mount: &str, path: &str, expires: u64) -> String {
+    format!("{}\n{}\n{}", mount, path, expires)
+}
+
+pub fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const STATS_FILE: &str = "stats.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Download,
+    Upload,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessEvent {
+    pub timestamp: u64,
+    pub mount: String,
+    pub kind: EventKind,
+    pub username: Option<String>,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MountStats {
+    pub mount: String,
+    pub downloads: u64,
+    pub uploads: u64,
+    pub unique_users: usize,
+    pub bytes_downloaded: u64,
+    pub bytes_uploaded: u64,
+}
+
+pub struct StatsLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl StatsLog {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(STATS_FILE),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn record(&self, event: &AccessEvent) -> Result<()> {
+        let line = serde_json::to_string(event)?;
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Stats lock poisoned"))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open stats log: {:?}", self.path))?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    pub fn summarize(&self, from: Option<u64>, until: Option<u64>) -> Result<Vec<MountStats>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read stats log: {:?}", self.path))
+            }
+        };
+
+        let mut summaries: BTreeMap<String, (MountStats, HashSet<String>)> = BTreeMap::new();
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let event: AccessEvent = match serde_json::from_str(line) {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Skipping malformed stats entry: {}", e);
+                    continue;
+                }
+            };
+            if from.is_some_and(|from| event.timestamp < from)
+                || until.is_some_and(|until| event.timestamp >= until)
+            {
+                continue;
+            }
+
+            let (stats, users) = summaries.entry(event.mount.clone()).or_default();
+            stats.mount = event.mount;
+            match event.kind {
+                EventKind::Download => {
+                    stats.downloads += 1;
+                    stats.bytes_downloaded += event.bytes;
+                }
+                EventKind::Upload => {
+                    stats.uploads += 1;
+                    stats.bytes_uploaded += event.bytes;
+                }
+            }
+            if let Some(username) = event.username {
+                users.insert(username);
+            }
+        }
+
+        Ok(summaries
+            .into_values()
+            .map(|(mut stats, users)| {
+                stats.unique_users = users.len();
+                stats
+            })
+            .collect())
+    }
+}
+
+pub fn to_csv(stats: &[MountStats]) -> String {
+    let mut csv =
+        String::from("mount,downloads,uploads,unique_users,bytes_downloaded,bytes_uploaded\n");
+    for row in stats {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            csv_field(&row.mount),
+            row.downloads,
+            row.uploads,
+            row.unique_users,
+            row.bytes_downloaded,
+            row.bytes_uploaded
+        ));
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,268 @@
+use actix_web::web::Bytes;
+use anyhow::{Context, Result};
+use futures_util::Stream;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::crypto::{self, EncryptingWriter, MountKey};
+
+const COMPRESSED_MAGIC: &[u8; 8] = b"LFZST\x00\x00\x01";
+const COMPRESSION_LEVEL: i32 = 3;
+const READ_CHUNK: u64 = 64 * 1024;
+const READ_QUEUE_CHUNKS: usize = 4;
+const INCOMPRESSIBLE_EXTENSIONS: [&str; 36] = [
+    "7z", "aac", "apk", "avi", "avif", "br", "bz2", "docx", "flac", "gif", "gz", "heic", "jar",
+    "jpeg", "jpg", "lz4", "m4a", "m4v", "mkv", "mov", "mp3", "mp4", "odp", "ods", "odt", "ogg",
+    "opus", "pdf", "png", "pptx", "rar", "tgz", "webm", "webp", "xlsx", "zst",
+];
+
+#[derive(Clone, Copy)]
+pub struct Codec<'a> {
+    pub key: Option<&'a MountKey>,
+    pub compress: bool,
+}
+
+#[derive(Clone)]
+pub struct OwnedCodec {
+    key: Option<MountKey>,
+    compress: bool,
+}
+
+impl Codec<'_> {
+    pub fn into_owned(self) -> OwnedCodec {
+        OwnedCodec {
+            key: self.key.cloned(),
+            compress: self.compress,
+        }
+    }
+}
+
+impl OwnedCodec {
+    pub fn as_codec(&self) -> Codec<'_> {
+        Codec {
+            key: self.key.as_ref(),
+            compress: self.compress,
+        }
+    }
+}
+
+pub fn should_compress(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    !INCOMPRESSIBLE_EXTENSIONS.contains(&extension.as_str())
+}
+
+enum Sink {
+    Plain(fs::File),
+    Encrypted(EncryptingWriter<fs::File>),
+}
+
+impl Sink {
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(mut file) => file.flush(),
+            Self::Encrypted(writer) => writer.finish().and_then(|mut file| file.flush()),
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.write(buf),
+            Self::Encrypted(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.flush(),
+            Self::Encrypted(writer) => writer.flush(),
+        }
+    }
+}
+
+enum Layer {
+    Raw(Sink),
+    Compressed(zstd::stream::write::Encoder<'static, Sink>),
+}
+
+pub struct FileWriter {
+    layer: Layer,
+}
+
+impl FileWriter {
+    pub fn create(codec: Codec, path: &Path) -> io::Result<Self> {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let file = fs::File::create(path)?;
+        let mut sink = match codec.key {
+            Some(key) => Sink::Encrypted(EncryptingWriter::new(key, file)?),
+            None => Sink::Plain(file),
+        };
+        let layer = if codec.compress && should_compress(path) {
+            sink.write_all(COMPRESSED_MAGIC)?;
+            Layer::Compressed(zstd::stream::write::Encoder::new(sink, COMPRESSION_LEVEL)?)
+        } else {
+            Layer::Raw(sink)
+        };
+        Ok(Self { layer })
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self.layer {
+            Layer::Raw(sink) => sink.finish(),
+            Layer::Compressed(encoder) => encoder.finish()?.finish(),
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.layer {
+            Layer::Raw(sink) => sink.write(buf),
+            Layer::Compressed(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.layer {
+            Layer::Raw(sink) => sink.flush(),
+            Layer::Compressed(encoder) => encoder.flush(),
+        }
+    }
+}
+
+pub struct QueuedWriter {
+    sender: mpsc::Sender<Bytes>,
+    task: JoinHandle<io::Result<()>>,
+}
+
+impl QueuedWriter {
+    pub fn spawn(mut writer: FileWriter, depth: usize) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<Bytes>(depth.max(1));
+        let task = tokio::task::spawn_blocking(move || {
+            while let Some(chunk) = receiver.blocking_recv() {
+                writer.write_all(&chunk)?;
+            }
+            writer.finish()
+        });
+        Self { sender, task }
+    }
+
+    pub async fn write(&mut self, chunk: Bytes) -> io::Result<()> {
+        if self.sender.send(chunk).await.is_ok() {
+            return Ok(());
+        }
+        Err(match (&mut self.task).await {
+            Ok(Err(e)) => e,
+            Ok(Ok(())) => io::Error::new(io::ErrorKind::BrokenPipe, "Upload writer stopped"),
+            Err(e) => io::Error::other(e),
+        })
+    }
+
+    pub async fn finish(self) -> io::Result<()> {
+        drop(self.sender);
+        self.task.await.map_err(io::Error::other)?
+    }
+}
+
+pub fn queued_reader(
+    mut reader: Box<dyn Read + Send>,
+    skip: u64,
+    length: u64,
+) -> impl Stream<Item = io::Result<Bytes>> {
+    let (sender, receiver) = mpsc::channel(READ_QUEUE_CHUNKS);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = io::copy(&mut (&mut reader).take(skip), &mut io::sink()) {
+            let _ = sender.blocking_send(Err(e));
+            return;
+        }
+        let mut remaining = length;
+        while remaining > 0 {
+            let mut buffer = vec![0u8; READ_CHUNK.min(remaining) as usize];
+            let chunk = match reader.read(&mut buffer) {
+                Ok(0) => return,
+                Ok(read) => {
+                    buffer.truncate(read);
+                    remaining -= read as u64;
+                    Ok(Bytes::from(buffer))
+                }
+                Err(e) => Err(e),
+            };
+            let failed = chunk.is_err();
+            if sender.blocking_send(chunk).is_err() || failed {
+                return;
+            }
+        }
+    });
+    futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let chunk = receiver.recv().await?;
+        Some((chunk, receiver))
+    })
+}
+
+pub fn persist(staged: &Path, destination: &Path) -> io::Result<()> {
+    if fs::rename(staged, destination).is_ok() {
+        return Ok(());
+    }
+    fs::copy(staged, destination)?;
+    fs::remove_file(staged)
+}
+
+pub fn is_encoded(codec: Codec, path: &Path) -> bool {
+    if codec.key.is_some() && crypto::is_encrypted(path) {
+        return true;
+    }
+    if !codec.compress {
+        return false;
+    }
+    let mut magic = [0u8; COMPRESSED_MAGIC.len()];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == COMPRESSED_MAGIC
+}
+
+pub fn open_reader(codec: Codec, path: &Path) -> Result<(Box<dyn Read + Send>, Option<u64>)> {
+    let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let (mut reader, size): (Box<dyn Read + Send>, u64) = match codec.key {
+        Some(key) if crypto::is_encrypted(path) => {
+            let (reader, size) = crypto::decrypting_reader(key, file)?;
+            (Box::new(reader), size)
+        }
+        _ => {
+            let size = file.metadata()?.len();
+            (Box::new(file), size)
+        }
+    };
+    if !codec.compress {
+        return Ok((reader, Some(size)));
+    }
+
+    let mut head = Vec::with_capacity(COMPRESSED_MAGIC.len());
+    (&mut reader)
+        .take(COMPRESSED_MAGIC.len() as u64)
+        .read_to_end(&mut head)?;
+    if head == COMPRESSED_MAGIC {
+        let decoder = zstd::stream::read::Decoder::new(reader)?;
+        return Ok((Box::new(decoder), None));
+    }
+    Ok((Box::new(io::Cursor::new(head).chain(reader)), Some(size)))
+}
+
+pub fn decode_file(codec: Codec, source: &Path, destination: &Path) -> Result<()> {
+    let (mut reader, _) = open_reader(codec, source)?;
+    let mut file = fs::File::create(destination)
+        .with_context(|| format!("Failed to create {:?}", destination))?;
+    io::copy(&mut reader, &mut file).with_context(|| format!("Failed to decode {:?}", source))?;
+    Ok(())
+}
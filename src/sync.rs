@@ -0,0 +1,274 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+use crate::storage::{self, Codec};
+use crate::{clock, duplicates};
+
+const JOURNAL_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Serialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    pub mtime: u64,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    #[serde(skip)]
+    pub seq: u64,
+    #[serde(skip)]
+    pub mount: String,
+    pub action: String,
+    pub path: String,
+}
+
+#[derive(Default)]
+pub struct HashCache {
+    hashes: Mutex<HashMap<PathBuf, (u64, u64, String)>>,
+    contents: Mutex<HashMap<PathBuf, (u64, u64, String, u64)>>,
+}
+
+pub fn manifest(base: &Path, cache: &HashCache) -> Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+    collect(base, Path::new(""), cache, &mut entries)?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+fn collect(
+    base: &Path,
+    relative: &Path,
+    cache: &HashCache,
+    entries: &mut Vec<ManifestEntry>,
+) -> Result<()> {
+    let current = base.join(relative);
+    let listing = match fs::read_dir(&current) {
+        Ok(listing) => listing,
+        Err(e) if relative.as_os_str().is_empty() => {
+            return Err(e).with_context(|| format!("Failed to read directory: {:?}", current))
+        }
+        Err(e) => {
+            log::debug!("Skipping {:?}: {}", current, e);
+            return Ok(());
+        }
+    };
+    for entry in listing {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with(".lunafinder_") {
+            continue;
+        }
+        let child_relative = relative.join(&name);
+        let Some(metadata) = crate::contained_metadata(base, &entry) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            collect(base, &child_relative, cache, entries)?;
+            continue;
+        }
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let size = metadata.len();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let hash = match cache.hash(&entry.path(), size, mtime) {
+            Ok(hash) => hash,
+            Err(e) => {
+                log::debug!("Skipping {:?}: {}", entry.path(), e);
+                continue;
+            }
+        };
+        entries.push(ManifestEntry {
+            path: crate::osname::encode_path(&child_relative),
+            size,
+            mtime,
+            hash,
+        });
+    }
+    Ok(())
+}
+
+impl HashCache {
+    fn hash(&self, path: &Path, size: u64, mtime: u64) -> Result<String> {
+        if let Ok(hashes) = self.hashes.lock() {
+            if let Some((cached_size, cached_mtime, hash)) = hashes.get(path) {
+                if *cached_size == size && *cached_mtime == mtime {
+                    return Ok(hash.clone());
+                }
+            }
+        }
+        let hash = duplicates::hash_file(path)?;
+        if let Ok(mut hashes) = self.hashes.lock() {
+            hashes.insert(path.to_path_buf(), (size, mtime, hash.clone()));
+        }
+        Ok(hash)
+    }
+
+    pub fn content_hash(
+        &self,
+        codec: Codec,
+        path: &Path,
+        size: u64,
+        mtime: u64,
+    ) -> Result<(String, u64)> {
+        if !storage::is_encoded(codec, path) {
+            return Ok((self.hash(path, size, mtime)?, size));
+        }
+        if let Ok(contents) = self.contents.lock() {
+            if let Some((cached_size, cached_mtime, hash, length)) = contents.get(path) {
+                if *cached_size == size && *cached_mtime == mtime {
+                    return Ok((hash.clone(), *length));
+                }
+            }
+        }
+        let (mut reader, _) = storage::open_reader(codec, path)?;
+        let mut hasher = Sha256::new();
+        let length = std::io::copy(&mut reader, &mut hasher)
+            .with_context(|| format!("Failed to hash {:?}", path))?;
+        let hash = format!("{:x}", hasher.finalize());
+        if let Ok(mut contents) = self.contents.lock() {
+            contents.insert(path.to_path_buf(), (size, mtime, hash.clone(), length));
+        }
+        Ok((hash, length))
+    }
+}
+
+pub struct ChangeJournal {
+    epoch: u64,
+    state: Mutex<(u64, VecDeque<Change>)>,
+    notify: Notify,
+}
+
+impl Default for ChangeJournal {
+    fn default() -> Self {
+        Self {
+            epoch: clock::now_unix(),
+            state: Mutex::new((0, VecDeque::new())),
+            notify: Notify::new(),
+        }
+    }
+}
+
+impl ChangeJournal {
+    pub fn record(&self, mount: &str, action: &str, path: &str) {
+        {
+            let Ok(mut state) = self.state.lock() else {
+                return;
+            };
+            let (next, changes) = &mut *state;
+            *next += 1;
+            changes.push_back(Change {
+                seq: *next,
+                mount: mount.to_string(),
+                action: action.to_string(),
+                path: path.to_string(),
+            });
+            if changes.len() > JOURNAL_CAPACITY {
+                changes.pop_front();
+            }
+        }
+        self.notify.notify_waiters();
+    }
+
+    pub fn cursor(&self) -> String {
+        let seq = self.state.lock().map(|state| state.0).unwrap_or(0);
+        self.format_cursor(seq)
+    }
+
+    pub fn since(&self, mount: &str, cursor: &str) -> Option<(Vec<Change>, String)> {
+        let (epoch, seq) = cursor.split_once('-')?;
+        if epoch.parse::<u64>().ok()? != self.epoch {
+            return None;
+        }
+        let seq = seq.parse::<u64>().ok()?;
+
+        let state = self.state.lock().ok()?;
+        let (latest, changes) = &*state;
+        let oldest = changes
+            .front()
+            .map(|change| change.seq)
+            .unwrap_or(latest + 1);
+        if seq > *latest || seq + 1 < oldest {
+            return None;
+        }
+        let changes = changes
+            .iter()
+            .filter(|change| change.seq > seq && change.mount == mount)
+            .cloned()
+            .collect();
+        Some((changes, self.format_cursor(*latest)))
+    }
+
+    pub async fn wait_since(
+        &self,
+        mount: &str,
+        cursor: &str,
+        timeout: Duration,
+    ) -> Option<(Vec<Change>, String)> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let (changes, latest) = self.since(mount, cursor)?;
+            if !changes.is_empty() {
+                return Some((changes, latest));
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return Some((changes, latest));
+            }
+        }
+    }
+
+    fn format_cursor(&self, seq: u64) -> String {
+        format!("{}-{}", self.epoch, seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_skips_escaping_and_broken_symlinks() {
+        let root = std::env::temp_dir().join(format!("lunafinder-sync-{}", std::process::id()));
+        let (base, outside) = (root.join("mount"), root.join("outside"));
+        fs::create_dir_all(base.join("docs")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(base.join("docs/inside.txt"), b"inside").unwrap();
+        fs::write(outside.join("secret.txt"), b"secret").unwrap();
+        std::os::unix::fs::symlink(&outside, base.join("escape")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), base.join("secret.txt")).unwrap();
+        std::os::unix::fs::symlink(base.join("missing"), base.join("broken")).unwrap();
+        std::os::unix::fs::symlink(&base, base.join("docs/loop")).unwrap();
+
+        let base = fs::canonicalize(&base).unwrap();
+        let entries = manifest(&base, &HashCache::default());
+        fs::remove_dir_all(&root).unwrap();
+
+        let paths = entries
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["docs/inside.txt".to_string()]);
+    }
+}
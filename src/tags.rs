@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const TAGS_FILE: &str = "file_tags.json";
+pub const MAX_TAGS: usize = 32;
+pub const MAX_TAG_LEN: usize = 64;
+
+pub struct TagStore {
+    path: PathBuf,
+    tags: RwLock<HashMap<String, BTreeMap<String, Vec<String>>>>,
+}
+
+impl TagStore {
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(TAGS_FILE);
+        let tags = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read file tags: {:?}", path))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse file tags: {:?}", path))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path,
+            tags: RwLock::new(tags),
+        })
+    }
+
+    pub fn get(&self, mount: &str, path: &str) -> Vec<String> {
+        self.tags
+            .read()
+            .ok()
+            .and_then(|tags| tags.get(mount)?.get(path).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn set(&self, mount: &str, path: &str, tags: Vec<String>) -> Result<()> {
+        let mut all = self
+            .tags
+            .write()
+            .map_err(|_| anyhow::anyhow!("File tags lock poisoned"))?;
+        let entries = all.entry(mount.to_string()).or_default();
+        if tags.is_empty() {
+            entries.remove(path);
+        } else {
+            entries.insert(path.to_string(), tags);
+        }
+        if entries.is_empty() {
+            all.remove(mount);
+        }
+        let content = serde_json::to_string_pretty(&*all)?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to save file tags: {:?}", self.path))
+    }
+}
+
+pub fn normalize(tags: &[String]) -> Option<Vec<String>> {
+    let mut normalized = tags
+        .iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect::<Vec<_>>();
+    normalized.sort();
+    normalized.dedup();
+    let valid = normalized.len() <= MAX_TAGS
+        && normalized
+            .iter()
+            .all(|tag| tag.chars().count() <= MAX_TAG_LEN && !tag.chars().any(char::is_control));
+    valid.then_some(normalized)
+}
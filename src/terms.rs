@@ -0,0 +1,49 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+const ACCEPTANCE_FILE: &str = "terms_acceptance.json";
+
+pub struct TermsAcceptance {
+    path: PathBuf,
+    accepted: RwLock<HashMap<String, String>>,
+}
+
+impl TermsAcceptance {
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(ACCEPTANCE_FILE);
+        let accepted = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read terms acceptance: {:?}", path))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse terms acceptance: {:?}", path))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path,
+            accepted: RwLock::new(accepted),
+        })
+    }
+
+    pub fn has_accepted(&self, username: &str, version: &str) -> bool {
+        self.accepted
+            .read()
+            .map(|accepted| accepted.get(username).map(String::as_str) == Some(version))
+            .unwrap_or(false)
+    }
+
+    pub fn accept(&self, username: &str, version: &str) -> Result<()> {
+        let mut accepted = self
+            .accepted
+            .write()
+            .map_err(|_| anyhow::anyhow!("Terms acceptance lock poisoned"))?;
+        accepted.insert(username.to_string(), version.to_string());
+        let content = serde_json::to_string_pretty(&*accepted)?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to save terms acceptance: {:?}", self.path))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+pub const SIZES: [u32; 4] = [64, 128, 256, 512];
+pub const DEFAULT_SIZE: u32 = 256;
+pub const MAX_SOURCE_SIZE: u64 = 64 * 1024 * 1024;
+const CACHE_DIR: &str = "thumbnails";
+const MAX_DIMENSION: u32 = 16_384;
+const MAX_ALLOC: u64 = 512 * 1024 * 1024;
+
+pub struct Thumbnail {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+}
+
+pub struct ThumbCache {
+    root: PathBuf,
+}
+
+impl ThumbCache {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            root: data_dir.join(CACHE_DIR),
+        }
+    }
+
+    pub fn cached(
+        &self,
+        mount: &str,
+        relative: &Path,
+        version: &str,
+        size: u32,
+        format: ImageFormat,
+    ) -> Option<Thumbnail> {
+        let path = self
+            .directory(mount, relative)
+            .join(file_name(version, size, format));
+        let bytes = fs::read(path).ok()?;
+        Some(Thumbnail {
+            bytes,
+            content_type: content_type(format),
+        })
+    }
+
+    pub fn store(
+        &self,
+        mount: &str,
+        relative: &Path,
+        version: &str,
+        size: u32,
+        format: ImageFormat,
+        thumbnail: &Thumbnail,
+    ) -> Result<()> {
+        let directory = self.directory(mount, relative);
+        fs::create_dir_all(&directory)
+            .with_context(|| format!("Failed to create thumbnail cache: {:?}", directory))?;
+        let name = file_name(version, size, format);
+        let prefix = format!("{}-", size);
+        for entry in fs::read_dir(&directory)?.flatten() {
+            let stale = entry.file_name();
+            let stale = stale.to_string_lossy();
+            if stale.starts_with(&prefix) && stale != name {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+        let temp = directory.join(format!(".{}.tmp", name));
+        fs::write(&temp, &thumbnail.bytes)
+            .with_context(|| format!("Failed to write thumbnail: {:?}", temp))?;
+        fs::rename(&temp, directory.join(&name))
+            .with_context(|| format!("Failed to store thumbnail: {:?}", name))
+    }
+
+    fn directory(&self, mount: &str, relative: &Path) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(mount.as_bytes());
+        hasher.update([0]);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        self.root
+            .join(crate::signing::hex(&hasher.finalize()[..16]))
+    }
+}
+
+pub fn size_bucket(requested: Option<u32>) -> u32 {
+    let requested = requested.unwrap_or(DEFAULT_SIZE);
+    SIZES
+        .iter()
+        .copied()
+        .find(|size| *size >= requested)
+        .unwrap_or(SIZES[SIZES.len() - 1])
+}
+
+pub fn source_format(path: &Path) -> Option<ImageFormat> {
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+        "png" => Some(ImageFormat::Png),
+        "webp" => Some(ImageFormat::WebP),
+        "gif" => Some(ImageFormat::Gif),
+        _ => None,
+    }
+}
+
+pub fn render(reader: impl Read, format: ImageFormat, size: u32) -> Result<Option<Thumbnail>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(MAX_SOURCE_SIZE)
+        .read_to_end(&mut bytes)
+        .context("Failed to read image")?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_ALLOC);
+    let mut image_reader = ImageReader::with_format(Cursor::new(bytes), format);
+    image_reader.limits(limits);
+    let image = match image_reader.decode() {
+        Ok(image) => image,
+        Err(e) => {
+            log::debug!("Failed to decode image for a thumbnail: {}", e);
+            return Ok(None);
+        }
+    };
+
+    let output = output_format(format);
+    let thumbnail = image.thumbnail(size, size);
+    let thumbnail = if output == ImageFormat::Jpeg {
+        DynamicImage::ImageRgb8(thumbnail.to_rgb8())
+    } else {
+        thumbnail
+    };
+    let mut encoded = Cursor::new(Vec::new());
+    thumbnail
+        .write_to(&mut encoded, output)
+        .context("Failed to encode thumbnail")?;
+    Ok(Some(Thumbnail {
+        bytes: encoded.into_inner(),
+        content_type: content_type(format),
+    }))
+}
+
+fn output_format(source: ImageFormat) -> ImageFormat {
+    if source == ImageFormat::Jpeg {
+        ImageFormat::Jpeg
+    } else {
+        ImageFormat::Png
+    }
+}
+
+fn content_type(source: ImageFormat) -> &'static str {
+    if output_format(source) == ImageFormat::Jpeg {
+        "image/jpeg"
+    } else {
+        "image/png"
+    }
+}
+
+fn file_name(version: &str, size: u32, source: ImageFormat) -> String {
+    let digest = Sha256::digest(version.as_bytes());
+    let extension = if output_format(source) == ImageFormat::Jpeg {
+        "jpg"
+    } else {
+        "png"
+    };
+    format!(
+        "{}-{}.{}",
+        size,
+        crate::signing::hex(&digest[..8]),
+        extension
+    )
+}
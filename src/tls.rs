@@ -0,0 +1,25 @@
+use anyhow::{anyhow, Context, Result};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::config::TlsConfig;
+
+pub fn load_server_config(tls: &TlsConfig) -> Result<rustls::ServerConfig> {
+    let cert_file = File::open(&tls.cert_file)
+        .with_context(|| format!("Failed to open TLS certificate: {:?}", tls.cert_file))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<Vec<CertificateDer<'static>>, _>>()
+        .with_context(|| format!("Failed to parse TLS certificate: {:?}", tls.cert_file))?;
+
+    let key_file = File::open(&tls.key_file)
+        .with_context(|| format!("Failed to open TLS private key: {:?}", tls.key_file))?;
+    let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+        .with_context(|| format!("Failed to parse TLS private key: {:?}", tls.key_file))?
+        .ok_or_else(|| anyhow!("No private key found in {:?}", tls.key_file))?;
+
+    rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("Invalid TLS certificate or private key")
+}
@@ -0,0 +1,273 @@
+use anyhow::{anyhow, Context, Result};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::{clock, signing};
+
+const TOKENS_FILE: &str = "guest_tokens.json";
+const TOKENS_TEMP_FILE: &str = "guest_tokens.json.tmp";
+const TOKEN_LEN: usize = 32;
+const MAX_REFERRERS: usize = 50;
+const MAX_VISITORS: usize = 10_000;
+const VISITOR_HASH_LEN: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestToken {
+    pub token: String,
+    pub mount: String,
+    pub path: PathBuf,
+    pub actions: Vec<String>,
+    pub expires_at: u64,
+    pub created_by: String,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub hits: u64,
+    #[serde(default)]
+    pub analytics: ShareAnalytics,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drop: Option<DropBox>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DropBox {
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub received: u64,
+}
+
+impl DropBox {
+    pub fn allows_name(&self, name: &str) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+        Path::new(name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                self.extensions
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(extension))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShareAnalytics {
+    #[serde(default)]
+    pub visitors: BTreeSet<String>,
+    #[serde(default)]
+    pub referrers: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub bytes_served: u64,
+}
+
+impl GuestToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= clock::now_unix()
+    }
+}
+
+pub struct TokenStore {
+    path: PathBuf,
+    visitor_key: Vec<u8>,
+    tokens: RwLock<HashMap<String, GuestToken>>,
+    dirty: AtomicBool,
+}
+
+impl TokenStore {
+    pub fn load(data_dir: &Path, visitor_key: Vec<u8>) -> Result<Self> {
+        let path = data_dir.join(TOKENS_FILE);
+        let tokens = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read guest tokens: {:?}", path))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse guest tokens: {:?}", path))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path,
+            visitor_key,
+            tokens: RwLock::new(tokens),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    pub fn get(&self, token: &str) -> Option<GuestToken> {
+        let tokens = self.tokens.read().ok()?;
+        tokens
+            .get(token)
+            .filter(|guest| !guest.is_expired())
+            .cloned()
+    }
+
+    pub fn mint(
+        &self,
+        mount: &str,
+        path: &Path,
+        actions: Vec<String>,
+        expires_at: u64,
+        created_by: &str,
+        drop: Option<DropBox>,
+    ) -> Result<GuestToken> {
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LEN)
+            .map(char::from)
+            .collect::<String>();
+        let guest = GuestToken {
+            token: token.clone(),
+            mount: mount.to_string(),
+            path: path.to_path_buf(),
+            actions,
+            expires_at,
+            created_by: created_by.to_string(),
+            created_at: clock::now_unix(),
+            hits: 0,
+            analytics: ShareAnalytics::default(),
+            drop,
+        };
+
+        let mut tokens = self
+            .tokens
+            .write()
+            .map_err(|_| anyhow!("Guest token lock poisoned"))?;
+        tokens.retain(|_, existing| !existing.is_expired());
+        tokens.insert(token, guest.clone());
+        self.persist(&tokens)?;
+        Ok(guest)
+    }
+
+    pub fn list(&self, created_by: Option<&str>) -> Vec<GuestToken> {
+        let Ok(tokens) = self.tokens.read() else {
+            return Vec::new();
+        };
+        let mut listed = tokens
+            .values()
+            .filter(|guest| created_by.is_none_or(|owner| guest.created_by == owner))
+            .cloned()
+            .collect::<Vec<_>>();
+        listed.sort_by_key(|guest| Reverse(guest.created_at));
+        listed
+    }
+
+    pub fn record_hit(
+        &self,
+        token: &str,
+        visitor: Option<&str>,
+        referrer: Option<&str>,
+    ) -> Result<u64> {
+        let mut tokens = self
+            .tokens
+            .write()
+            .map_err(|_| anyhow!("Guest token lock poisoned"))?;
+        let Some(guest) = tokens.get_mut(token) else {
+            return Ok(0);
+        };
+        guest.hits += 1;
+        if let Some(visitor) = visitor {
+            let visitors = &mut guest.analytics.visitors;
+            if visitors.len() < MAX_VISITORS {
+                visitors.insert(visitor_hash(&self.visitor_key, token, visitor));
+            }
+        }
+        if let Some(referrer) = referrer {
+            let referrers = &mut guest.analytics.referrers;
+            if referrers.contains_key(referrer) || referrers.len() < MAX_REFERRERS {
+                *referrers.entry(referrer.to_string()).or_default() += 1;
+            }
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(guest.hits)
+    }
+
+    pub fn record_bytes(&self, token: &str, bytes: u64) -> Result<()> {
+        let mut tokens = self
+            .tokens
+            .write()
+            .map_err(|_| anyhow!("Guest token lock poisoned"))?;
+        let Some(guest) = tokens.get_mut(token) else {
+            return Ok(());
+        };
+        guest.analytics.bytes_served += bytes;
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn record_drop(&self, token: &str) -> Result<u64> {
+        let mut tokens = self
+            .tokens
+            .write()
+            .map_err(|_| anyhow!("Guest token lock poisoned"))?;
+        let Some(drop) = tokens.get_mut(token).and_then(|guest| guest.drop.as_mut()) else {
+            return Ok(0);
+        };
+        drop.received += 1;
+        let received = drop.received;
+        self.persist(&tokens)?;
+        Ok(received)
+    }
+
+    pub fn revoke(&self, token: &str, created_by: Option<&str>) -> Result<bool> {
+        let mut tokens = self
+            .tokens
+            .write()
+            .map_err(|_| anyhow!("Guest token lock poisoned"))?;
+        let owned = tokens
+            .get(token)
+            .is_some_and(|guest| created_by.is_none_or(|owner| guest.created_by == owner));
+        if !owned {
+            return Ok(false);
+        }
+        tokens.remove(token);
+        self.persist(&tokens)?;
+        Ok(true)
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        if !self.dirty.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let tokens = self
+            .tokens
+            .read()
+            .map_err(|_| anyhow!("Guest token lock poisoned"))?;
+        self.persist(&tokens)
+    }
+
+    fn persist(&self, tokens: &HashMap<String, GuestToken>) -> Result<()> {
+        self.dirty.store(false, Ordering::Relaxed);
+        let content = serde_json::to_string_pretty(tokens)?;
+        let temp_path = self.path.with_file_name(TOKENS_TEMP_FILE);
+        let _ = fs::remove_file(&temp_path);
+        let saved = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&temp_path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .and_then(|_| fs::rename(&temp_path, &self.path))
+            .with_context(|| format!("Failed to save guest tokens: {:?}", self.path));
+        if saved.is_err() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        saved
+    }
+}
+
+fn visitor_hash(key: &[u8], token: &str, visitor: &str) -> String {
+    let digest = signing::hmac_sha256(key, &format!("{}\n{}", token, visitor));
+    signing::hex(&digest[..VISITOR_HASH_LEN])
+}
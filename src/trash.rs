@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::clock;
+
+pub const TRASH_DIR: &str = ".lunafinder_trash";
+
+const RECORD_EXTENSION: &str = "json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedEntry {
+    pub id: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub deleted_at: u64,
+    #[serde(default)]
+    pub deleted_by: Option<String>,
+}
+
+pub fn discard(
+    base: &Path,
+    relative: &Path,
+    source: &Path,
+    username: Option<&str>,
+) -> Result<TrashedEntry> {
+    let metadata = fs::symlink_metadata(source)
+        .with_context(|| format!("Failed to read entry: {:?}", source))?;
+    let root = base.join(TRASH_DIR);
+    fs::create_dir_all(&root)
+        .with_context(|| format!("Failed to create trash directory: {:?}", root))?;
+    let deleted_at = clock::now_unix();
+    let entry = TrashedEntry {
+        id: format!("{}-{:08x}", deleted_at, rand::random::<u32>()),
+        path: crate::osname::encode_path(relative),
+        is_dir: metadata.is_dir(),
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        deleted_at,
+        deleted_by: username.map(str::to_string),
+    };
+    fs::rename(source, root.join(&entry.id))
+        .with_context(|| format!("Failed to move {:?} to the trash", relative))?;
+    fs::write(record_file(base, &entry.id), serde_json::to_string(&entry)?)
+        .with_context(|| format!("Failed to record trashed entry: {:?}", relative))?;
+    Ok(entry)
+}
+
+pub fn list(base: &Path) -> Result<Vec<TrashedEntry>> {
+    let root = base.join(TRASH_DIR);
+    let mut entries = Vec::new();
+    if !root.is_dir() {
+        return Ok(entries);
+    }
+    for item in fs::read_dir(&root).with_context(|| format!("Failed to read trash: {:?}", root))? {
+        let path = item?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(RECORD_EXTENSION) {
+            continue;
+        }
+        let Some(entry) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<TrashedEntry>(&content).ok())
+        else {
+            continue;
+        };
+        if fs::symlink_metadata(root.join(&entry.id)).is_err() {
+            let _ = fs::remove_file(&path);
+            continue;
+        }
+        entries.push(entry);
+    }
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(a.id.cmp(&b.id)));
+    Ok(entries)
+}
+
+pub fn get(base: &Path, id: &str) -> Result<TrashedEntry> {
+    if !is_valid_id(id) {
+        return Err(anyhow!("Invalid trash entry: {}", id));
+    }
+    let content = fs::read_to_string(record_file(base, id))
+        .with_context(|| format!("Trash entry not found: {}", id))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+pub fn restore(base: &Path, entry: &TrashedEntry, destination: &Path) -> Result<()> {
+    if fs::symlink_metadata(destination).is_ok() {
+        return Err(anyhow!("An entry already exists at {}", entry.path));
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+    fs::rename(entry_path(base, entry), destination)
+        .with_context(|| format!("Failed to restore {}", entry.path))?;
+    let _ = fs::remove_file(record_file(base, &entry.id));
+    Ok(())
+}
+
+pub fn purge(base: &Path, entry: &TrashedEntry) -> Result<()> {
+    let item = entry_path(base, entry);
+    if entry.is_dir {
+        fs::remove_dir_all(&item)
+    } else {
+        fs::remove_file(&item)
+    }
+    .with_context(|| format!("Failed to delete {} from the trash", entry.path))?;
+    let _ = fs::remove_file(record_file(base, &entry.id));
+    Ok(())
+}
+
+pub fn empty(base: &Path) -> Result<()> {
+    let root = base.join(TRASH_DIR);
+    if root.exists() {
+        fs::remove_dir_all(&root).with_context(|| format!("Failed to empty trash: {:?}", root))?;
+    }
+    Ok(())
+}
+
+pub fn entry_path(base: &Path, entry: &TrashedEntry) -> PathBuf {
+    base.join(TRASH_DIR).join(&entry.id)
+}
+
+fn record_file(base: &Path, id: &str) -> PathBuf {
+    base.join(TRASH_DIR)
+        .join(format!("{}.{}", id, RECORD_EXTENSION))
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::clock;
+
+pub const SESSION_TTL_SECS: u64 = 7 * 24 * 3600;
+
+const UPLOADS_DIR: &str = "uploads";
+const PART_EXTENSION: &str = "part";
+const SESSION_EXTENSION: &str = "json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub id: String,
+    pub mount: String,
+    pub path: String,
+    pub size: u64,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub overwrite: bool,
+    pub created_at: u64,
+}
+
+impl UploadSession {
+    pub fn is_expired(&self) -> bool {
+        self.created_at + SESSION_TTL_SECS <= clock::now_unix()
+    }
+}
+
+pub struct UploadStore {
+    root: PathBuf,
+    active: Mutex<HashSet<String>>,
+}
+
+pub struct ActiveUpload<'a> {
+    store: &'a UploadStore,
+    id: String,
+}
+
+impl Drop for ActiveUpload<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.store.active.lock() {
+            active.remove(&self.id);
+        }
+    }
+}
+
+impl UploadStore {
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let root = data_dir.join(UPLOADS_DIR);
+        fs::create_dir_all(&root)
+            .with_context(|| format!("Failed to create upload directory: {:?}", root))?;
+        let store = Self {
+            root,
+            active: Mutex::new(HashSet::new()),
+        };
+        store.expire();
+        Ok(store)
+    }
+
+    pub fn create(
+        &self,
+        mount: &str,
+        path: &str,
+        size: u64,
+        username: Option<&str>,
+        overwrite: bool,
+    ) -> Result<UploadSession> {
+        let session = UploadSession {
+            id: format!("{:032x}", rand::random::<u128>()),
+            mount: mount.to_string(),
+            path: path.to_string(),
+            size,
+            username: username.map(str::to_string),
+            overwrite,
+            created_at: clock::now_unix(),
+        };
+        fs::File::create(self.part_path(&session.id))
+            .with_context(|| format!("Failed to create upload part: {}", session.id))?;
+        fs::write(
+            self.session_path(&session.id),
+            serde_json::to_string(&session)?,
+        )
+        .with_context(|| format!("Failed to save upload session: {}", session.id))?;
+        Ok(session)
+    }
+
+    pub fn get(&self, id: &str) -> Option<UploadSession> {
+        if !is_valid_id(id) {
+            return None;
+        }
+        let content = fs::read_to_string(self.session_path(id)).ok()?;
+        serde_json::from_str::<UploadSession>(&content)
+            .ok()
+            .filter(|session| !session.is_expired())
+    }
+
+    pub fn offset(&self, id: &str) -> u64 {
+        fs::metadata(self.part_path(id))
+            .map(|metadata| metadata.len())
+            .unwrap_or(0)
+    }
+
+    pub fn part_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", id, PART_EXTENSION))
+    }
+
+    pub fn begin(&self, id: &str) -> Result<ActiveUpload<'_>> {
+        let mut active = self
+            .active
+            .lock()
+            .map_err(|_| anyhow!("Upload lock poisoned"))?;
+        if !active.insert(id.to_string()) {
+            return Err(anyhow!("Another request is writing to upload {}", id));
+        }
+        Ok(ActiveUpload {
+            store: self,
+            id: id.to_string(),
+        })
+    }
+
+    pub fn remove(&self, id: &str) {
+        let _ = fs::remove_file(self.part_path(id));
+        let _ = fs::remove_file(self.session_path(id));
+    }
+
+    pub fn expire(&self) {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SESSION_EXTENSION) {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if self.get(id).is_none() {
+                log::info!("Removing expired upload session {}", id);
+                self.remove(id);
+            }
+        }
+    }
+
+    fn session_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", id, SESSION_EXTENSION))
+    }
+}
+
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
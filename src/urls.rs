@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::path::Path;
+use tera::Value;
+
+use crate::osname;
+
+pub fn segment(value: &str) -> String {
+    urlencoding::encode(value).into_owned()
+}
+
+pub fn path(link: &str) -> String {
+    link.split('/').map(segment).collect::<Vec<_>>().join("/")
+}
+
+pub fn mount(prefix: &str, mount: &str) -> String {
+    format!("/{}/{}", prefix, segment(mount))
+}
+
+pub fn entry(prefix: &str, mount_name: &str, relative: &Path) -> String {
+    format!(
+        "{}/{}",
+        mount(prefix, mount_name),
+        path(&osname::encode_path(relative))
+    )
+}
+
+pub fn segment_filter(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let value = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("Filter `url_segment` expects a string"))?;
+    Ok(Value::String(segment(value)))
+}
+
+pub fn path_filter(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let value = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("Filter `url_path` expects a string"))?;
+    Ok(Value::String(path(value)))
+}
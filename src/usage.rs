@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::clock;
+
+pub const CACHE_TTL_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Usage {
+    pub directories: u64,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+pub fn measure(root: &Path) -> Result<Usage> {
+    let mut usage = Usage::default();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let listing = match fs::read_dir(&directory) {
+            Ok(listing) => listing,
+            Err(e) if directory == root => {
+                return Err(e).with_context(|| format!("Failed to read directory: {:?}", directory))
+            }
+            Err(_) => continue,
+        };
+        for entry in listing {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if crate::is_reserved_name(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let Ok(metadata) = fs::symlink_metadata(entry.path()) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                usage.directories += 1;
+                pending.push(entry.path());
+            } else if metadata.is_file() {
+                usage.files += 1;
+                usage.bytes += metadata.len();
+            }
+        }
+    }
+    Ok(usage)
+}
+
+#[derive(Default)]
+pub struct UsageCache {
+    entries: Mutex<HashMap<String, (u64, Usage)>>,
+}
+
+impl UsageCache {
+    pub fn get(&self, mount: &str) -> Option<Usage> {
+        let now = clock::now_unix();
+        self.entries
+            .lock()
+            .ok()?
+            .get(mount)
+            .filter(|(measured, _)| measured + CACHE_TTL_SECS > now)
+            .map(|(_, usage)| *usage)
+    }
+
+    pub fn insert(&self, mount: &str, usage: Usage) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(mount.to_string(), (clock::now_unix(), usage));
+        }
+    }
+}
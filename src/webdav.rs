@@ -0,0 +1,687 @@
+use actix_web::error::PayloadError;
+use actix_web::http::header::{self, HttpDate};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context as _;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use futures_util::{Stream, TryStreamExt as _};
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::auth::verify_password;
+use crate::config::Permission;
+use crate::error::LunaError;
+use crate::{clock, content, moderation, osname, stats, storage, trash, urls};
+use crate::{ActixResult, AppState, DavTarget};
+
+pub const ALLOW: &str =
+    "OPTIONS, PROPFIND, GET, HEAD, PUT, MKCOL, MOVE, COPY, DELETE, LOCK, UNLOCK";
+const REALM: &str = "LunaFinder";
+const LOCK_TIMEOUT_SECS: u64 = 3600;
+const LOCK_TOKEN_PREFIX: &str = "opaquelocktoken:";
+
+pub struct Resource {
+    pub href: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<u64>,
+}
+
+impl Resource {
+    pub fn new(mount: &str, relative: &Path, metadata: &Metadata) -> Self {
+        let link = crate::pathbuf_to_string(relative);
+        let is_dir = metadata.is_dir();
+        let mut href = urls::mount("dav", mount);
+        if !link.is_empty() {
+            href.push('/');
+            href.push_str(&urls::path(&link));
+        }
+        if is_dir {
+            href.push('/');
+        }
+        Self {
+            href,
+            name: relative
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| mount.to_string()),
+            is_dir,
+            size: if is_dir { 0 } else { metadata.len() },
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+        }
+    }
+}
+
+struct Lock {
+    token: String,
+    owner: String,
+    expires: u64,
+}
+
+#[derive(Default)]
+pub struct LockTable {
+    locks: Mutex<HashMap<(String, PathBuf), Lock>>,
+}
+
+impl LockTable {
+    fn acquire(
+        &self,
+        mount: &str,
+        relative: &Path,
+        owner: &str,
+        submitted: &[String],
+    ) -> Option<String> {
+        let mut locks = self.locks.lock().ok()?;
+        let now = clock::now_unix();
+        locks.retain(|_, lock| lock.expires > now);
+        let key = (mount.to_string(), relative.to_path_buf());
+        if let Some(lock) = locks.get_mut(&key) {
+            if lock.owner != owner || !submitted.contains(&lock.token) {
+                return None;
+            }
+            lock.expires = now + LOCK_TIMEOUT_SECS;
+            return Some(lock.token.clone());
+        }
+        let token = format!("{}{:032x}", LOCK_TOKEN_PREFIX, rand::random::<u128>());
+        locks.insert(
+            key,
+            Lock {
+                token: token.clone(),
+                owner: owner.to_string(),
+                expires: now + LOCK_TIMEOUT_SECS,
+            },
+        );
+        Some(token)
+    }
+
+    fn release(&self, mount: &str, relative: &Path, owner: &str, token: &str) -> bool {
+        let Ok(mut locks) = self.locks.lock() else {
+            return false;
+        };
+        let key = (mount.to_string(), relative.to_path_buf());
+        if !locks
+            .get(&key)
+            .is_some_and(|lock| lock.owner == owner && lock.token == token)
+        {
+            return false;
+        }
+        locks.remove(&key);
+        true
+    }
+
+    pub fn conflicts(
+        &self,
+        mount: &str,
+        relative: &Path,
+        username: Option<&str>,
+        submitted: &[String],
+    ) -> bool {
+        let Ok(locks) = self.locks.lock() else {
+            return false;
+        };
+        let now = clock::now_unix();
+        locks.iter().any(|((locked_mount, path), lock)| {
+            locked_mount == mount
+                && (path.starts_with(relative) || relative.starts_with(path))
+                && lock.expires > now
+                && !(username == Some(lock.owner.as_str()) && submitted.contains(&lock.token))
+        })
+    }
+}
+
+pub fn credentials(req: &HttpRequest) -> Option<(String, String)> {
+    let value = req
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(value.trim()).ok()?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+pub fn challenge() -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .append_header((
+            header::WWW_AUTHENTICATE,
+            format!("Basic realm=\"{}\", charset=\"UTF-8\"", REALM),
+        ))
+        .finish()
+}
+
+pub fn depth(req: &HttpRequest) -> u8 {
+    match req
+        .headers()
+        .get("Depth")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+    {
+        Some("0") => 0,
+        _ => 1,
+    }
+}
+
+pub fn multistatus(resources: &[Resource]) -> String {
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for resource in resources {
+        body.push_str("<D:response><D:href>");
+        body.push_str(&escape(&resource.href));
+        body.push_str("</D:href><D:propstat><D:prop><D:displayname>");
+        body.push_str(&escape(&resource.name));
+        body.push_str("</D:displayname>");
+        if resource.is_dir {
+            body.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            body.push_str(&format!(
+                "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength><D:getcontenttype>{}</D:getcontenttype>",
+                resource.size,
+                escape(mime_guess::from_path(&resource.name).first_or_octet_stream().essence_str())
+            ));
+        }
+        if let Some(modified) = resource.modified {
+            let time = UNIX_EPOCH + std::time::Duration::from_secs(modified);
+            body.push_str(&format!(
+                "<D:getlastmodified>{}</D:getlastmodified><D:creationdate>{}</D:creationdate><D:getetag>\"{:x}-{:x}\"</D:getetag>",
+                HttpDate::from(time),
+                clock::format_iso8601(modified),
+                resource.size,
+                modified
+            ));
+        }
+        body.push_str(
+            "<D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>",
+        );
+        body.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+    }
+    body.push_str("</D:multistatus>\n");
+    body
+}
+
+fn lock_response(href: &str, owner: &str, token: &str) -> HttpResponse {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>0</D:depth><D:owner>{}</D:owner><D:timeout>Second-{}</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock></D:lockdiscovery></D:prop>\n",
+        escape(owner),
+        LOCK_TIMEOUT_SECS,
+        token,
+        escape(href)
+    );
+    HttpResponse::Ok()
+        .append_header(("Lock-Token", format!("<{}>", token)))
+        .content_type("application/xml; charset=utf-8")
+        .body(body)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub async fn handle(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    payload: web::Payload,
+) -> ActixResult<HttpResponse> {
+    let (mount_name, tail) = path.into_inner();
+    if req.method() == Method::OPTIONS {
+        return Ok(HttpResponse::Ok()
+            .append_header(("DAV", "1, 2"))
+            .append_header(("MS-Author-Via", "DAV"))
+            .append_header((header::ALLOW, ALLOW))
+            .finish());
+    }
+    let mount = state
+        .config
+        .mounts
+        .get(&mount_name)
+        .ok_or_else(|| LunaError::not_found("Mount not found"))?;
+
+    let username = match credentials(&req) {
+        Some((username, password)) => {
+            let valid = state.config.users.get(&username).is_some_and(|user| {
+                !user.password.is_empty()
+                    && !state.accounts.is_disabled(&state.config, &username)
+                    && verify_password(&password, &user.password, &user.hash_algorithm)
+            });
+            if !valid {
+                let source = req
+                    .peer_addr()
+                    .map(|addr| addr.ip().to_string())
+                    .unwrap_or_else(|| "unknown".to_string());
+                crate::record_failed_login(&state, "WebDAV", &username, &source);
+                return Ok(challenge());
+            }
+            if crate::needs_terms_acceptance(&state, &username) {
+                return Err(LunaError::forbidden(
+                    "Accept the terms of service in the web interface first",
+                )
+                .into());
+            }
+            Some(username)
+        }
+        None => None,
+    };
+
+    let relative = crate::normalize_raw_path(tail.trim_matches('/'))
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    let Some(permission) =
+        crate::effective_permission(&state, username.as_deref(), None, &mount_name, &relative)
+            .filter(Permission::allows_read)
+    else {
+        return denied(username.as_deref(), "Read permission required");
+    };
+    let base = crate::canonicalize_mount(&mount.path).map_err(LunaError::internal)?;
+    let path = crate::resolve_path(&base, &relative).map_err(LunaError::bad_request)?;
+    let target = DavTarget {
+        mount_name,
+        mount,
+        base,
+        relative,
+        path,
+        permission,
+        username,
+    };
+
+    let submitted = submitted_tokens(&req);
+    if matches!(req.method().as_str(), "PUT" | "DELETE" | "MKCOL" | "MOVE")
+        && state.dav_locks.conflicts(
+            &target.mount_name,
+            &target.relative,
+            target.username.as_deref(),
+            &submitted,
+        )
+    {
+        return Ok(locked());
+    }
+
+    match req.method().as_str() {
+        "PROPFIND" => propfind(&state, &req, target).await,
+        "GET" | "HEAD" => get(&state, &req, target),
+        "PUT" => put(&state, target, payload).await,
+        "MKCOL" => mkcol(&state, target),
+        "DELETE" => delete(&state, target).await,
+        "MOVE" | "COPY" => transfer(&state, &req, target, &submitted).await,
+        "LOCK" => lock(&state, &req, target, &submitted),
+        "UNLOCK" => unlock(&state, &req, target),
+        _ => Ok(HttpResponse::MethodNotAllowed()
+            .append_header((header::ALLOW, ALLOW))
+            .finish()),
+    }
+}
+
+fn denied(username: Option<&str>, message: &str) -> ActixResult<HttpResponse> {
+    match username {
+        Some(_) => Err(LunaError::forbidden(message).into()),
+        None => Ok(challenge()),
+    }
+}
+
+async fn propfind(
+    state: &AppState,
+    req: &HttpRequest,
+    target: DavTarget<'_>,
+) -> ActixResult<HttpResponse> {
+    let metadata =
+        fs::metadata(&target.path).map_err(|_| LunaError::not_found("Resource not found"))?;
+    let mut resources = vec![Resource::new(
+        &target.mount_name,
+        &target.relative,
+        &metadata,
+    )];
+    if metadata.is_dir() && depth(req) > 0 {
+        let mount_name = target.mount_name.clone();
+        let relative = target.relative.clone();
+        let path = target.path.clone();
+        let children = crate::run_blocking(state, move || {
+            let mut children = Vec::new();
+            for entry in fs::read_dir(&path)
+                .with_context(|| format!("Failed to read directory: {:?}", path))?
+            {
+                let entry = entry?;
+                if crate::is_reserved_name(&entry.file_name().to_string_lossy()) {
+                    continue;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                children.push(Resource::new(
+                    &mount_name,
+                    &relative.join(entry.file_name()),
+                    &metadata,
+                ));
+            }
+            Ok(children)
+        })
+        .await?;
+        resources.extend(children);
+    }
+    Ok(HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(multistatus(&resources)))
+}
+
+pub fn get(
+    state: &AppState,
+    req: &HttpRequest,
+    target: DavTarget<'_>,
+) -> ActixResult<HttpResponse> {
+    if target.path.is_dir() {
+        return Ok(HttpResponse::MethodNotAllowed()
+            .append_header((header::ALLOW, ALLOW))
+            .finish());
+    }
+    if !target.path.is_file() {
+        return Err(LunaError::not_found("Resource not found").into());
+    }
+    let (response, size) = crate::file_response(state, req, &target.mount_name, &target.path)
+        .map_err(LunaError::internal)?;
+    if req.method() != actix_web::http::Method::HEAD {
+        crate::record_access(
+            state,
+            &target.mount_name,
+            stats::EventKind::Download,
+            target.username,
+            size,
+        );
+    }
+    Ok(response)
+}
+
+pub async fn put(
+    state: &AppState,
+    target: DavTarget<'_>,
+    mut payload: impl Stream<Item = Result<web::Bytes, PayloadError>> + Unpin,
+) -> ActixResult<HttpResponse> {
+    crate::ensure_writable(state)?;
+    if !target.permission.allows_upload() {
+        return denied(target.username.as_deref(), "Write permission required");
+    }
+    if target.relative.as_os_str().is_empty() || target.path.is_dir() {
+        return Err(LunaError::conflict("A folder already exists at this path").into());
+    }
+
+    let replaced = target.path.is_file();
+    let moderated = target.mount.moderation && !target.permission.allows_moderate();
+    let destination = if moderated {
+        moderation::queue_root(&target.base).join(&target.relative)
+    } else {
+        target.path.clone()
+    };
+    let parent = destination
+        .parent()
+        .ok_or_else(|| LunaError::bad_request("Invalid path"))?;
+    if !parent.is_dir() {
+        if !moderated {
+            return Err(LunaError::conflict("Parent folder does not exist").into());
+        }
+        fs::create_dir_all(parent).map_err(LunaError::internal)?;
+    }
+
+    let limits = &state.config.server.limits;
+    let temp_path = crate::staging_path(state, parent, "dav");
+    let max_upload_size =
+        crate::max_upload_size(&state.config, target.mount, target.username.as_deref());
+    let mut file = storage::QueuedWriter::spawn(
+        storage::FileWriter::create(
+            crate::staged_codec(state, &target.mount_name, &destination),
+            &temp_path,
+        )
+        .map_err(LunaError::internal)?,
+        state.config.server.uploads.queue_chunks,
+    );
+    let mut head = Vec::new();
+    let mut written: u64 = 0;
+    let result: ActixResult<()> = async {
+        while let Some(chunk) = payload.try_next().await.map_err(LunaError::internal)? {
+            written += chunk.len() as u64;
+            crate::check_upload_limits(limits, written, written)?;
+            crate::check_upload_size(
+                &state.config,
+                max_upload_size,
+                written,
+                target.username.as_deref(),
+            )?;
+            if head.len() < content::SNIFF_LEN {
+                let take = (content::SNIFF_LEN - head.len()).min(chunk.len());
+                head.extend_from_slice(&chunk[..take]);
+            }
+            file.write(chunk).await.map_err(LunaError::internal)?;
+        }
+        if let Err(category) = content::check(
+            &target.mount.allow_content,
+            &target.mount.deny_content,
+            &head,
+        ) {
+            return Err(LunaError::unsupported_media_type(format!(
+                "Uploading {} content to this mount is not allowed",
+                category
+            ))
+            .into());
+        }
+        Ok(())
+    }
+    .await;
+    let result = match result {
+        Ok(()) => file
+            .finish()
+            .await
+            .map_err(|e| LunaError::internal(e).into()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result.and_then(|_| {
+        storage::persist(&temp_path, &destination).map_err(|e| LunaError::internal(e).into())
+    }) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    crate::finish_stored_upload(state, &target, &destination, written).await?;
+
+    if replaced {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::Created().finish())
+    }
+}
+
+pub fn mkcol(state: &AppState, target: DavTarget<'_>) -> ActixResult<HttpResponse> {
+    crate::ensure_writable(state)?;
+    if !target.permission.allows_create_folder() {
+        return denied(
+            target.username.as_deref(),
+            "Create folder permission required",
+        );
+    }
+    if fs::symlink_metadata(&target.path).is_ok() {
+        return Ok(HttpResponse::MethodNotAllowed()
+            .append_header((header::ALLOW, ALLOW))
+            .finish());
+    }
+    if !target.path.parent().is_some_and(Path::is_dir) {
+        return Err(LunaError::conflict("Parent folder does not exist").into());
+    }
+    fs::create_dir(&target.path).map_err(LunaError::internal)?;
+    crate::publish_file_event(
+        state,
+        &target.mount_name,
+        "create_folder",
+        &target.relative,
+        target.username.as_deref(),
+    );
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn delete(state: &AppState, target: DavTarget<'_>) -> ActixResult<HttpResponse> {
+    crate::ensure_writable(state)?;
+    if !target.permission.allows_delete() {
+        return denied(target.username.as_deref(), "Delete permission required");
+    }
+    if target.relative.as_os_str().is_empty() {
+        return Err(LunaError::forbidden("The mount root cannot be deleted").into());
+    }
+    let metadata = fs::symlink_metadata(&target.path)
+        .map_err(|_| LunaError::not_found("Resource not found"))?;
+    let path = target.path.clone();
+    if target.mount.trash {
+        let base = target.base.clone();
+        let relative = target.relative.clone();
+        let username = target.username.clone();
+        crate::run_blocking(state, move || {
+            trash::discard(&base, &relative, &path, username.as_deref())
+        })
+        .await?;
+    } else {
+        crate::run_blocking(state, move || {
+            if metadata.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            }
+            .with_context(|| format!("Failed to delete {:?}", path))
+        })
+        .await?;
+        crate::schedule_blob_gc(state, target.mount, &target.base);
+    }
+    crate::publish_file_event(
+        state,
+        &target.mount_name,
+        "delete",
+        &target.relative,
+        target.username.as_deref(),
+    );
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn transfer(
+    state: &AppState,
+    req: &HttpRequest,
+    target: DavTarget<'_>,
+    submitted: &[String],
+) -> ActixResult<HttpResponse> {
+    if target.username.is_none() {
+        return Ok(challenge());
+    }
+    let (destination_mount, destination) = req
+        .headers()
+        .get("Destination")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            let value = match value.split_once("://") {
+                Some((_, rest)) => &rest[rest.find('/')?..],
+                None => value,
+            };
+            let value = urlencoding::decode(value).ok()?;
+            let path = value.strip_prefix("/dav/")?;
+            let (mount, rest) = path.split_once('/').unwrap_or((path, ""));
+            let relative = crate::normalize_raw_path(rest.trim_matches('/'))?;
+            Some((mount.to_string(), relative))
+        })
+        .ok_or_else(|| LunaError::bad_request("A valid destination is required"))?;
+    if state.dav_locks.conflicts(
+        &destination_mount,
+        &destination,
+        target.username.as_deref(),
+        submitted,
+    ) {
+        return Ok(locked());
+    }
+    let overwrite = !matches!(
+        req.headers()
+            .get("Overwrite")
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim),
+        Some("F") | Some("f")
+    );
+    let body = crate::ApiTransferRequest {
+        path: osname::encode_path(&target.relative),
+        destination: Some(format!(
+            "{}/{}",
+            destination_mount,
+            osname::encode_path(&destination)
+        )),
+        overwrite: Some(overwrite),
+        depth: None,
+        confirm: None,
+    };
+    crate::api_transfer(
+        state,
+        req,
+        target.username,
+        target.mount_name,
+        body,
+        req.method().as_str() == "MOVE",
+    )
+    .await
+}
+
+fn lock(
+    state: &AppState,
+    req: &HttpRequest,
+    target: DavTarget<'_>,
+    submitted: &[String],
+) -> ActixResult<HttpResponse> {
+    let Some(username) = target.username.as_deref() else {
+        return Ok(challenge());
+    };
+    crate::ensure_writable(state)?;
+    if !target.permission.allows_upload() {
+        return denied(Some(username), "Write permission required");
+    }
+    let Some(token) =
+        state
+            .dav_locks
+            .acquire(&target.mount_name, &target.relative, username, submitted)
+    else {
+        return Ok(locked());
+    };
+    Ok(lock_response(&req.uri().to_string(), username, &token))
+}
+
+fn unlock(state: &AppState, req: &HttpRequest, target: DavTarget<'_>) -> ActixResult<HttpResponse> {
+    let Some(username) = target.username.as_deref() else {
+        return Ok(challenge());
+    };
+    let released = req
+        .headers()
+        .get("Lock-Token")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().trim_start_matches('<').trim_end_matches('>'))
+        .is_some_and(|token| {
+            state
+                .dav_locks
+                .release(&target.mount_name, &target.relative, username, token)
+        });
+    if !released {
+        return Err(LunaError::conflict("No matching lock for this resource").into());
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn locked() -> HttpResponse {
+    HttpResponse::build(StatusCode::LOCKED).finish()
+}
+
+pub fn submitted_tokens(req: &HttpRequest) -> Vec<String> {
+    ["If", "Lock-Token"]
+        .iter()
+        .filter_map(|name| req.headers().get(*name)?.to_str().ok())
+        .flat_map(|value| value.split('<').skip(1))
+        .filter_map(|part| part.split_once('>').map(|(token, _)| token))
+        .filter(|token| token.starts_with(LOCK_TOKEN_PREFIX))
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,207 @@
+use pulldown_cmark::{html, CowStr, Event, LinkType, Options, Parser, Tag};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::urls;
+
+const PAGE_EXTENSION: &str = "md";
+const SAFE_SCHEMES: [&str; 2] = ["http", "https"];
+
+pub struct Site<'a> {
+    pub base: &'a Path,
+    pub mount: &'a str,
+    pub query: &'a str,
+}
+
+impl Site<'_> {
+    fn link(&self, target: PathBuf, fragment: Option<&str>, page: bool) -> (String, bool) {
+        let target = if page
+            && target.extension().is_none()
+            && fs::symlink_metadata(self.base.join(&target)).is_err()
+        {
+            target.with_extension(PAGE_EXTENSION)
+        } else {
+            target
+        };
+        if page && is_page(&target) && fs::symlink_metadata(self.base.join(&target)).is_err() {
+            return (urls::entry("edit", self.mount, &target), true);
+        }
+        let mut href = format!(
+            "{}{}",
+            urls::entry("browse", self.mount, &target),
+            self.query
+        );
+        if let Some(fragment) = fragment {
+            href.push('#');
+            href.push_str(fragment);
+        }
+        (href, false)
+    }
+}
+
+pub fn is_page(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(PAGE_EXTENSION))
+}
+
+pub fn render(markdown: &str, page_dir: &Path, site: &Site) -> String {
+    let expanded = expand_wiki_links(markdown);
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+
+    let events = Parser::new_ext(&expanded, options).map(|event| match event {
+        Event::Html(raw) => Event::Text(raw),
+        Event::Start(Tag::Link(link_type, destination, title)) if !is_safe(&destination) => {
+            Event::Start(Tag::Link(link_type, CowStr::Borrowed(""), title))
+        }
+        Event::Start(Tag::Image(link_type, destination, title)) if !is_safe(&destination) => {
+            Event::Start(Tag::Image(link_type, CowStr::Borrowed(""), title))
+        }
+        Event::Start(Tag::Link(link_type, destination, title))
+            if !matches!(link_type, LinkType::Autolink | LinkType::Email) =>
+        {
+            let Some((target, fragment)) = resolve(page_dir, &destination) else {
+                return Event::Start(Tag::Link(link_type, destination, title));
+            };
+            match site.link(target, fragment, true) {
+                (href, true) => Event::Html(CowStr::from(format!(
+                    "<a class=\"missing\" href=\"{}\" title=\"새 페이지 만들기\">",
+                    tera::escape_html(&href)
+                ))),
+                (href, false) => Event::Start(Tag::Link(link_type, CowStr::from(href), title)),
+            }
+        }
+        Event::Start(Tag::Image(link_type, destination, title)) => {
+            match resolve(page_dir, &destination) {
+                Some((target, fragment)) => {
+                    let (href, _) = site.link(target, fragment, false);
+                    Event::Start(Tag::Image(link_type, CowStr::from(href), title))
+                }
+                None => Event::Start(Tag::Image(link_type, destination, title)),
+            }
+        }
+        other => other,
+    });
+
+    let mut output = String::new();
+    html::push_html(&mut output, events);
+    output
+}
+
+fn scheme(destination: &str) -> Option<&str> {
+    destination
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| {
+            scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        })
+}
+
+fn is_safe(destination: &str) -> bool {
+    let prefix = destination
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default();
+    let Some((scheme, _)) = prefix.split_once(':') else {
+        return true;
+    };
+    let scheme = scheme
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .collect::<String>();
+    SAFE_SCHEMES
+        .iter()
+        .any(|safe| safe.eq_ignore_ascii_case(&scheme))
+}
+
+fn resolve<'a>(page_dir: &Path, destination: &'a str) -> Option<(PathBuf, Option<&'a str>)> {
+    if destination.is_empty() || destination.starts_with('#') || destination.starts_with("//") {
+        return None;
+    }
+    if scheme(destination).is_some() {
+        return None;
+    }
+
+    let (path, fragment) = match destination.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (destination, None),
+    };
+    let path = path.split('?').next().unwrap_or_default();
+    let path = urlencoding::decode(path).ok()?;
+
+    let mut resolved = if path.starts_with('/') {
+        PathBuf::new()
+    } else {
+        page_dir.to_path_buf()
+    };
+    for component in Path::new(path.as_ref()).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir | Component::RootDir => {}
+            Component::Prefix(_) => return None,
+        }
+    }
+    if resolved.as_os_str().is_empty() {
+        return None;
+    }
+    Some((resolved, fragment))
+}
+
+fn expand_wiki_links(markdown: &str) -> String {
+    let mut output = String::with_capacity(markdown.len());
+    let mut fenced = false;
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fenced = !fenced;
+        }
+        if fenced || !line.contains("[[") {
+            output.push_str(line);
+            continue;
+        }
+
+        let mut rest = line;
+        while let Some(start) = rest.find("[[") {
+            let Some(length) = rest[start + 2..].find("]]") else {
+                break;
+            };
+            let end = start + 2 + length + 2;
+            let inner = &rest[start + 2..start + 2 + length];
+            let (target, label) = inner.split_once('|').unwrap_or((inner, inner));
+            let (target, label) = (target.trim(), label.trim());
+            output.push_str(&rest[..start]);
+            if target.is_empty() || target.contains(['<', '>', '[', ']']) {
+                output.push_str(&rest[start..end]);
+            } else {
+                let (page, fragment) = match target.split_once('#') {
+                    Some((page, fragment)) => (page, Some(fragment)),
+                    None => (target, None),
+                };
+                output.push('[');
+                output.push_str(&label.replace('[', "\\[").replace(']', "\\]"));
+                output.push_str("](<");
+                output.push_str(page);
+                if !page.is_empty() && !is_page(Path::new(page)) {
+                    output.push('.');
+                    output.push_str(PAGE_EXTENSION);
+                }
+                if let Some(fragment) = fragment {
+                    output.push('#');
+                    output.push_str(fragment);
+                }
+                output.push_str(">)");
+            }
+            rest = &rest[end..];
+        }
+        output.push_str(rest);
+    }
+    output
+}